@@ -0,0 +1,73 @@
+use axum::http::{header, HeaderMap};
+use sha2::{Digest, Sha256};
+
+pub fn sha256(input: impl AsRef<[u8]>) -> String {
+    format!("{:x}", Sha256::digest(input))
+}
+
+/// Builds an ETag for a request against a given version of the data. Responses only depend on
+/// the data and the request, so the same request against the same data gets the same ETag.
+pub fn etag(
+    data_version: &str,
+    query: &str,
+    operation_name: Option<&str>,
+    variables: &str,
+) -> String {
+    let mut hasher = Sha256::new();
+    for part in [
+        data_version,
+        query,
+        operation_name.unwrap_or_default(),
+        variables,
+    ] {
+        hasher.update(part);
+        hasher.update([0]);
+    }
+
+    format!(r#""{:x}""#, hasher.finalize())
+}
+
+/// Whether the client already has the response with this ETag, per `If-None-Match`
+pub fn is_fresh(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|value| value.trim().trim_start_matches("W/"))
+        .any(|value| value == "*" || value == etag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn it_changes_etag_with_data_version() {
+        let query = "{ cards { aspect } }";
+
+        assert_eq!(
+            etag("v1", query, None, "null"),
+            etag("v1", query, None, "null")
+        );
+        assert_ne!(
+            etag("v1", query, None, "null"),
+            etag("v2", query, None, "null")
+        );
+    }
+
+    #[test]
+    fn it_matches_if_none_match() {
+        let etag = etag("v1", "{ cards { aspect } }", None, "null");
+        let mut headers = HeaderMap::new();
+
+        assert!(!is_fresh(&headers, &etag));
+
+        headers.insert(
+            header::IF_NONE_MATCH,
+            HeaderValue::from_str(&format!(r#""other", W/{etag}"#)).unwrap(),
+        );
+        assert!(is_fresh(&headers, &etag));
+    }
+}
@@ -0,0 +1,426 @@
+use crate::limits::QueryLimits;
+use axum::http::{HeaderValue, Method};
+use serde::Deserialize;
+use std::{
+    env, fs, io,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+use thiserror::Error;
+use tracing_subscriber::EnvFilter;
+
+/// Config file used when `SHQ_CONFIG` isn't set, if it exists
+const DEFAULT_CONFIG_PATH: &str = "shield-hq.toml";
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("Could not read config file {path}: {source}")]
+    Read { path: PathBuf, source: io::Error },
+    #[error("Invalid config file {path}: {source}")]
+    Parse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    #[error("Invalid value for {name}: {value}")]
+    Env { name: &'static str, value: String },
+    #[error("{0} must be set along with {1}")]
+    MissingEnv(&'static str, &'static str),
+    #[error("Invalid log level: {0}")]
+    LogLevel(String),
+    #[error("Invalid CORS origin {0}, expected * or an origin like https://example.com")]
+    CorsOrigin(String),
+    #[error("Invalid CORS method: {0}")]
+    CorsMethod(String),
+    #[error("TLS {0} file does not exist: {1}")]
+    TlsFile(&'static str, PathBuf),
+    #[error("Data directory does not exist: {0}")]
+    DataDir(PathBuf),
+    #[error("Persisted queries directory does not exist: {0}")]
+    PersistedQueriesDir(PathBuf),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub address: IpAddr,
+    pub port: u16,
+    pub log_level: String,
+    pub log_format: LogFormat,
+    /// Directory with the TOML data. The data built into the binary is used when it isn't set.
+    pub data_dir: Option<PathBuf>,
+    /// SQLite database cards and products are served from, filled from the data whenever it's
+    /// loaded. They're served from memory when it isn't set.
+    pub database: Option<PathBuf>,
+    /// File campaign logs are saved to. They're only kept in memory when it isn't set.
+    pub campaign_logs: Option<PathBuf>,
+    /// File logged plays are saved to. They're only kept in memory when it isn't set.
+    pub plays: Option<PathBuf>,
+    pub graphiql: bool,
+    pub persisted_queries_dir: Option<PathBuf>,
+    pub cors: CorsConfig,
+    pub tls: Option<TlsConfig>,
+    pub limits: QueryLimits,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CorsConfig {
+    pub allowed_origins: Vec<String>,
+    pub allowed_methods: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TlsConfig {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            address: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            port: 3000,
+            log_level: String::from("info"),
+            log_format: LogFormat::default(),
+            data_dir: None,
+            database: None,
+            campaign_logs: None,
+            plays: None,
+            graphiql: true,
+            persisted_queries_dir: None,
+            cors: CorsConfig::default(),
+            tls: None,
+            limits: QueryLimits::default(),
+        }
+    }
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self {
+            allowed_origins: vec![],
+            allowed_methods: vec![String::from("GET"), String::from("POST")],
+        }
+    }
+}
+
+fn parse_env<T: FromStr>(name: &'static str, value: String) -> Result<T, ConfigError> {
+    value.parse().map_err(|_| ConfigError::Env { name, value })
+}
+
+fn split(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+impl Config {
+    /// Loads the config file from `SHQ_CONFIG` or `shield-hq.toml`, then applies any `SHQ_*`
+    /// environment overrides and validates the result.
+    pub fn load() -> Result<Self, ConfigError> {
+        let path = env::var_os("SHQ_CONFIG").map(PathBuf::from).or_else(|| {
+            let path = PathBuf::from(DEFAULT_CONFIG_PATH);
+            path.exists().then_some(path)
+        });
+
+        Self::load_from(path.as_deref(), |name| env::var(name).ok())
+    }
+
+    pub fn load_from(
+        path: Option<&Path>,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self, ConfigError> {
+        let mut config = match path {
+            Some(path) => {
+                let source = fs::read_to_string(path).map_err(|source| ConfigError::Read {
+                    path: path.to_path_buf(),
+                    source,
+                })?;
+                toml::from_str(&source).map_err(|source| ConfigError::Parse {
+                    path: path.to_path_buf(),
+                    source,
+                })?
+            }
+            None => Config::default(),
+        };
+
+        config.apply_env(env)?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    fn apply_env(&mut self, env: impl Fn(&str) -> Option<String>) -> Result<(), ConfigError> {
+        if let Some(value) = env("SHQ_ADDRESS") {
+            self.address = parse_env("SHQ_ADDRESS", value)?;
+        }
+        if let Some(value) = env("SHQ_PORT") {
+            self.port = parse_env("SHQ_PORT", value)?;
+        }
+        if let Some(value) = env("SHQ_LOG_LEVEL") {
+            self.log_level = value;
+        }
+        if let Some(value) = env("SHQ_LOG_FORMAT") {
+            self.log_format = parse_env("SHQ_LOG_FORMAT", value)?;
+        }
+        if let Some(value) = env("SHQ_DATA_DIR") {
+            self.data_dir = Some(PathBuf::from(value));
+        }
+        if let Some(value) = env("SHQ_DATABASE") {
+            self.database = Some(PathBuf::from(value));
+        }
+        if let Some(value) = env("SHQ_CAMPAIGN_LOGS") {
+            self.campaign_logs = Some(PathBuf::from(value));
+        }
+        if let Some(value) = env("SHQ_PLAYS") {
+            self.plays = Some(PathBuf::from(value));
+        }
+        if let Some(value) = env("SHQ_GRAPHIQL") {
+            self.graphiql = parse_env("SHQ_GRAPHIQL", value)?;
+        }
+        if let Some(value) = env("SHQ_PERSISTED_QUERIES_DIR") {
+            self.persisted_queries_dir = Some(PathBuf::from(value));
+        }
+        if let Some(value) = env("SHQ_CORS_ALLOWED_ORIGINS") {
+            self.cors.allowed_origins = split(&value);
+        }
+        if let Some(value) = env("SHQ_CORS_ALLOWED_METHODS") {
+            self.cors.allowed_methods = split(&value);
+        }
+        match (env("SHQ_TLS_CERT"), env("SHQ_TLS_KEY")) {
+            (Some(cert), Some(key)) => {
+                self.tls = Some(TlsConfig {
+                    cert: PathBuf::from(cert),
+                    key: PathBuf::from(key),
+                })
+            }
+            (None, None) => (),
+            (Some(_), None) => return Err(ConfigError::MissingEnv("SHQ_TLS_KEY", "SHQ_TLS_CERT")),
+            (None, Some(_)) => return Err(ConfigError::MissingEnv("SHQ_TLS_CERT", "SHQ_TLS_KEY")),
+        }
+        if let Some(value) = env("SHQ_MAX_QUERY_DEPTH") {
+            self.limits.max_depth = parse_env("SHQ_MAX_QUERY_DEPTH", value)?;
+        }
+        if let Some(value) = env("SHQ_MAX_QUERY_COMPLEXITY") {
+            self.limits.max_complexity = parse_env("SHQ_MAX_QUERY_COMPLEXITY", value)?;
+        }
+        if let Some(value) = env("SHQ_MAX_BATCH_SIZE") {
+            self.limits.max_batch_size = parse_env("SHQ_MAX_BATCH_SIZE", value)?;
+        }
+        if let Some(value) = env("SHQ_QUERY_TIMEOUT_MS") {
+            self.limits.timeout = Duration::from_millis(parse_env("SHQ_QUERY_TIMEOUT_MS", value)?);
+        }
+
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        self.env_filter()?;
+        self.cors_origins()?;
+        self.cors_methods()?;
+        if let Some(tls) = &self.tls {
+            if !tls.cert.is_file() {
+                return Err(ConfigError::TlsFile("cert", tls.cert.clone()));
+            }
+            if !tls.key.is_file() {
+                return Err(ConfigError::TlsFile("key", tls.key.clone()));
+            }
+        }
+        if let Some(data_dir) = &self.data_dir {
+            if !data_dir.is_dir() {
+                return Err(ConfigError::DataDir(data_dir.clone()));
+            }
+        }
+        if let Some(persisted_queries_dir) = &self.persisted_queries_dir {
+            if !persisted_queries_dir.is_dir() {
+                return Err(ConfigError::PersistedQueriesDir(
+                    persisted_queries_dir.clone(),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn socket_addr(&self) -> SocketAddr {
+        SocketAddr::new(self.address, self.port)
+    }
+
+    pub fn env_filter(&self) -> Result<EnvFilter, ConfigError> {
+        EnvFilter::try_new(&self.log_level)
+            .map_err(|_| ConfigError::LogLevel(self.log_level.clone()))
+    }
+
+    /// Allowed CORS origins, where `None` allows any origin
+    pub fn cors_origins(&self) -> Result<Option<Vec<HeaderValue>>, ConfigError> {
+        if self.cors.allowed_origins.iter().any(|origin| origin == "*") {
+            return if self.cors.allowed_origins.len() == 1 {
+                Ok(None)
+            } else {
+                Err(ConfigError::CorsOrigin(self.cors.allowed_origins.join(",")))
+            };
+        }
+
+        self.cors
+            .allowed_origins
+            .iter()
+            .map(|origin| {
+                let valid = (origin.starts_with("http://") || origin.starts_with("https://"))
+                    && !origin.ends_with('/');
+                valid
+                    .then(|| HeaderValue::from_str(origin).ok())
+                    .flatten()
+                    .ok_or_else(|| ConfigError::CorsOrigin(origin.clone()))
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map(Some)
+    }
+
+    pub fn cors_methods(&self) -> Result<Vec<Method>, ConfigError> {
+        self.cors
+            .allowed_methods
+            .iter()
+            .map(|method| {
+                Method::from_str(&method.to_uppercase())
+                    .map_err(|_| ConfigError::CorsMethod(method.clone()))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        collections::HashMap,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    static FILES: AtomicUsize = AtomicUsize::new(0);
+
+    fn load(source: &str, env: &[(&str, &str)]) -> Result<Config, ConfigError> {
+        let path = env::temp_dir().join(format!(
+            "shq-config-{}-{}.toml",
+            std::process::id(),
+            FILES.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::write(&path, source).unwrap();
+        let env: HashMap<String, String> = env
+            .iter()
+            .map(|(name, value)| (String::from(*name), String::from(*value)))
+            .collect();
+
+        let config = Config::load_from(Some(&path), |name| env.get(name).cloned());
+        fs::remove_file(&path).unwrap();
+
+        config
+    }
+
+    #[test]
+    fn it_uses_defaults_without_a_file() {
+        let config = Config::load_from(None, |_| None).unwrap();
+
+        assert_eq!("127.0.0.1:3000", config.socket_addr().to_string());
+        assert!(config.graphiql);
+        assert_eq!(Some(vec![]), config.cors_origins().unwrap());
+    }
+
+    #[test]
+    fn it_parses_config_files() {
+        let config = load(
+            r#"
+            address = "0.0.0.0"
+            port = 8080
+            log_format = "json"
+            graphiql = false
+
+            [cors]
+            allowed_origins = ["https://deckbuilder.example.com"]
+            allowed_methods = ["get"]
+
+            [limits]
+            max_depth = 5
+            timeout_ms = 500
+            "#,
+            &[],
+        )
+        .unwrap();
+
+        assert_eq!("0.0.0.0:8080", config.socket_addr().to_string());
+        assert!(!config.graphiql);
+        assert_eq!(LogFormat::Json, config.log_format);
+        assert_eq!(vec![Method::GET], config.cors_methods().unwrap());
+        assert_eq!(5, config.limits.max_depth);
+        assert_eq!(500, config.limits.timeout.as_millis());
+    }
+
+    #[test]
+    fn it_applies_env_overrides() {
+        let config = load(
+            "port = 8080",
+            &[
+                ("SHQ_PORT", "9090"),
+                ("SHQ_CORS_ALLOWED_ORIGINS", "*"),
+                ("SHQ_MAX_QUERY_COMPLEXITY", "100"),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(9090, config.port);
+        assert_eq!(None, config.cors_origins().unwrap());
+        assert_eq!(100, config.limits.max_complexity);
+    }
+
+    #[test]
+    fn it_rejects_invalid_config() {
+        assert!(matches!(
+            load("prot = 8080", &[]),
+            Err(ConfigError::Parse { .. })
+        ));
+        assert!(matches!(
+            load("", &[("SHQ_PORT", "http")]),
+            Err(ConfigError::Env {
+                name: "SHQ_PORT",
+                ..
+            })
+        ));
+        assert!(matches!(
+            load("[cors]\nallowed_origins = [\"example.com\"]", &[]),
+            Err(ConfigError::CorsOrigin(_))
+        ));
+        assert!(matches!(
+            load("[tls]\ncert = \"missing.pem\"\nkey = \"missing.key\"", &[]),
+            Err(ConfigError::TlsFile("cert", _))
+        ));
+        assert!(matches!(
+            load("data_dir = \"missing\"", &[]),
+            Err(ConfigError::DataDir(_))
+        ));
+    }
+}
@@ -0,0 +1,89 @@
+use crate::{config::ConfigError, loader::LoadError};
+use data::{campaign::CampaignError, card_store::CardStoreError, play::PlayError};
+use std::{io, net::SocketAddr, path::PathBuf};
+use thiserror::Error;
+use tokio::task::JoinError;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(transparent)]
+    Config(#[from] ConfigError),
+    #[error("Could not load TLS certificate: {0}")]
+    Tls(io::Error),
+    #[error(transparent)]
+    Load(#[from] LoadError),
+    #[error("Could not open database {path}: {source}")]
+    Database {
+        path: PathBuf,
+        source: CardStoreError,
+    },
+    #[error(transparent)]
+    CampaignLogs(CampaignError),
+    #[error(transparent)]
+    Plays(PlayError),
+    #[error("Could not load persisted queries from {path}: {source}")]
+    PersistedQueries { path: PathBuf, source: io::Error },
+    #[error("Could not bind to {addr}: {source}")]
+    Bind { addr: SocketAddr, source: io::Error },
+    #[error("Server error: {0}")]
+    Serve(io::Error),
+    #[error("Could not serialize response: {0}")]
+    Serialize(#[from] serde_json::Error),
+    #[error("Query execution failed: {0}")]
+    Execute(#[from] JoinError),
+}
+
+impl Error {
+    /// Process exit code, following the BSD `sysexits.h` conventions
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Config(_) | Error::Tls(_) => 78,
+            Error::Load(_)
+            | Error::Database { .. }
+            | Error::CampaignLogs(_)
+            | Error::Plays(_)
+            | Error::PersistedQueries { .. } => 65,
+            Error::Bind { .. } => 69,
+            Error::Serve(_) => 74,
+            Error::Serialize(_) | Error::Execute(_) => 70,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_has_distinct_exit_codes() {
+        let addr = SocketAddr::from(([127, 0, 0, 1], 3000));
+        let codes = [
+            Error::Config(ConfigError::LogLevel(String::from("[["))).exit_code(),
+            Error::Load(LoadError::Missing("products.toml")).exit_code(),
+            Error::Bind {
+                addr,
+                source: io::ErrorKind::AddrInUse.into(),
+            }
+            .exit_code(),
+            Error::Serve(io::ErrorKind::Other.into()).exit_code(),
+            Error::Serialize(serde_json::from_str::<()>("").unwrap_err()).exit_code(),
+        ];
+
+        for (i, code) in codes.iter().enumerate() {
+            assert!(!codes[..i].contains(code), "{code}");
+        }
+    }
+
+    #[test]
+    fn it_formats_bind_errors() {
+        let error = Error::Bind {
+            addr: SocketAddr::from(([127, 0, 0, 1], 3000)),
+            source: io::Error::new(io::ErrorKind::AddrInUse, "Address already in use"),
+        };
+
+        assert_eq!(
+            "Could not bind to 127.0.0.1:3000: Address already in use",
+            error.to_string()
+        );
+    }
+}
@@ -0,0 +1,485 @@
+use data::graphql::{SHQScalarValue, Schema};
+use juniper::{
+    graphql_value, meta::MetaType, parser::parse_document_source, Definition, FieldError,
+    OperationType, Selection,
+};
+use serde::{Deserialize, Deserializer};
+use std::{collections::HashMap, time::Duration};
+use thiserror::Error;
+
+/// Fragments by name, with the type they apply to and their selections
+type Fragments<'a> = HashMap<&'a str, Fragment<'a>>;
+type Fragment<'a> = (&'a str, &'a [Selection<'a, SHQScalarValue>]);
+
+/// Fragments being walked, to skip cycles, and what every fragment walked so far came to. A
+/// fragment is only walked once however often it's spread, which keeps fragments spreading
+/// others several times from taking exponential time.
+#[derive(Default)]
+struct Walked<'a> {
+    walking: Vec<&'a str>,
+    results: HashMap<&'a str, usize>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct QueryLimits {
+    pub max_depth: usize,
+    pub max_complexity: usize,
+    /// Most requests a single batch can hold
+    pub max_batch_size: usize,
+    /// Multiplier applied to the selections under a list field, keyed by `Type.field`
+    pub list_costs: HashMap<String, usize>,
+    #[serde(rename = "timeout_ms", deserialize_with = "millis")]
+    pub timeout: Duration,
+}
+
+fn millis<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+    u64::deserialize(deserializer).map(Duration::from_millis)
+}
+
+impl Default for QueryLimits {
+    fn default() -> Self {
+        let list_costs = [
+            ("Query.cards", 50),
+            ("Query.products", 10),
+            ("Query.traits", 25),
+            ("Card.products", 10),
+            ("Card.printings", 10),
+            ("Card.reprints", 10),
+            ("Card.signatureCards", 10),
+            ("CardProduct.sets", 10),
+            ("CardSide.traits", 25),
+            ("Product.sets", 10),
+        ]
+        .into_iter()
+        .map(|(field, cost)| (String::from(field), cost))
+        .collect();
+
+        Self {
+            max_depth: 10,
+            max_complexity: 5000,
+            max_batch_size: 10,
+            list_costs,
+            timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum LimitError {
+    #[error("Query depth of {depth} exceeds the maximum depth of {max}")]
+    Depth { depth: usize, max: usize },
+    #[error("Query complexity of {complexity} exceeds the maximum complexity of {max}")]
+    Complexity { complexity: usize, max: usize },
+    #[error("Batch of {size} requests exceeds the maximum batch size of {max}")]
+    BatchSize { size: usize, max: usize },
+    #[error("Query did not finish within {}ms", .0.as_millis())]
+    Timeout(Duration),
+}
+
+impl LimitError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            LimitError::Depth { .. } => "QUERY_TOO_DEEP",
+            LimitError::Complexity { .. } => "QUERY_TOO_COMPLEX",
+            LimitError::BatchSize { .. } => "BATCH_TOO_LARGE",
+            LimitError::Timeout(_) => "QUERY_TIMEOUT",
+        }
+    }
+
+    pub fn into_field_error(self) -> FieldError<SHQScalarValue> {
+        let extensions = match &self {
+            LimitError::Depth { depth, max } => graphql_value!({
+                "code": (self.code()),
+                "depth": (*depth as i32),
+                "max": (*max as i32),
+            }),
+            LimitError::Complexity { complexity, max } => graphql_value!({
+                "code": (self.code()),
+                "complexity": (*complexity as i32),
+                "max": (*max as i32),
+            }),
+            LimitError::BatchSize { size, max } => graphql_value!({
+                "code": (self.code()),
+                "size": (*size as i32),
+                "max": (*max as i32),
+            }),
+            LimitError::Timeout(timeout) => graphql_value!({
+                "code": (self.code()),
+                "timeoutMs": (timeout.as_millis() as i32),
+            }),
+        };
+
+        FieldError::new(self, extensions)
+    }
+}
+
+/// Type of the operation `operation_name` selects from `query`. `None` when the query doesn't
+/// parse or has no such operation, which the executor reports.
+pub fn operation_type(
+    schema: &Schema,
+    query: &str,
+    operation_name: Option<&str>,
+) -> Option<OperationType> {
+    let document = parse_document_source(query, &schema.schema).ok()?;
+
+    document.iter().find_map(|definition| match definition {
+        Definition::Operation(operation)
+            if operation_name.is_none()
+                || operation.item.name.as_ref().map(|name| name.item) == operation_name =>
+        {
+            Some(operation.item.operation_type.clone())
+        }
+        _ => None,
+    })
+}
+
+impl QueryLimits {
+    /// Checks the depth and complexity of the operation that would be executed. Queries that
+    /// don't parse are let through, so the executor can report the parse error.
+    pub fn check(
+        &self,
+        schema: &Schema,
+        query: &str,
+        operation_name: Option<&str>,
+    ) -> Result<(), LimitError> {
+        let document = match parse_document_source(query, &schema.schema) {
+            Ok(document) => document,
+            Err(_) => return Ok(()),
+        };
+        let fragments: Fragments = document
+            .iter()
+            .filter_map(|definition| match definition {
+                Definition::Fragment(fragment) => Some((
+                    fragment.item.name.item,
+                    (
+                        fragment.item.type_condition.item,
+                        fragment.item.selection_set.as_slice(),
+                    ),
+                )),
+                Definition::Operation(_) => None,
+            })
+            .collect();
+        let operations = document.iter().filter_map(|definition| match definition {
+            Definition::Operation(operation) => Some(&operation.item),
+            Definition::Fragment(_) => None,
+        });
+
+        for operation in operations {
+            if operation_name.is_some()
+                && operation.name.as_ref().map(|name| name.item) != operation_name
+            {
+                continue;
+            }
+
+            let depth = self.depth(&operation.selection_set, &fragments, &mut Walked::default());
+            if depth > self.max_depth {
+                return Err(LimitError::Depth {
+                    depth,
+                    max: self.max_depth,
+                });
+            }
+
+            let root = match operation.operation_type {
+                OperationType::Query => Some(schema.schema.concrete_query_type()),
+                OperationType::Mutation => schema.schema.concrete_mutation_type(),
+                OperationType::Subscription => schema.schema.concrete_subscription_type(),
+            };
+            let complexity = self.complexity(
+                schema,
+                &operation.selection_set,
+                root,
+                &fragments,
+                &mut Walked::default(),
+            );
+            if complexity > self.max_complexity {
+                return Err(LimitError::Complexity {
+                    complexity,
+                    max: self.max_complexity,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Levels of fields in `selections`, counted from them
+    fn depth<'a>(
+        &self,
+        selections: &'a [Selection<'a, SHQScalarValue>],
+        fragments: &Fragments<'a>,
+        walked: &mut Walked<'a>,
+    ) -> usize {
+        selections
+            .iter()
+            .map(|selection| match selection {
+                Selection::Field(field) => match &field.item.selection_set {
+                    Some(selection_set) => 1 + self.depth(selection_set, fragments, walked),
+                    None => 1,
+                },
+                Selection::InlineFragment(fragment) => {
+                    self.depth(&fragment.item.selection_set, fragments, walked)
+                }
+                Selection::FragmentSpread(spread) => self.spread(
+                    spread.item.name.item,
+                    fragments,
+                    walked,
+                    |(_, selections), walked| self.depth(selections, fragments, walked),
+                ),
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Sums the cost of `selections` on the `parent` type. Fields on types the schema doesn't
+    /// know, which validation rejects, cost 1.
+    fn complexity<'a>(
+        &self,
+        schema: &'a Schema,
+        selections: &'a [Selection<'a, SHQScalarValue>],
+        parent: Option<&'a MetaType<'a, SHQScalarValue>>,
+        fragments: &Fragments<'a>,
+        walked: &mut Walked<'a>,
+    ) -> usize {
+        let on = |name: &str| schema.schema.concrete_type_by_name(name);
+
+        selections
+            .iter()
+            .map(|selection| match selection {
+                Selection::Field(field) => {
+                    let name = field.item.name.item;
+                    let meta = parent.and_then(|parent| parent.field_by_name(name));
+                    let children = field
+                        .item
+                        .selection_set
+                        .as_ref()
+                        .map(|selection_set| {
+                            let child = meta.and_then(|meta| on(meta.field_type.innermost_name()));
+                            self.complexity(schema, selection_set, child, fragments, walked)
+                        })
+                        .unwrap_or(0);
+                    let cost = parent
+                        .and_then(MetaType::name)
+                        .and_then(|parent| self.list_costs.get(&format!("{parent}.{name}")))
+                        .copied()
+                        .unwrap_or(1);
+
+                    children.saturating_add(1).saturating_mul(cost)
+                }
+                Selection::InlineFragment(fragment) => {
+                    let parent = match &fragment.item.type_condition {
+                        Some(type_condition) => on(type_condition.item),
+                        None => parent,
+                    };
+                    self.complexity(
+                        schema,
+                        &fragment.item.selection_set,
+                        parent,
+                        fragments,
+                        walked,
+                    )
+                }
+                Selection::FragmentSpread(spread) => self.spread(
+                    spread.item.name.item,
+                    fragments,
+                    walked,
+                    |(type_condition, selections), walked| {
+                        let parent = on(type_condition);
+                        self.complexity(schema, selections, parent, fragments, walked)
+                    },
+                ),
+            })
+            .fold(0, usize::saturating_add)
+    }
+
+    /// Walks into a named fragment, unless it's been walked already, skipping unknown or cyclic
+    /// fragments which validation rejects
+    fn spread<'a>(
+        &self,
+        name: &'a str,
+        fragments: &Fragments<'a>,
+        walked: &mut Walked<'a>,
+        walk: impl FnOnce(Fragment<'a>, &mut Walked<'a>) -> usize,
+    ) -> usize {
+        if let Some(&result) = walked.results.get(name) {
+            return result;
+        }
+
+        match fragments.get(name) {
+            Some(fragment) if !walked.walking.contains(&name) => {
+                walked.walking.push(name);
+                let result = walk(*fragment, walked);
+                walked.walking.pop();
+                walked.results.insert(name, result);
+
+                result
+            }
+            _ => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use data::graphql::schema;
+
+    #[test]
+    fn it_allows_small_queries() {
+        let limits = QueryLimits::default();
+
+        assert_eq!(
+            Ok(()),
+            limits.check(&schema(), "{ products { name } }", None)
+        );
+    }
+
+    #[test]
+    fn it_rejects_deep_queries() {
+        let limits = QueryLimits {
+            max_depth: 3,
+            ..QueryLimits::default()
+        };
+        let query = "{ cards { products { sets { name } } } }";
+
+        assert_eq!(
+            Err(LimitError::Depth { depth: 4, max: 3 }),
+            limits.check(&schema(), query, None)
+        );
+    }
+
+    #[test]
+    fn it_counts_depth_through_fragments() {
+        let limits = QueryLimits {
+            max_depth: 3,
+            ..QueryLimits::default()
+        };
+        let query = r#"
+            query { cards { ...Products } }
+            fragment Products on Card { products { sets { name } } }
+        "#;
+
+        assert_eq!(
+            Err(LimitError::Depth { depth: 4, max: 3 }),
+            limits.check(&schema(), query, None)
+        );
+    }
+
+    #[test]
+    fn it_weighs_list_fields() {
+        let limits = QueryLimits {
+            max_complexity: 100,
+            ..QueryLimits::default()
+        };
+        // cards: (1 + products) * 50, products: (1 + name) * 10
+        let query = "{ cards { products { name } } }";
+
+        assert_eq!(
+            Err(LimitError::Complexity {
+                complexity: 1050,
+                max: 100
+            }),
+            limits.check(&schema(), query, None)
+        );
+    }
+
+    #[test]
+    fn it_weighs_list_fields_by_their_type() {
+        let limits = QueryLimits {
+            max_complexity: 10,
+            ..QueryLimits::default()
+        };
+        // DeckStats.traits isn't weighed like Query.traits
+        let query = "{ deckStats(cards: []) { traits { count } } }";
+
+        assert_eq!(Ok(()), limits.check(&schema(), query, None));
+        assert_eq!(
+            Err(LimitError::Complexity {
+                complexity: 50,
+                max: 10
+            }),
+            limits.check(&schema(), "{ traits { name } }", None)
+        );
+    }
+
+    #[test]
+    fn it_weighs_list_fields_in_fragments() {
+        let limits = QueryLimits {
+            max_complexity: 100,
+            ..QueryLimits::default()
+        };
+        let query = r#"
+            query { cards { ...Products } }
+            fragment Products on Card { products { name } }
+        "#;
+
+        assert_eq!(
+            Err(LimitError::Complexity {
+                complexity: 1050,
+                max: 100
+            }),
+            limits.check(&schema(), query, None)
+        );
+    }
+
+    #[test]
+    fn it_only_checks_the_named_operation() {
+        let limits = QueryLimits {
+            max_depth: 2,
+            ..QueryLimits::default()
+        };
+        let query = r#"
+            query Shallow { products { name } }
+            query Deep { cards { products { name } } }
+        "#;
+
+        assert_eq!(Ok(()), limits.check(&schema(), query, Some("Shallow")));
+        assert!(limits.check(&schema(), query, Some("Deep")).is_err());
+    }
+
+    #[test]
+    fn it_finds_the_operation_type() {
+        let query = r#"
+            query Cards { cards { aspect } }
+            subscription Changes { dataReloaded { version } }
+        "#;
+
+        assert_eq!(
+            Some(OperationType::Query),
+            operation_type(&schema(), query, None)
+        );
+        assert_eq!(
+            Some(OperationType::Subscription),
+            operation_type(&schema(), query, Some("Changes"))
+        );
+        assert_eq!(None, operation_type(&schema(), "{", None));
+    }
+
+    #[test]
+    fn it_ignores_cyclic_fragments() {
+        let limits = QueryLimits::default();
+        let query = r#"
+            query { cards { ...A } }
+            fragment A on Card { ...B }
+            fragment B on Card { ...A }
+        "#;
+
+        assert_eq!(Ok(()), limits.check(&schema(), query, None));
+    }
+
+    #[test]
+    fn it_walks_each_fragment_once() {
+        let limits = QueryLimits::default();
+        // every fragment spreads the next one twice, so walking each spread would take 2^40 steps
+        let fragments: String = (0..40)
+            .map(|i| format!("fragment F{i} on Card {{ ...F{} ...F{} }}\n", i + 1, i + 1))
+            .collect();
+        let query =
+            format!("query {{ cards {{ ...F0 }} }}\n{fragments}fragment F40 on Card {{ aspect }}");
+
+        assert!(matches!(
+            limits.check(&schema(), &query, None),
+            Err(LimitError::Complexity { .. })
+        ));
+    }
+}
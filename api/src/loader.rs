@@ -0,0 +1,275 @@
+use crate::cache;
+use data::{
+    card::{self, r#trait::TraitError, Card, Trait},
+    card_store::CardStoreError,
+    product::{self, Product, SetError},
+    scenario::{self, ScenarioError},
+};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+const PRODUCTS_FILE: &str = "products.toml";
+const TRAITS_FILE: &str = "traits.toml";
+const SCENARIOS_FILE: &str = "scenarios.toml";
+
+/// Data built into the binary, used when no data directory is configured
+const EMBEDDED: &[(&str, &str)] = &[
+    (
+        "core-set.toml",
+        include_str!("../../data/data/core-set.toml"),
+    ),
+    (PRODUCTS_FILE, include_str!("../../data/data/products.toml")),
+    (TRAITS_FILE, include_str!("../../data/data/traits.toml")),
+    (
+        SCENARIOS_FILE,
+        include_str!("../../data/data/scenarios.toml"),
+    ),
+];
+
+#[derive(Debug, Error)]
+pub enum LoadError {
+    #[error("Could not read {path}: {source}")]
+    Read { path: PathBuf, source: io::Error },
+    #[error("Invalid data in {path}: {source}")]
+    Parse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    #[error("Missing {0} in the data directory")]
+    Missing(&'static str),
+    #[error("Invalid sets:\n{}", .0.iter().map(ToString::to_string).collect::<Vec<_>>().join("\n"))]
+    Sets(Vec<SetError>),
+    #[error("Invalid traits:\n{}", .0.iter().map(ToString::to_string).collect::<Vec<_>>().join("\n"))]
+    Traits(Vec<TraitError>),
+    #[error("Invalid scenarios:\n{}", .0.iter().map(ToString::to_string).collect::<Vec<_>>().join("\n"))]
+    Scenarios(Vec<ScenarioError>),
+    #[error("Could not import the data into the database: {0}")]
+    Database(#[from] CardStoreError),
+}
+
+pub struct Data {
+    pub cards: Vec<Card>,
+    pub products: Vec<Product>,
+    pub traits: Vec<Trait>,
+    pub scenarios: scenario::Document,
+    /// Hash of the source files, changing whenever the data does
+    pub version: String,
+}
+
+impl Data {
+    /// Loads the data from `dir`, or the embedded data when it's `None`. `products.toml` and
+    /// `traits.toml` are required and `scenarios.toml` is optional, every other TOML file in the
+    /// directory holds cards.
+    pub fn load(dir: Option<&Path>) -> Result<Self, LoadError> {
+        let sources = match dir {
+            Some(dir) => read_dir(dir)?,
+            None => EMBEDDED
+                .iter()
+                .map(|(name, source)| (PathBuf::from(name), String::from(*source)))
+                .collect(),
+        };
+
+        Self::parse(&sources)
+    }
+
+    fn parse(sources: &[(PathBuf, String)]) -> Result<Self, LoadError> {
+        let find = |name: &'static str| {
+            sources
+                .iter()
+                .find(|(path, _)| path.file_name().is_some_and(|file| file == name))
+                .ok_or(LoadError::Missing(name))
+        };
+
+        let (products_path, products_source) = find(PRODUCTS_FILE)?;
+        let (traits_path, traits_source) = find(TRAITS_FILE)?;
+        let products = parse::<product::Document>(products_path, products_source)?.products;
+        let traits = parse::<card::r#trait::Document>(traits_path, traits_source)?.traits;
+        let scenarios = match find(SCENARIOS_FILE) {
+            Ok((path, source)) => parse::<scenario::Document>(path, source)?,
+            Err(_) => scenario::Document::default(),
+        };
+
+        let mut cards = Vec::new();
+        for (path, source) in sources {
+            let file = path.file_name().unwrap_or_default();
+            if ![PRODUCTS_FILE, TRAITS_FILE, SCENARIOS_FILE]
+                .contains(&file.to_str().unwrap_or_default())
+            {
+                cards.extend(parse::<card::Document>(path, source)?.cards);
+            }
+        }
+        product::validate(&products, &cards).map_err(LoadError::Sets)?;
+        card::r#trait::validate(&cards, &traits).map_err(LoadError::Traits)?;
+        scenario::validate(&scenarios, &products).map_err(LoadError::Scenarios)?;
+
+        let version = version(sources);
+
+        Ok(Self {
+            cards,
+            products,
+            traits,
+            scenarios,
+            version,
+        })
+    }
+}
+
+fn read_dir(dir: &Path) -> Result<Vec<(PathBuf, String)>, LoadError> {
+    let read_error = |path: &Path| {
+        let path = path.to_path_buf();
+        move |source| LoadError::Read { path, source }
+    };
+
+    let mut paths = fs::read_dir(dir)
+        .map_err(read_error(dir))?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(read_error(dir))?;
+    paths.retain(|path| {
+        path.extension()
+            .is_some_and(|extension| extension == "toml")
+    });
+    // sorted so the data version doesn't depend on directory order
+    paths.sort();
+
+    paths
+        .into_iter()
+        .map(|path| {
+            let source = fs::read_to_string(&path).map_err(read_error(&path))?;
+
+            Ok((path, source))
+        })
+        .collect()
+}
+
+/// Hashes every file's name and length ahead of its contents, so moving data from one file to
+/// another or renaming a file changes the version too
+fn version(sources: &[(PathBuf, String)]) -> String {
+    let mut input = Vec::new();
+    for (path, source) in sources {
+        let name = path.file_name().unwrap_or_default().as_encoded_bytes();
+        input.extend_from_slice(&(name.len() as u64).to_le_bytes());
+        input.extend_from_slice(name);
+        input.extend_from_slice(&(source.len() as u64).to_le_bytes());
+        input.extend_from_slice(source.as_bytes());
+    }
+
+    cache::sha256(input)
+}
+
+fn parse<T: serde::de::DeserializeOwned>(path: &Path, source: &str) -> Result<T, LoadError> {
+    toml::from_str(source).map_err(|source| LoadError::Parse {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_loads_embedded_data() {
+        let data = Data::load(None).unwrap();
+
+        assert!(!data.cards.is_empty());
+        assert!(!data.products.is_empty());
+        assert!(!data.traits.is_empty());
+        assert!(!data.scenarios.scenarios.is_empty());
+    }
+
+    #[test]
+    fn it_loads_a_data_directory() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../data/data");
+        let data = Data::load(Some(&dir)).unwrap();
+
+        assert_eq!(Data::load(None).unwrap().cards.len(), data.cards.len());
+    }
+
+    #[test]
+    fn it_requires_products_and_traits() {
+        let sources = [(PathBuf::from("products.toml"), String::new())];
+
+        assert!(matches!(
+            Data::parse(&sources),
+            Err(LoadError::Missing(TRAITS_FILE))
+        ));
+    }
+
+    #[test]
+    fn it_reports_the_file_that_failed_to_parse() {
+        let mut sources: Vec<_> = EMBEDDED
+            .iter()
+            .map(|(name, source)| (PathBuf::from(name), String::from(*source)))
+            .collect();
+        sources.push((PathBuf::from("broken.toml"), String::from("[[card]]")));
+        let error = Data::parse(&sources).err().unwrap();
+
+        assert!(error.to_string().starts_with("Invalid data in broken.toml"));
+    }
+
+    #[test]
+    fn it_versions_the_files_and_not_only_their_contents() {
+        let sources = |files: [(&str, &str); 2]| {
+            files
+                .map(|(name, source)| (PathBuf::from(name), String::from(source)))
+                .to_vec()
+        };
+        let original = version(&sources([("a.toml", "ab"), ("b.toml", "c")]));
+
+        assert_eq!(
+            original,
+            version(&sources([("a.toml", "ab"), ("b.toml", "c")]))
+        );
+        assert_ne!(
+            original,
+            version(&sources([("a.toml", "a"), ("b.toml", "bc")]))
+        );
+        assert_ne!(
+            original,
+            version(&sources([("a.toml", "ab"), ("c.toml", "c")]))
+        );
+    }
+
+    #[test]
+    fn it_validates_scenarios() {
+        let mut sources: Vec<_> = EMBEDDED
+            .iter()
+            .map(|(name, source)| (PathBuf::from(name), String::from(*source)))
+            .collect();
+        for (path, source) in sources.iter_mut() {
+            if path.as_os_str() == SCENARIOS_FILE {
+                *source = source.replace("villain_set = \"Rhino\"", "villain_set = \"Nope\"");
+            }
+        }
+
+        assert!(matches!(
+            Data::parse(&sources),
+            Err(LoadError::Scenarios(errors)) if errors.len() == 1
+        ));
+    }
+
+    #[test]
+    fn it_validates_sets() {
+        let mut sources: Vec<_> = EMBEDDED
+            .iter()
+            .map(|(name, source)| (PathBuf::from(name), String::from(*source)))
+            .collect();
+        for (path, source) in sources.iter_mut() {
+            if path.as_os_str() == PRODUCTS_FILE {
+                *source = source.replace("name = \"Captain Marvel\"", "name = \"Spider-Man\"");
+            }
+        }
+
+        assert!(matches!(
+            Data::parse(&sources),
+            Err(LoadError::Sets(errors)) if errors.contains(&SetError::Duplicate {
+                product: String::from("MC01en"),
+                code: String::from("spider-man"),
+            })
+        ));
+    }
+}
@@ -1,66 +1,637 @@
 use axum::{
-    extract::{FromRef, State},
-    http::StatusCode,
-    response::{Html, IntoResponse},
-    routing::{get, post},
+    extract::{ws::WebSocketUpgrade, FromRef, Query as QueryString, State},
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
+    response::{Html, IntoResponse, Response},
+    routing::get,
     Json, Router,
 };
-use data::graphql::{Ctx, Query, SHQScalarValue, Schema};
-use juniper::{http::GraphQLRequest, EmptyMutation, EmptySubscription, RootNode};
-use std::{net::SocketAddr, sync::Arc};
-use tracing::info;
+use axum_server::{tls_rustls::RustlsConfig, Handle};
+use config::{Config, ConfigError, LogFormat};
+use data::{
+    campaign::CampaignLogs,
+    card_store::SqliteStore,
+    graphql::{self, Ctx, SHQScalarValue, Schema},
+    play::Plays,
+};
+use error::Error;
+use futures::future;
+use juniper::{graphql_value, http::GraphQLResponse, FieldError, OperationType};
+use limits::{LimitError, QueryLimits};
+use loader::Data;
+use metrics::{Metrics, MetricsLayer};
+use persisted::{PersistedQueries, PersistedQueryError};
+use reload::{LiveData, Logs, Snapshot};
+use request::{BatchRequest, GetRequest, Request};
+use std::{io, net::TcpListener, process, sync::Arc, time::Instant};
+use tokio::signal;
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+use tracing::{error, field, info, info_span, Instrument, Span};
+use tracing_subscriber::{filter::filter_fn, layer::SubscriberExt, util::SubscriberInitExt, Layer};
+
+mod cache;
+mod config;
+mod error;
+mod limits;
+mod loader;
+mod metrics;
+mod persisted;
+mod reload;
+mod request;
+mod rest;
+mod subscriptions;
 
 async fn graphiql() -> impl IntoResponse {
     Html(juniper::http::graphiql::graphiql_source("/graphql", None))
 }
 
+async fn graphql_get(
+    State(state): State<AppState>,
+    ws: Option<WebSocketUpgrade>,
+    headers: HeaderMap,
+    QueryString(req): QueryString<GetRequest>,
+) -> Response {
+    if let Some(ws) = ws {
+        return ws
+            .protocols([subscriptions::PROTOCOL])
+            .on_upgrade(move |socket| {
+                subscriptions::serve(socket, state.schema, state.data, state.limits)
+            });
+    }
+
+    match Request::try_from(req) {
+        Ok(req) => {
+            let snapshot = state.data.current();
+            graphql(&state, &snapshot, &Method::GET, &headers, req)
+                .await
+                .into_response()
+        }
+        Err(error) => Outcome::error(
+            StatusCode::BAD_REQUEST,
+            FieldError::new(error, graphql_value!({ "code": "BAD_REQUEST" })),
+        )
+        .into_response(),
+    }
+}
+
+async fn graphql_post(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(req): Json<BatchRequest>,
+) -> Response {
+    let snapshot = state.data.current();
+
+    match req {
+        BatchRequest::Single(req) => graphql(&state, &snapshot, &Method::POST, &headers, req)
+            .await
+            .into_response(),
+        BatchRequest::Batch(reqs) => {
+            let max = state.limits.max_batch_size;
+            if reqs.len() > max {
+                let error = LimitError::BatchSize {
+                    size: reqs.len(),
+                    max,
+                };
+                return Outcome::error(StatusCode::BAD_REQUEST, error.into_field_error())
+                    .into_response();
+            }
+
+            // conditional requests apply to a single response, so they're ignored in batches
+            let headers = HeaderMap::new();
+            // every request executes on its own blocking thread, so joining them runs the batch,
+            // at most `max_batch_size` requests, in parallel against the same snapshot
+            let outcomes = future::join_all(
+                reqs.into_iter()
+                    .map(|req| graphql(&state, &snapshot, &Method::POST, &headers, req)),
+            )
+            .await;
+
+            batch(outcomes)
+        }
+    }
+}
+
+/// Answers a batch with the status its requests share, or 207 Multi-Status when they differ, and
+/// puts the status each request would have been answered with on its own in its `extensions`
+fn batch(outcomes: Vec<Outcome>) -> Response {
+    let status = match outcomes.first() {
+        Some(first)
+            if outcomes
+                .iter()
+                .all(|outcome| outcome.status == first.status) =>
+        {
+            first.status
+        }
+        Some(_) => StatusCode::MULTI_STATUS,
+        None => StatusCode::OK,
+    };
+    let bodies: Vec<_> = outcomes
+        .into_iter()
+        .map(|outcome| {
+            let mut body = outcome.body.unwrap_or_default();
+            if let Some(body) = body.as_object_mut() {
+                let extensions = body
+                    .entry("extensions")
+                    .or_insert_with(|| serde_json::json!({}));
+                extensions["status"] = outcome.status.as_u16().into();
+            }
+            body
+        })
+        .collect();
+
+    (status, Json(bodies)).into_response()
+}
+
+/// Runs a request in a span with its operation, query hash, variable names, status, error count
+/// and duration, leaving variable values and the query text out of the logs
 async fn graphql(
-    State(schema): State<Arc<Schema>>,
-    State(ctx): State<Arc<Ctx>>,
-    req: Json<GraphQLRequest<SHQScalarValue>>,
-) -> impl IntoResponse {
-    info!("{:?}", req);
-    let response = req.execute(&schema, &ctx).await;
-    let status = if response.is_ok() {
-        StatusCode::OK
-    } else {
-        StatusCode::BAD_REQUEST
+    state: &AppState,
+    snapshot: &Snapshot,
+    method: &Method,
+    headers: &HeaderMap,
+    req: Request,
+) -> Outcome {
+    let operation_name = req.operation_name.clone();
+    let span = info_span!(
+        "graphql",
+        operation = operation_name.as_deref().unwrap_or("anonymous"),
+        query_hash = field::Empty,
+        variables = %req.variable_names().join(","),
+        status = field::Empty,
+        errors = field::Empty,
+        duration_ms = field::Empty,
+    );
+
+    async move {
+        let start = Instant::now();
+        let outcome = execute(state, snapshot, method, headers, req).await;
+        let duration = start.elapsed();
+        let errors = outcome.errors();
+
+        let span = Span::current();
+        span.record("status", outcome.status.as_u16());
+        span.record("errors", errors);
+        span.record("duration_ms", duration.as_secs_f64() * 1000.0);
+        info!("graphql request finished");
+        metrics::request(operation_name.as_deref(), duration, errors);
+
+        outcome
+    }
+    .instrument(span)
+    .await
+}
+
+async fn execute(
+    state: &AppState,
+    snapshot: &Snapshot,
+    method: &Method,
+    headers: &HeaderMap,
+    mut req: Request,
+) -> Outcome {
+    let query = req.query.take();
+    let register = query.is_some() && req.persisted_query().is_some();
+    let query = match state
+        .persisted_queries
+        .resolve(query, req.persisted_query())
+    {
+        Ok(query) => {
+            Span::current().record("query_hash", cache::sha256(&query).as_str());
+            query
+        }
+        Err(error) => {
+            // Apollo clients expect a successful response when they need to send the full query
+            let status = if error == PersistedQueryError::NotFound {
+                StatusCode::OK
+            } else {
+                StatusCode::BAD_REQUEST
+            };
+            let extensions = graphql_value!({ "code": (error.code()) });
+
+            return Outcome::error(status, FieldError::new(error, extensions));
+        }
     };
-    let json = serde_json::to_string(&response).unwrap();
+    let limits = &state.limits;
+    if let Err(error) = limits.check(&state.schema, &query, req.operation_name.as_deref()) {
+        return Outcome::error(StatusCode::BAD_REQUEST, error.into_field_error());
+    }
+    let operation = limits::operation_type(&state.schema, &query, req.operation_name.as_deref());
+    let is_mutation = operation == Some(OperationType::Mutation);
+    // GET requests have to be safe to repeat and cache, so they can't change anything
+    if is_mutation && method == Method::GET {
+        let error = FieldError::new(
+            "Mutations have to be sent with POST",
+            graphql_value!({ "code": "METHOD_NOT_ALLOWED" }),
+        );
+
+        return Outcome::error(StatusCode::METHOD_NOT_ALLOWED, error);
+    }
+    if register {
+        state.persisted_queries.insert(query.clone());
+    }
+
+    let variables = match serde_json::to_string(&req.variables) {
+        Ok(variables) => variables,
+        Err(error) => return Outcome::internal_error(error.into()),
+    };
+    // logs change without the data changing
+    let version = format!("{}-{}", snapshot.version, snapshot.logs.revision());
+    // a mutation's response depends on more than the data it was sent against
+    let etag = (!is_mutation)
+        .then(|| cache::etag(&version, &query, req.operation_name.as_deref(), &variables));
+    if etag
+        .as_ref()
+        .is_some_and(|etag| cache::is_fresh(headers, etag))
+    {
+        return Outcome {
+            status: StatusCode::NOT_MODIFIED,
+            etag,
+            body: None,
+        };
+    }
+
+    let req = req.into_graphql(query);
+    let schema = Arc::clone(&state.schema);
+    let ctx = Arc::clone(&snapshot.ctx);
+    let deadline = Instant::now() + limits.timeout;
+    // resolvers are synchronous, so they run on the blocking pool where the timeout can stop
+    // waiting on them. A request still queued for a thread at its deadline is never started.
+    let outcome = tokio::task::spawn_blocking(move || {
+        if Instant::now() >= deadline {
+            return None;
+        }
+        let response = req.execute_sync(&schema, &ctx);
+        let status = if response.is_ok() {
+            StatusCode::OK
+        } else {
+            StatusCode::BAD_REQUEST
+        };
+
+        Some(Outcome::new(status, &response))
+    });
+    let timeout = || {
+        Outcome::error(
+            StatusCode::SERVICE_UNAVAILABLE,
+            LimitError::Timeout(limits.timeout).into_field_error(),
+        )
+    };
+    match tokio::time::timeout_at(deadline.into(), outcome).await {
+        Ok(Ok(Some(outcome))) if outcome.status == StatusCode::OK => Outcome { etag, ..outcome },
+        Ok(Ok(Some(outcome))) => outcome,
+        Ok(Ok(None)) | Err(_) => timeout(),
+        Ok(Err(error)) => Outcome::internal_error(error.into()),
+    }
+}
+
+/// The result of a single GraphQL request, before it's sent on its own or as part of a batch
+struct Outcome {
+    status: StatusCode,
+    etag: Option<String>,
+    /// `None` when the client's cached response is still fresh
+    body: Option<serde_json::Value>,
+}
+
+impl Outcome {
+    fn new(status: StatusCode, response: &GraphQLResponse<SHQScalarValue>) -> Self {
+        match serde_json::to_value(response) {
+            Ok(body) => Self {
+                status,
+                etag: None,
+                body: Some(body),
+            },
+            Err(error) => Self::internal_error(error.into()),
+        }
+    }
+
+    fn error(status: StatusCode, error: FieldError<SHQScalarValue>) -> Self {
+        Self::new(status, &GraphQLResponse::error(error))
+    }
+
+    fn internal_error(error: Error) -> Self {
+        error!("{}", error);
+        let error = FieldError::new(
+            "Internal server error",
+            graphql_value!({ "code": "INTERNAL_SERVER_ERROR" }),
+        );
+
+        Self::error(StatusCode::INTERNAL_SERVER_ERROR, error)
+    }
+
+    fn errors(&self) -> usize {
+        self.body
+            .as_ref()
+            .and_then(|body| body["errors"].as_array())
+            .map_or(0, Vec::len)
+    }
+}
+
+impl IntoResponse for Outcome {
+    fn into_response(self) -> Response {
+        let mut response = match self.body {
+            Some(body) => (self.status, Json(body)).into_response(),
+            None => self.status.into_response(),
+        };
+        if let Some(etag) = self.etag.and_then(|etag| HeaderValue::from_str(&etag).ok()) {
+            response.headers_mut().insert(header::ETAG, etag);
+        }
+        // only mutations are refused a method, and they can always be sent with POST
+        if self.status == StatusCode::METHOD_NOT_ALLOWED {
+            response
+                .headers_mut()
+                .insert(header::ALLOW, HeaderValue::from_static("POST"));
+        }
+
+        response
+    }
+}
+
+async fn healthz() -> &'static str {
+    "ok"
+}
+
+async fn readyz(State(metrics): State<Arc<Metrics>>) -> impl IntoResponse {
+    if metrics.is_ready() {
+        (StatusCode::OK, "ready")
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, "not ready")
+    }
+}
 
-    (status, json)
+async fn prometheus(State(metrics): State<Arc<Metrics>>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        metrics.render(),
+    )
 }
 
-#[derive(Clone, FromRef)]
+#[derive(Clone)]
 struct AppState {
     schema: Arc<Schema>,
-    ctx: Arc<Ctx>,
+    data: Arc<LiveData>,
+    limits: Arc<QueryLimits>,
+    persisted_queries: Arc<PersistedQueries>,
+    metrics: Arc<Metrics>,
+}
+
+/// Handlers that only read the data get the current snapshot of it
+impl FromRef<AppState> for Arc<Ctx> {
+    fn from_ref(state: &AppState) -> Self {
+        Arc::clone(&state.data.current().ctx)
+    }
+}
+
+impl FromRef<AppState> for Arc<Metrics> {
+    fn from_ref(state: &AppState) -> Self {
+        Arc::clone(&state.metrics)
+    }
+}
+
+fn cors(config: &Config) -> Option<CorsLayer> {
+    // both were checked when the config was loaded
+    let origins = config.cors_origins().ok()?;
+    let methods = config.cors_methods().ok()?;
+    let allow_origin = match origins {
+        Some(origins) if origins.is_empty() => return None,
+        Some(origins) => AllowOrigin::list(origins),
+        None => AllowOrigin::from(Any),
+    };
+
+    Some(
+        CorsLayer::new()
+            .allow_origin(allow_origin)
+            .allow_methods(methods)
+            .allow_headers([header::CONTENT_TYPE, header::IF_NONE_MATCH])
+            .expose_headers([header::ETAG]),
+    )
+}
+
+/// Resolves on SIGINT or SIGTERM, after which in-flight requests are drained
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(error) = signal::ctrl_c().await {
+            error!("could not listen for SIGINT: {}", error);
+            future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        match signal::unix::signal(signal::unix::SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(error) => {
+                error!("could not listen for SIGTERM: {}", error);
+                future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => (),
+        _ = terminate => (),
+    }
+    info!("shutting down, waiting for in-flight requests");
+}
+
+async fn run(config: Config, metrics: Arc<Metrics>) -> Result<(), Error> {
+    let schema = Arc::new(graphql::schema());
+    let data = Data::load(config.data_dir.as_deref())?;
+    let campaigns = match &config.campaign_logs {
+        Some(path) => CampaignLogs::load(path).map_err(Error::CampaignLogs)?,
+        None => CampaignLogs::default(),
+    };
+    let plays = match &config.plays {
+        Some(path) => Plays::load(path).map_err(Error::Plays)?,
+        None => Plays::default(),
+    };
+    let logs = Logs {
+        campaigns: Arc::new(campaigns),
+        plays: Arc::new(plays),
+    };
+    let database = match &config.database {
+        Some(path) => Some(Arc::new(SqliteStore::open(path).map_err(|source| {
+            Error::Database {
+                path: path.clone(),
+                source,
+            }
+        })?)),
+        None => None,
+    };
+    let data = Arc::new(LiveData::new(
+        data,
+        config.data_dir.clone(),
+        logs,
+        database,
+    )?);
+    tokio::spawn(reload::reload_on_hangup(Arc::clone(&data)));
+    let persisted_queries = match &config.persisted_queries_dir {
+        Some(path) => {
+            PersistedQueries::from_dir(path).map_err(|source| Error::PersistedQueries {
+                path: path.clone(),
+                source,
+            })?
+        }
+        None => PersistedQueries::default(),
+    };
+    info!("loaded {} persisted queries", persisted_queries.len());
+    let state = AppState {
+        schema,
+        data,
+        limits: Arc::new(config.limits.clone()),
+        persisted_queries: Arc::new(persisted_queries),
+        metrics,
+    };
+
+    let mut app = Router::new()
+        .route("/graphql", get(graphql_get).post(graphql_post))
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .route("/metrics", get(prometheus))
+        .nest("/api", rest::routes());
+    if config.graphiql {
+        app = app.route("/", get(graphiql));
+    }
+    let mut app = app.with_state(state);
+    if let Some(cors) = cors(&config) {
+        app = app.layer(cors);
+    }
+
+    let addr = config.socket_addr();
+    let listener = TcpListener::bind(addr)
+        .and_then(|listener| listener.set_nonblocking(true).map(|_| listener))
+        .map_err(|source| Error::Bind { addr, source })?;
+    match &config.tls {
+        Some(tls) => {
+            let tls = RustlsConfig::from_pem_file(&tls.cert, &tls.key)
+                .await
+                .map_err(Error::Tls)?;
+            let handle = Handle::new();
+            let shutdown = handle.clone();
+            tokio::spawn(async move {
+                shutdown_signal().await;
+                shutdown.graceful_shutdown(None);
+            });
+            info!("listening on https://{}", addr);
+            axum_server::from_tcp_rustls(listener, tls)
+                .handle(handle)
+                .serve(app.into_make_service())
+                .await
+                .map_err(Error::Serve)
+        }
+        None => {
+            info!("listening on http://{}", addr);
+            axum::Server::from_tcp(listener)
+                .map_err(|source| Error::Bind {
+                    addr,
+                    source: io::Error::other(source),
+                })?
+                .serve(app.into_make_service())
+                .with_graceful_shutdown(shutdown_signal())
+                .await
+                .map_err(|error| Error::Serve(io::Error::other(error)))
+        }
+    }
+}
+
+fn init_tracing(config: &Config, metrics: Arc<Metrics>) -> Result<(), ConfigError> {
+    let fmt = match config.log_format {
+        LogFormat::Text => tracing_subscriber::fmt::layer().boxed(),
+        LogFormat::Json => tracing_subscriber::fmt::layer()
+            .json()
+            .with_current_span(true)
+            .with_span_list(false)
+            .boxed(),
+    };
+
+    tracing_subscriber::registry()
+        .with(
+            fmt.with_filter(config.env_filter()?)
+                .with_filter(filter_fn(|metadata| metadata.target() != metrics::TARGET)),
+        )
+        .with(
+            MetricsLayer::new(metrics)
+                .with_filter(filter_fn(|metadata| metadata.target() == metrics::TARGET)),
+        )
+        .init();
+
+    Ok(())
 }
 
 #[tokio::main]
 async fn main() {
-    tracing_subscriber::fmt::init();
-
-    let schema = Arc::new(RootNode::new_with_scalar_value(
-        Query {},
-        EmptyMutation::<Ctx>::new(),
-        EmptySubscription::<Ctx>::new(),
-    ));
-    let cards_doc: data::card::Document =
-        toml::from_str(include_str!("../../data/data/core-set.toml")).unwrap();
-    let products_doc: data::product::Document =
-        toml::from_str(include_str!("../../data/data/products.toml")).unwrap();
-    let ctx = Arc::new(Ctx::new(cards_doc.cards, products_doc.products));
-    let state = AppState { schema, ctx };
-
-    let app = Router::new()
-        .route("/", get(graphiql))
-        .route("/graphql", post(graphql))
-        .with_state(state);
-    let addr = SocketAddr::from(([127, 0, 0, 1], 3000));
-    info!("listening on {}", addr);
-    axum::Server::bind(&addr)
-        .serve(app.into_make_service())
-        .await
-        .unwrap();
+    let result = async {
+        let config = Config::load()?;
+        let metrics = Arc::new(Metrics::default());
+        init_tracing(&config, Arc::clone(&metrics))?;
+
+        run(config, metrics).await
+    }
+    .await;
+
+    if let Err(error) = result {
+        eprintln!("{error}");
+        process::exit(error.exit_code());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::HttpBody;
+
+    use super::*;
+
+    fn state() -> AppState {
+        let data = Data::load(None).unwrap();
+        let data = LiveData::new(data, None, Logs::default(), None).unwrap();
+
+        AppState {
+            schema: Arc::new(graphql::schema()),
+            data: Arc::new(data),
+            limits: Arc::new(QueryLimits::default()),
+            persisted_queries: Arc::new(PersistedQueries::default()),
+            metrics: Arc::new(Metrics::default()),
+        }
+    }
+
+    async fn post(body: serde_json::Value) -> (StatusCode, serde_json::Value) {
+        let req: BatchRequest = serde_json::from_value(body).unwrap();
+        let response = graphql_post(State(state()), HeaderMap::new(), Json(req)).await;
+        let status = response.status();
+
+        let mut body = response.into_body();
+        let mut bytes = Vec::new();
+        while let Some(chunk) = body.data().await {
+            bytes.extend_from_slice(&chunk.unwrap());
+        }
+
+        (status, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    #[tokio::test]
+    async fn it_reports_the_status_of_every_request_in_a_batch() {
+        let (status, body) = post(serde_json::json!([
+            { "query": "{ __typename }" },
+            { "query": "{ unknownField }" },
+        ]))
+        .await;
+
+        assert_eq!(StatusCode::MULTI_STATUS, status);
+        assert_eq!(200, body[0]["extensions"]["status"]);
+        assert_eq!("Query", body[0]["data"]["__typename"]);
+        assert_eq!(400, body[1]["extensions"]["status"]);
+        assert!(body[1]["errors"].is_array());
+    }
+
+    #[tokio::test]
+    async fn it_answers_a_batch_with_the_status_its_requests_share() {
+        let (status, body) = post(serde_json::json!([
+            { "query": "{ unknownField }" },
+            { "query": "{ otherUnknownField }" },
+        ]))
+        .await;
+
+        assert_eq!(StatusCode::BAD_REQUEST, status);
+        assert_eq!(400, body[0]["extensions"]["status"]);
+        assert_eq!(400, body[1]["extensions"]["status"]);
+    }
 }
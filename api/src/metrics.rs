@@ -0,0 +1,340 @@
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Write},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tracing::{
+    field::{Field, Visit},
+    info, Event, Subscriber,
+};
+use tracing_subscriber::{layer::Context, Layer};
+
+/// Target of the events the metrics are recorded from
+pub const TARGET: &str = "shield_hq::metrics";
+/// Upper bounds of the request duration histogram, in seconds
+const BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+/// Operation names are chosen by clients, so the number of labels is capped
+const MAX_OPERATIONS: usize = 100;
+const ANONYMOUS: &str = "anonymous";
+const OTHER: &str = "other";
+
+/// Records a GraphQL request for the metrics layer
+pub fn request(operation: Option<&str>, duration: Duration, errors: usize) {
+    info!(
+        target: TARGET,
+        operation = operation.unwrap_or(ANONYMOUS),
+        duration = duration.as_secs_f64(),
+        errors = errors as u64,
+    );
+}
+
+/// Records a (re)load of the data for the metrics layer
+pub fn data_loaded(cards: usize, products: usize, sets: usize) {
+    info!(
+        target: TARGET,
+        cards = cards as u64,
+        products = products as u64,
+        sets = sets as u64,
+    );
+}
+
+#[derive(Default)]
+struct Operation {
+    requests: u64,
+    errors: u64,
+    buckets: [u64; BUCKETS.len()],
+    duration: f64,
+}
+
+#[derive(Default)]
+struct Data {
+    cards: u64,
+    products: u64,
+    sets: u64,
+    loaded_at: f64,
+}
+
+#[derive(Default)]
+pub struct Metrics {
+    operations: Mutex<BTreeMap<String, Operation>>,
+    data: Mutex<Option<Data>>,
+}
+
+impl Metrics {
+    /// Ready once the data has been loaded and validated
+    pub fn is_ready(&self) -> bool {
+        self.data.lock().unwrap().is_some()
+    }
+
+    fn record_request(&self, operation: &str, duration: f64, errors: u64) {
+        let mut operations = self.operations.lock().unwrap();
+        let name = if operations.contains_key(operation) || operations.len() < MAX_OPERATIONS - 1 {
+            operation
+        } else {
+            OTHER
+        };
+        let operation = operations.entry(String::from(name)).or_default();
+
+        operation.requests += 1;
+        operation.errors += errors;
+        operation.duration += duration;
+        for (bucket, le) in operation.buckets.iter_mut().zip(BUCKETS) {
+            if duration <= le {
+                *bucket += 1;
+            }
+        }
+    }
+
+    fn record_data(&self, cards: u64, products: u64, sets: u64) {
+        let loaded_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64();
+
+        *self.data.lock().unwrap() = Some(Data {
+            cards,
+            products,
+            sets,
+            loaded_at,
+        });
+    }
+
+    /// Renders the metrics in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        // writing to a String can't fail
+        let _ = self.write(&mut out);
+
+        out
+    }
+
+    fn write(&self, out: &mut String) -> fmt::Result {
+        let operations = self.operations.lock().unwrap();
+
+        writeln!(
+            out,
+            "# HELP shq_graphql_requests_total GraphQL requests by operation name."
+        )?;
+        writeln!(out, "# TYPE shq_graphql_requests_total counter")?;
+        for (name, operation) in operations.iter() {
+            let name = escape(name);
+            writeln!(
+                out,
+                "shq_graphql_requests_total{{operation=\"{name}\"}} {}",
+                operation.requests
+            )?;
+        }
+
+        writeln!(
+            out,
+            "# HELP shq_graphql_errors_total GraphQL errors returned by operation name."
+        )?;
+        writeln!(out, "# TYPE shq_graphql_errors_total counter")?;
+        for (name, operation) in operations.iter() {
+            let name = escape(name);
+            writeln!(
+                out,
+                "shq_graphql_errors_total{{operation=\"{name}\"}} {}",
+                operation.errors
+            )?;
+        }
+
+        writeln!(
+            out,
+            "# HELP shq_graphql_request_duration_seconds GraphQL request latency by operation name."
+        )?;
+        writeln!(out, "# TYPE shq_graphql_request_duration_seconds histogram")?;
+        for (name, operation) in operations.iter() {
+            let name = escape(name);
+            for (count, le) in operation.buckets.iter().zip(BUCKETS) {
+                writeln!(
+                    out,
+                    "shq_graphql_request_duration_seconds_bucket{{operation=\"{name}\",le=\"{le}\"}} {count}"
+                )?;
+            }
+            writeln!(
+                out,
+                "shq_graphql_request_duration_seconds_bucket{{operation=\"{name}\",le=\"+Inf\"}} {}",
+                operation.requests
+            )?;
+            writeln!(
+                out,
+                "shq_graphql_request_duration_seconds_sum{{operation=\"{name}\"}} {}",
+                operation.duration
+            )?;
+            writeln!(
+                out,
+                "shq_graphql_request_duration_seconds_count{{operation=\"{name}\"}} {}",
+                operation.requests
+            )?;
+        }
+
+        if let Some(data) = self.data.lock().unwrap().as_ref() {
+            for (name, help, value) in [
+                ("shq_cards", "Loaded cards.", data.cards),
+                ("shq_products", "Loaded products.", data.products),
+                ("shq_sets", "Loaded sets.", data.sets),
+            ] {
+                writeln!(out, "# HELP {name} {help}")?;
+                writeln!(out, "# TYPE {name} gauge")?;
+                writeln!(out, "{name} {value}")?;
+            }
+            writeln!(
+                out,
+                "# HELP shq_data_loaded_timestamp_seconds Unix time the data was last loaded."
+            )?;
+            writeln!(out, "# TYPE shq_data_loaded_timestamp_seconds gauge")?;
+            writeln!(out, "shq_data_loaded_timestamp_seconds {}", data.loaded_at)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn escape(label: &str) -> String {
+    label
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[derive(Default)]
+struct Visitor {
+    operation: Option<String>,
+    duration: f64,
+    errors: u64,
+    cards: Option<u64>,
+    products: u64,
+    sets: u64,
+}
+
+impl Visit for Visitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "operation" {
+            self.operation = Some(String::from(value));
+        }
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        if field.name() == "duration" {
+            self.duration = value;
+        }
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        match field.name() {
+            "errors" => self.errors = value,
+            "cards" => self.cards = Some(value),
+            "products" => self.products = value,
+            "sets" => self.sets = value,
+            _ => (),
+        }
+    }
+
+    fn record_debug(&mut self, _field: &Field, _value: &dyn fmt::Debug) {}
+}
+
+/// Updates the metrics from the events sent by [`request`] and [`data_loaded`]
+pub struct MetricsLayer {
+    metrics: Arc<Metrics>,
+}
+
+impl MetricsLayer {
+    pub fn new(metrics: Arc<Metrics>) -> Self {
+        Self { metrics }
+    }
+}
+
+impl<S: Subscriber> Layer<S> for MetricsLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        if event.metadata().target() != TARGET {
+            return;
+        }
+
+        let mut visitor = Visitor::default();
+        event.record(&mut visitor);
+        if let Some(operation) = &visitor.operation {
+            self.metrics
+                .record_request(operation, visitor.duration, visitor.errors);
+        } else if let Some(cards) = visitor.cards {
+            self.metrics
+                .record_data(cards, visitor.products, visitor.sets);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    fn record(f: impl FnOnce()) -> Arc<Metrics> {
+        let metrics = Arc::new(Metrics::default());
+        let subscriber =
+            tracing_subscriber::registry().with(MetricsLayer::new(Arc::clone(&metrics)));
+        tracing::subscriber::with_default(subscriber, f);
+
+        metrics
+    }
+
+    #[test]
+    fn it_counts_requests_by_operation() {
+        let metrics = record(|| {
+            request(Some("Cards"), Duration::from_millis(20), 0);
+            request(Some("Cards"), Duration::from_millis(200), 2);
+            request(None, Duration::from_millis(1), 0);
+        });
+        let output = metrics.render();
+
+        assert!(output.contains("shq_graphql_requests_total{operation=\"Cards\"} 2\n"));
+        assert!(output.contains("shq_graphql_requests_total{operation=\"anonymous\"} 1\n"));
+        assert!(output.contains("shq_graphql_errors_total{operation=\"Cards\"} 2\n"));
+        assert!(output.contains(
+            "shq_graphql_request_duration_seconds_bucket{operation=\"Cards\",le=\"0.025\"} 1\n"
+        ));
+        assert!(output.contains(
+            "shq_graphql_request_duration_seconds_bucket{operation=\"Cards\",le=\"0.25\"} 2\n"
+        ));
+    }
+
+    #[test]
+    fn it_caps_operation_names() {
+        let metrics = record(|| {
+            for i in 0..MAX_OPERATIONS * 2 {
+                request(Some(&format!("Query{i}")), Duration::ZERO, 0);
+            }
+        });
+        let output = metrics.render();
+
+        assert_eq!(
+            MAX_OPERATIONS,
+            output.matches("shq_graphql_requests_total{").count()
+        );
+        assert!(output.contains(&format!(
+            "shq_graphql_requests_total{{operation=\"other\"}} {}\n",
+            MAX_OPERATIONS + 1
+        )));
+    }
+
+    #[test]
+    fn it_is_ready_after_loading_data() {
+        let metrics = record(|| ());
+        assert!(!metrics.is_ready());
+
+        let metrics = record(|| data_loaded(38, 1, 6));
+        let output = metrics.render();
+
+        assert!(metrics.is_ready());
+        assert!(output.contains("shq_cards 38\n"));
+        assert!(output.contains("shq_sets 6\n"));
+        assert!(output.contains("shq_data_loaded_timestamp_seconds "));
+    }
+
+    #[test]
+    fn it_escapes_labels() {
+        assert_eq!(r#"a\"b\\c\n"#, escape("a\"b\\c\n"));
+    }
+}
@@ -0,0 +1,228 @@
+use crate::{cache::sha256, request::PersistedQuery};
+use std::{
+    collections::{HashMap, VecDeque},
+    fs, io,
+    path::Path,
+    sync::RwLock,
+};
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum PersistedQueryError {
+    // Apollo clients look for this exact message before resending the full query
+    #[error("PersistedQueryNotFound")]
+    NotFound,
+    #[error("provided sha does not match query")]
+    HashMismatch,
+    #[error("Unsupported persisted query version: {0}")]
+    UnsupportedVersion(u32),
+    #[error("Must provide a query or a persisted query hash")]
+    MissingQuery,
+}
+
+impl PersistedQueryError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            PersistedQueryError::NotFound => "PERSISTED_QUERY_NOT_FOUND",
+            PersistedQueryError::HashMismatch => "PERSISTED_QUERY_HASH_MISMATCH",
+            PersistedQueryError::UnsupportedVersion(_) => "PERSISTED_QUERY_NOT_SUPPORTED",
+            PersistedQueryError::MissingQuery => "MISSING_QUERY",
+        }
+    }
+}
+
+/// Most queries clients can register, after which the oldest registration is evicted
+const MAX_REGISTERED: usize = 1000;
+
+/// In memory store of Automatic Persisted Queries, keyed by the sha256 hash of the query
+pub struct PersistedQueries {
+    /// Queries loaded from disk, which are never evicted
+    seeded: HashMap<String, String>,
+    registered: RwLock<Registered>,
+    max_registered: usize,
+}
+
+/// Queries registered by clients, with their hashes in the order they were registered
+#[derive(Default)]
+struct Registered {
+    queries: HashMap<String, String>,
+    order: VecDeque<String>,
+}
+
+impl Default for PersistedQueries {
+    fn default() -> Self {
+        Self::with_capacity(MAX_REGISTERED)
+    }
+}
+
+impl PersistedQueries {
+    /// A store that holds at most `max_registered` queries registered by clients
+    pub fn with_capacity(max_registered: usize) -> Self {
+        Self {
+            seeded: HashMap::new(),
+            registered: RwLock::default(),
+            max_registered,
+        }
+    }
+
+    /// Seeds the store with every `.graphql` file in a directory
+    pub fn from_dir(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut persisted_queries = Self::default();
+
+        for entry in fs::read_dir(path)? {
+            let path = entry?.path();
+            if path
+                .extension()
+                .is_some_and(|extension| extension == "graphql")
+            {
+                let query = fs::read_to_string(path)?;
+                persisted_queries.seeded.insert(sha256(&query), query);
+            }
+        }
+
+        Ok(persisted_queries)
+    }
+
+    pub fn get(&self, hash: &str) -> Option<String> {
+        self.seeded
+            .get(hash)
+            .or(self.registered.read().unwrap().queries.get(hash))
+            .cloned()
+    }
+
+    /// Registers a query sent with its hash, evicting the oldest registration once the store is
+    /// full. Only queries that passed the query limits should be registered.
+    pub fn insert(&self, query: String) -> String {
+        let hash = sha256(&query);
+        if self.seeded.contains_key(&hash) || self.max_registered == 0 {
+            return hash;
+        }
+
+        let mut registered = self.registered.write().unwrap();
+        if registered.queries.insert(hash.clone(), query).is_none() {
+            registered.order.push_back(hash.clone());
+        }
+        while registered.order.len() > self.max_registered {
+            if let Some(oldest) = registered.order.pop_front() {
+                registered.queries.remove(&oldest);
+            }
+        }
+
+        hash
+    }
+
+    pub fn len(&self) -> usize {
+        self.seeded.len() + self.registered.read().unwrap().queries.len()
+    }
+
+    /// Finds the query to run. A query sent with its hash is checked against it, but isn't
+    /// registered until it's known to be within the query limits.
+    pub fn resolve(
+        &self,
+        query: Option<String>,
+        persisted_query: Option<&PersistedQuery>,
+    ) -> Result<String, PersistedQueryError> {
+        match (query, persisted_query) {
+            (_, Some(persisted_query)) if persisted_query.version != 1 => Err(
+                PersistedQueryError::UnsupportedVersion(persisted_query.version),
+            ),
+            (Some(query), Some(persisted_query)) => {
+                if sha256(&query) == persisted_query.sha256_hash {
+                    Ok(query)
+                } else {
+                    Err(PersistedQueryError::HashMismatch)
+                }
+            }
+            (None, Some(persisted_query)) => self
+                .get(&persisted_query.sha256_hash)
+                .ok_or(PersistedQueryError::NotFound),
+            (Some(query), None) => Ok(query),
+            (None, None) => Err(PersistedQueryError::MissingQuery),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const QUERY: &str = "{ cards { aspect } }";
+
+    fn persisted_query(query: &str) -> PersistedQuery {
+        PersistedQuery {
+            version: 1,
+            sha256_hash: sha256(query),
+        }
+    }
+
+    #[test]
+    fn it_registers_queries_with_hashes() {
+        let persisted_queries = PersistedQueries::default();
+        let persisted_query = persisted_query(QUERY);
+
+        assert_eq!(
+            Err(PersistedQueryError::NotFound),
+            persisted_queries.resolve(None, Some(&persisted_query))
+        );
+        assert_eq!(
+            Ok(String::from(QUERY)),
+            persisted_queries.resolve(Some(String::from(QUERY)), Some(&persisted_query))
+        );
+        // resolving leaves registering to the caller, once the query is within the limits
+        assert_eq!(0, persisted_queries.len());
+
+        persisted_queries.insert(String::from(QUERY));
+        assert_eq!(
+            Ok(String::from(QUERY)),
+            persisted_queries.resolve(None, Some(&persisted_query))
+        );
+    }
+
+    #[test]
+    fn it_evicts_the_oldest_registration() {
+        let persisted_queries = PersistedQueries::with_capacity(2);
+        let queries = [
+            "{ cards { aspect } }",
+            "{ products { name } }",
+            "{ traits { name } }",
+        ];
+        for query in queries {
+            persisted_queries.insert(String::from(query));
+        }
+
+        assert_eq!(2, persisted_queries.len());
+        assert_eq!(None, persisted_queries.get(&sha256(queries[0])));
+        assert!(persisted_queries.get(&sha256(queries[2])).is_some());
+    }
+
+    #[test]
+    fn it_rejects_mismatched_hashes() {
+        let persisted_queries = PersistedQueries::default();
+
+        assert_eq!(
+            Err(PersistedQueryError::HashMismatch),
+            persisted_queries.resolve(
+                Some(String::from(QUERY)),
+                Some(&persisted_query("{ products { name } }"))
+            )
+        );
+        assert_eq!(0, persisted_queries.len());
+    }
+
+    #[test]
+    fn it_seeds_from_a_directory() {
+        let dir = std::env::temp_dir().join(format!("shq-persisted-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("cards.graphql"), QUERY).unwrap();
+        fs::write(dir.join("README.md"), "not a query").unwrap();
+
+        let persisted_queries = PersistedQueries::from_dir(&dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(1, persisted_queries.len());
+        assert_eq!(
+            Some(String::from(QUERY)),
+            persisted_queries.get(&sha256(QUERY))
+        );
+    }
+}
@@ -0,0 +1,318 @@
+use crate::{
+    loader::{Data, LoadError},
+    metrics,
+};
+use data::{
+    campaign::CampaignLogs,
+    card::Card,
+    card_store::SqliteStore,
+    graphql::{subscription, Ctx, DataEvent, DataReload, Published},
+    play::Plays,
+    product::Product,
+};
+use serde::Serialize;
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+    sync::{Arc, RwLock},
+};
+use tokio::sync::broadcast;
+use tracing::{error, info};
+
+/// The data requests run against, which a reload swaps out as a whole
+pub struct Snapshot {
+    pub ctx: Arc<Ctx>,
+    /// Hash of the data, changing whenever the data does
+    pub version: String,
+    pub logs: Logs,
+}
+
+/// Logs kept by the server. They aren't part of the data, so every snapshot shares them.
+#[derive(Clone, Default)]
+pub struct Logs {
+    pub campaigns: Arc<CampaignLogs>,
+    pub plays: Arc<Plays>,
+}
+
+impl Logs {
+    /// Changes whenever a log does
+    pub fn revision(&self) -> String {
+        format!("{}.{}", self.campaigns.revision(), self.plays.revision())
+    }
+}
+
+pub struct LiveData {
+    data_dir: Option<PathBuf>,
+    current: RwLock<Arc<Snapshot>>,
+    events: broadcast::Sender<DataEvent>,
+    logs: Logs,
+    /// Database cards and products are served from instead of memory, refilled on every load
+    database: Option<Arc<SqliteStore>>,
+}
+
+impl LiveData {
+    pub fn new(
+        data: Data,
+        data_dir: Option<PathBuf>,
+        logs: Logs,
+        database: Option<Arc<SqliteStore>>,
+    ) -> Result<Self, LoadError> {
+        let events = subscription::channel();
+        let snapshot = snapshot(data, events.clone(), logs.clone(), database.as_ref())?;
+        record(&snapshot.ctx);
+
+        Ok(Self {
+            data_dir,
+            current: RwLock::new(Arc::new(snapshot)),
+            events,
+            logs,
+            database,
+        })
+    }
+
+    pub fn current(&self) -> Arc<Snapshot> {
+        Arc::clone(&self.current.read().unwrap())
+    }
+
+    /// Loads the data again, publishing the cards and products that were added, changed or
+    /// removed. The current data is kept when the new data doesn't load.
+    pub fn reload(&self) -> Result<DataReload, LoadError> {
+        let data = Data::load(self.data_dir.as_deref())?;
+        let snapshot = snapshot(
+            data,
+            self.events.clone(),
+            self.logs.clone(),
+            self.database.as_ref(),
+        )?;
+        let ctx = Arc::clone(&snapshot.ctx);
+        let old = self.current();
+        let cards = changed(&old.ctx.cards, &ctx.cards, Card::code);
+        let product_code = |product: &Product| Some(product.code.clone());
+        let products = changed(&old.ctx.products, &ctx.products, product_code);
+        let removed_cards = removed(&old.ctx.cards, &ctx.cards, Card::code);
+        let removed_products = removed(&old.ctx.products, &ctx.products, product_code);
+        let reload = DataReload {
+            version: snapshot.version.clone(),
+            cards_count: ctx.cards.len() as i32,
+            products_count: ctx.products.len() as i32,
+            changed_cards_count: cards.len() as i32,
+            changed_products_count: products.len() as i32,
+        };
+
+        record(&ctx);
+        *self.current.write().unwrap() = Arc::new(snapshot);
+        for card in cards {
+            ctx.publish(DataEvent::CardChanged(Published::new(
+                Arc::clone(card),
+                Arc::clone(&ctx),
+            )));
+        }
+        for code in removed_cards {
+            ctx.publish(DataEvent::CardRemoved(code));
+        }
+        for product in products {
+            ctx.publish(DataEvent::ProductChanged(Published::new(
+                Arc::clone(product),
+                Arc::clone(&ctx),
+            )));
+        }
+        for code in removed_products {
+            ctx.publish(DataEvent::ProductRemoved(code));
+        }
+        ctx.publish(DataEvent::DataReloaded(reload.clone()));
+
+        Ok(reload)
+    }
+
+    /// The channel changes to the data are published on
+    pub fn events(&self) -> broadcast::Sender<DataEvent> {
+        self.events.clone()
+    }
+
+    #[cfg(test)]
+    pub fn subscribe(&self) -> broadcast::Receiver<DataEvent> {
+        self.events.subscribe()
+    }
+}
+
+fn snapshot(
+    data: Data,
+    events: broadcast::Sender<DataEvent>,
+    logs: Logs,
+    database: Option<&Arc<SqliteStore>>,
+) -> Result<Snapshot, LoadError> {
+    let mut ctx = Ctx::new(data.cards, data.products, data.traits)
+        .with_scenarios(data.scenarios)
+        .with_campaign_logs(Arc::clone(&logs.campaigns))
+        .with_plays(Arc::clone(&logs.plays))
+        .with_events(events);
+    if let Some(database) = database {
+        // a version of its own, so requests still reading the current snapshot keep its data
+        let store = database.import(&ctx.cards, &ctx.products)?;
+        ctx = ctx.with_card_store(Arc::new(store));
+    }
+
+    Ok(Snapshot {
+        ctx: Arc::new(ctx),
+        version: data.version,
+        logs,
+    })
+}
+
+fn record(ctx: &Ctx) {
+    metrics::data_loaded(ctx.cards.len(), ctx.products.len(), ctx.sets().count());
+}
+
+/// Items in `new` that weren't in `old`, or that differ from the item with the same key
+fn changed<'a, T: Serialize>(
+    old: &[Arc<T>],
+    new: &'a [Arc<T>],
+    key: impl Fn(&T) -> Option<String>,
+) -> Vec<&'a Arc<T>> {
+    let old: HashMap<_, _> = old
+        .iter()
+        .filter_map(|item| Some((key(item)?, serde_json::to_value(item.as_ref()).ok()?)))
+        .collect();
+
+    new.iter()
+        .filter(|item| {
+            let previous = key(item).and_then(|key| old.get(&key));
+            previous.is_none() || previous.cloned() != serde_json::to_value(item.as_ref()).ok()
+        })
+        .collect()
+}
+
+/// Keys of the items in `old` that aren't in `new`
+fn removed<T>(old: &[Arc<T>], new: &[Arc<T>], key: impl Fn(&T) -> Option<String>) -> Vec<String> {
+    let new: HashSet<_> = new.iter().filter_map(|item| key(item)).collect();
+
+    old.iter()
+        .filter_map(|item| key(item))
+        .filter(|key| !new.contains(key))
+        .collect()
+}
+
+/// Reloads the data whenever the process receives SIGHUP
+#[cfg(unix)]
+pub async fn reload_on_hangup(data: Arc<LiveData>) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangup = match signal(SignalKind::hangup()) {
+        Ok(hangup) => hangup,
+        Err(error) => {
+            error!("could not listen for SIGHUP: {}", error);
+            return;
+        }
+    };
+
+    while hangup.recv().await.is_some() {
+        match data.reload() {
+            Ok(reload) => info!(
+                version = %reload.version,
+                changed_cards = reload.changed_cards_count,
+                changed_products = reload.changed_products_count,
+                "reloaded data"
+            ),
+            Err(error) => error!("could not reload data, keeping the current data: {}", error),
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub async fn reload_on_hangup(_data: Arc<LiveData>) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, path::Path};
+
+    fn data_dir(name: &str) -> PathBuf {
+        let source = Path::new(env!("CARGO_MANIFEST_DIR")).join("../data/data");
+        let dir = std::env::temp_dir().join(format!("shq-reload-{}-{name}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for file in [
+            "core-set.toml",
+            "products.toml",
+            "traits.toml",
+            "scenarios.toml",
+        ] {
+            fs::copy(source.join(file), dir.join(file)).unwrap();
+        }
+
+        dir
+    }
+
+    #[test]
+    fn it_finds_changed_items() {
+        let old = vec![Arc::new((1, "a")), Arc::new((2, "b"))];
+        let new = vec![Arc::new((1, "a")), Arc::new((2, "c")), Arc::new((3, "d"))];
+
+        assert_eq!(
+            vec![&new[1], &new[2]],
+            changed(&old, &new, |item| Some(item.0.to_string()))
+        );
+    }
+
+    #[test]
+    fn it_finds_removed_items() {
+        let old = vec![Arc::new((1, "a")), Arc::new((2, "b"))];
+        let new = vec![Arc::new((2, "c"))];
+
+        assert_eq!(
+            vec![String::from("1")],
+            removed(&old, &new, |item| Some(item.0.to_string()))
+        );
+    }
+
+    #[test]
+    fn it_publishes_changes_on_reload() {
+        let dir = data_dir("changes");
+        let data = LiveData::new(
+            Data::load(Some(&dir)).unwrap(),
+            Some(dir.clone()),
+            Logs::default(),
+            None,
+        )
+        .unwrap();
+        let mut events = data.subscribe();
+        let version = data.current().version.clone();
+
+        let core_set = fs::read_to_string(dir.join("core-set.toml")).unwrap();
+        fs::write(
+            dir.join("core-set.toml"),
+            core_set.replacen("name = \"Spider-Man\"", "name = \"Spider-Man!\"", 1),
+        )
+        .unwrap();
+        let reload = data.reload().unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(1, reload.changed_cards_count);
+        assert_eq!(0, reload.changed_products_count);
+        assert_ne!(version, data.current().version);
+        assert!(matches!(events.try_recv(), Ok(DataEvent::CardChanged(_))));
+        assert!(matches!(
+            events.try_recv(),
+            Ok(DataEvent::DataReloaded(published)) if published == reload
+        ));
+    }
+
+    #[test]
+    fn it_keeps_the_data_when_a_reload_fails() {
+        let dir = data_dir("failure");
+        let data = LiveData::new(
+            Data::load(Some(&dir)).unwrap(),
+            Some(dir.clone()),
+            Logs::default(),
+            None,
+        )
+        .unwrap();
+        let version = data.current().version.clone();
+
+        fs::write(dir.join("core-set.toml"), "[[card]]").unwrap();
+        let reload = data.reload();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(reload.is_err());
+        assert_eq!(version, data.current().version);
+    }
+}
@@ -0,0 +1,152 @@
+use data::graphql::SHQScalarValue;
+use juniper::{http::GraphQLRequest, InputValue};
+use serde::Deserialize;
+
+/// Mirrors `GraphQLRequest`, which doesn't expose its query, so the query can be inspected before
+/// it's executed. The query is optional since persisted queries can be sent as just a hash.
+#[derive(Debug, Deserialize)]
+pub struct Request {
+    pub query: Option<String>,
+    #[serde(rename = "operationName")]
+    pub operation_name: Option<String>,
+    pub variables: Option<InputValue<SHQScalarValue>>,
+    pub extensions: Option<Extensions>,
+}
+
+/// A POST body, holding either a single request or a batch of them
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum BatchRequest {
+    Single(Request),
+    Batch(Vec<Request>),
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Extensions {
+    #[serde(rename = "persistedQuery")]
+    pub persisted_query: Option<PersistedQuery>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PersistedQuery {
+    pub version: u32,
+    #[serde(rename = "sha256Hash")]
+    pub sha256_hash: String,
+}
+
+/// A GET request, where `variables` and `extensions` are JSON encoded in the query string
+#[derive(Debug, Deserialize)]
+pub struct GetRequest {
+    pub query: Option<String>,
+    #[serde(rename = "operationName")]
+    pub operation_name: Option<String>,
+    pub variables: Option<String>,
+    pub extensions: Option<String>,
+}
+
+impl Request {
+    pub fn persisted_query(&self) -> Option<&PersistedQuery> {
+        self.extensions
+            .as_ref()
+            .and_then(|extensions| extensions.persisted_query.as_ref())
+    }
+
+    /// Names of the variables sent with the request, so they can be logged without their values
+    pub fn variable_names(&self) -> Vec<&str> {
+        self.variables
+            .as_ref()
+            .and_then(InputValue::to_object_value)
+            .map(|variables| variables.into_keys().collect())
+            .unwrap_or_default()
+    }
+
+    pub fn into_graphql(self, query: String) -> GraphQLRequest<SHQScalarValue> {
+        GraphQLRequest::new(query, self.operation_name, self.variables)
+    }
+}
+
+impl TryFrom<GetRequest> for Request {
+    type Error = serde_json::Error;
+
+    fn try_from(request: GetRequest) -> Result<Self, Self::Error> {
+        let variables = request
+            .variables
+            .as_deref()
+            .map(serde_json::from_str)
+            .transpose()?;
+        let extensions = request
+            .extensions
+            .as_deref()
+            .map(serde_json::from_str)
+            .transpose()?;
+
+        Ok(Request {
+            query: request.query,
+            operation_name: request.operation_name,
+            variables,
+            extensions,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_parses_get_requests() {
+        let request = Request::try_from(GetRequest {
+            query: None,
+            operation_name: Some(String::from("Cards")),
+            variables: Some(String::from(r#"{"code": "MC01en"}"#)),
+            extensions: Some(String::from(
+                r#"{"persistedQuery": {"version": 1, "sha256Hash": "abc"}}"#,
+            )),
+        })
+        .unwrap();
+
+        assert_eq!(Some("Cards"), request.operation_name.as_deref());
+        assert!(request.variables.is_some());
+        assert_eq!(
+            Some("abc"),
+            request
+                .persisted_query()
+                .map(|persisted_query| persisted_query.sha256_hash.as_str())
+        );
+    }
+
+    #[test]
+    fn it_rejects_invalid_json_in_get_requests() {
+        let request = Request::try_from(GetRequest {
+            query: Some(String::from("{ cards { aspect } }")),
+            operation_name: None,
+            variables: Some(String::from("{")),
+            extensions: None,
+        });
+
+        assert!(request.is_err());
+    }
+
+    #[test]
+    fn it_lists_variable_names() {
+        let request: Request = serde_json::from_str(
+            r#"{"query": "{ cards { aspect } }", "variables": {"code": "MC01en", "first": 10}}"#,
+        )
+        .unwrap();
+
+        assert_eq!(vec!["code", "first"], request.variable_names());
+    }
+
+    #[test]
+    fn it_parses_batches() {
+        let single: BatchRequest =
+            serde_json::from_str(r#"{"query": "{ traits { name } }"}"#).unwrap();
+        let batch: BatchRequest = serde_json::from_str(
+            r#"[{"query": "{ traits { name } }"}, {"query": "{ products { name } }"}]"#,
+        )
+        .unwrap();
+
+        assert!(matches!(single, BatchRequest::Single(_)));
+        assert!(matches!(batch, BatchRequest::Batch(requests) if requests.len() == 2));
+    }
+}
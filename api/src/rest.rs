@@ -0,0 +1,569 @@
+use crate::AppState;
+use axum::{
+    extract::{rejection::QueryRejection, Path, Query as QueryString, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use data::{
+    card::{
+        card_side::CardSideInputBuilder, Aspect, Card, CardInput, CardProductInput, CardSetInput,
+        Keyword, ParseKeywordError, TraitError,
+    },
+    card_store::CardStoreError,
+    graphql::Ctx,
+    product::{Product, ProductInput, ProductType, SetInput, SetType},
+};
+use serde::{Deserialize, Serialize};
+use std::{str::FromStr, sync::Arc};
+use tracing::error;
+use utoipa::{IntoParams, ToSchema};
+
+mod openapi;
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/cards", get(cards))
+        .route("/cards/:code", get(card))
+        .route("/products", get(products))
+        .route("/products/:code", get(product))
+        .route("/sets/:name", get(set))
+        .route("/openapi.json", get(openapi::openapi))
+}
+
+pub enum RestError {
+    BadRequest(String),
+    NotFound(String),
+    Internal,
+}
+
+#[derive(Serialize, ToSchema)]
+struct ErrorResponse {
+    error: String,
+}
+
+impl IntoResponse for RestError {
+    fn into_response(self) -> Response {
+        let (status, error) = match self {
+            RestError::BadRequest(error) => (StatusCode::BAD_REQUEST, error),
+            RestError::NotFound(error) => (StatusCode::NOT_FOUND, error),
+            RestError::Internal => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                String::from("Internal server error"),
+            ),
+        };
+
+        (status, Json(ErrorResponse { error })).into_response()
+    }
+}
+
+impl From<QueryRejection> for RestError {
+    fn from(rejection: QueryRejection) -> Self {
+        RestError::BadRequest(rejection.to_string())
+    }
+}
+
+impl From<CardStoreError> for RestError {
+    fn from(error: CardStoreError) -> Self {
+        error!("{}", error);
+
+        RestError::Internal
+    }
+}
+
+impl From<TraitError> for RestError {
+    fn from(error: TraitError) -> Self {
+        RestError::BadRequest(error.to_string())
+    }
+}
+
+impl From<ParseKeywordError> for RestError {
+    fn from(error: ParseKeywordError) -> Self {
+        RestError::BadRequest(error.to_string())
+    }
+}
+
+/// Query string filters for cards. Lists are comma separated, i.e. `trait=Avenger,Spy`.
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+#[serde(deny_unknown_fields)]
+pub struct CardParams {
+    /// Aspect of the card
+    pub aspect: Option<Aspect>,
+    /// Code of a product the card is printed in
+    #[param(example = "MC01en")]
+    pub product: Option<String>,
+    /// Wave of a product the card is printed in
+    pub wave: Option<u32>,
+    /// Name of a set the card belongs to
+    #[param(example = "Spider-Man")]
+    pub set: Option<String>,
+    /// Type of a set the card belongs to
+    pub set_type: Option<SetType>,
+    /// Name of a side of the card
+    pub name: Option<String>,
+    /// Comma separated traits, matching cards with any of them
+    #[param(example = "Avenger,Spy")]
+    pub r#trait: Option<String>,
+    /// Comma separated keywords, matching cards with any of them
+    #[param(example = "Quickstrike")]
+    pub keyword: Option<String>,
+    /// Whether a side of the card is unique
+    pub unique: Option<bool>,
+}
+
+/// Query string filters for products
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+#[serde(deny_unknown_fields)]
+pub struct ProductParams {
+    /// Name of the product
+    pub name: Option<String>,
+    /// Type of the product
+    pub r#type: Option<ProductType>,
+    /// Wave the product was released in
+    pub wave: Option<u32>,
+    /// Name of a set in the product
+    #[param(example = "Rhino")]
+    pub set: Option<String>,
+    /// Type of a set in the product
+    pub set_type: Option<SetType>,
+}
+
+/// Query string for a set, when its name is shared by sets of several products
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+#[serde(deny_unknown_fields)]
+pub struct SetParams {
+    /// Code of the product the set is in
+    #[param(example = "MC01en")]
+    pub product: Option<String>,
+}
+
+fn split(list: Option<String>) -> Option<Vec<String>> {
+    list.map(|list| {
+        list.split(',')
+            .map(|item| String::from(item.trim()))
+            .collect()
+    })
+}
+
+impl TryFrom<CardParams> for CardInput {
+    type Error = ParseKeywordError;
+
+    fn try_from(params: CardParams) -> Result<Self, Self::Error> {
+        let keywords = split(params.keyword)
+            .map(|keywords| {
+                keywords
+                    .iter()
+                    .map(|keyword| Keyword::from_str(keyword))
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .transpose()?;
+        let sets = if params.set.is_some() || params.set_type.is_some() {
+            Some(Some(vec![CardSetInput {
+                code: None,
+                name: params.set,
+                positions: None,
+                r#type: params.set_type,
+            }]))
+        } else {
+            None
+        };
+        let products = if params.product.is_some() || params.wave.is_some() || sets.is_some() {
+            Some(vec![CardProductInput {
+                code: params.product,
+                positions: None,
+                sets,
+                name: None,
+                release_date: None,
+                r#type: None,
+                wave: params.wave,
+            }])
+        } else {
+            None
+        };
+        let traits = split(params.r#trait);
+        let sides = if params.name.is_some()
+            || traits.is_some()
+            || keywords.is_some()
+            || params.unique.is_some()
+        {
+            let side = CardSideInputBuilder::default()
+                .name(params.name)
+                .traits(traits.map(Some))
+                .keywords(keywords.map(Some))
+                .unique(params.unique.map(Some))
+                .build()
+                .expect("every CardSideInput field has a default");
+            Some(vec![side])
+        } else {
+            None
+        };
+
+        Ok(CardInput {
+            aspect: params.aspect.map(Some),
+            products,
+            sides,
+        })
+    }
+}
+
+impl From<ProductParams> for ProductInput {
+    fn from(params: ProductParams) -> Self {
+        let sets = if params.set.is_some() || params.set_type.is_some() {
+            Some(vec![SetInput {
+                code: None,
+                name: params.set,
+                r#type: params.set_type,
+            }])
+        } else {
+            None
+        };
+
+        ProductInput {
+            name: params.name,
+            release_date: None,
+            r#type: params.r#type,
+            code: None,
+            wave: params.wave,
+            sets,
+        }
+    }
+}
+
+/// A card with the code of its first printing
+#[derive(Serialize, ToSchema)]
+struct CardResponse<'a> {
+    /// Product code and position, i.e. MC01en-1
+    code: Option<String>,
+    #[serde(flatten)]
+    card: &'a Card,
+}
+
+impl<'a> From<&'a Arc<Card>> for CardResponse<'a> {
+    fn from(card: &'a Arc<Card>) -> Self {
+        CardResponse {
+            code: card.code(),
+            card,
+        }
+    }
+}
+
+/// A set with the codes of the products it's in and its cards
+#[derive(Serialize, ToSchema)]
+struct SetResponse<'a> {
+    name: &'a str,
+    r#type: &'a SetType,
+    products: Vec<&'a str>,
+    cards: Vec<CardResponse<'a>>,
+}
+
+/// List cards
+#[utoipa::path(
+    get,
+    path = "/api/cards",
+    params(CardParams),
+    responses(
+        (status = 200, description = "OK", body = [CardResponse]),
+        (status = 400, description = "Invalid filters", body = ErrorResponse),
+    )
+)]
+async fn cards(
+    State(ctx): State<Arc<Ctx>>,
+    params: Result<QueryString<CardParams>, QueryRejection>,
+) -> Result<Response, RestError> {
+    let QueryString(params) = params?;
+    let input = CardInput::try_from(params)?;
+    input.check_traits(&ctx)?;
+    let cards = ctx.filter_cards(&input)?;
+    let cards: Vec<CardResponse> = cards.iter().map(CardResponse::from).collect();
+
+    Ok(Json(cards).into_response())
+}
+
+/// Get a card by the code of one of its printings
+#[utoipa::path(
+    get,
+    path = "/api/cards/{code}",
+    params(("code" = String, Path, description = "Product code and position, i.e. MC01en-1")),
+    responses(
+        (status = 200, description = "OK", body = CardResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+    )
+)]
+async fn card(
+    State(ctx): State<Arc<Ctx>>,
+    Path(code): Path<String>,
+) -> Result<Response, RestError> {
+    let card = ctx
+        .card_store()
+        .card(&code)?
+        .ok_or_else(|| RestError::NotFound(format!("No card with code {code}")))?;
+
+    Ok(Json(CardResponse::from(&card)).into_response())
+}
+
+/// List products
+#[utoipa::path(
+    get,
+    path = "/api/products",
+    params(ProductParams),
+    responses(
+        (status = 200, description = "OK", body = [Product]),
+        (status = 400, description = "Invalid filters", body = ErrorResponse),
+    )
+)]
+async fn products(
+    State(ctx): State<Arc<Ctx>>,
+    params: Result<QueryString<ProductParams>, QueryRejection>,
+) -> Result<Response, RestError> {
+    let QueryString(params) = params?;
+    let input = ProductInput::from(params);
+    let products = ctx.card_store().products()?;
+    let products: Vec<&Product> = products
+        .iter()
+        .map(Arc::as_ref)
+        .filter(|product| product.included(&input))
+        .collect();
+
+    Ok(Json(products).into_response())
+}
+
+/// Get a product by its code
+#[utoipa::path(
+    get,
+    path = "/api/products/{code}",
+    params(("code" = String, Path, description = "Product code, i.e. MC01en")),
+    responses(
+        (status = 200, description = "OK", body = Product),
+        (status = 404, description = "Not found", body = ErrorResponse),
+    )
+)]
+async fn product(
+    State(ctx): State<Arc<Ctx>>,
+    Path(code): Path<String>,
+) -> Result<Response, RestError> {
+    let product = ctx
+        .card_store()
+        .product(&code)?
+        .ok_or_else(|| RestError::NotFound(format!("No product with code {code}")))?;
+
+    Ok(Json(product.as_ref()).into_response())
+}
+
+/// Get a set and its cards
+#[utoipa::path(
+    get,
+    path = "/api/sets/{name}",
+    params(("name" = String, Path, description = "Set name, i.e. Spider-Man"), SetParams),
+    responses(
+        (status = 200, description = "OK", body = SetResponse),
+        (status = 400, description = "Several sets have the name", body = ErrorResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+    )
+)]
+async fn set(
+    State(ctx): State<Arc<Ctx>>,
+    Path(name): Path<String>,
+    params: Result<QueryString<SetParams>, QueryRejection>,
+) -> Result<Response, RestError> {
+    let QueryString(params) = params?;
+    let matching: Vec<_> = ctx
+        .products
+        .iter()
+        .filter(|product| {
+            params
+                .product
+                .as_ref()
+                .is_none_or(|code| code == &product.code)
+        })
+        .flat_map(|product| {
+            product
+                .sets
+                .iter()
+                .filter(|set| set.name == name)
+                .map(move |set| (product.code.as_str(), set))
+        })
+        .collect();
+    let (product, set) = match matching.as_slice() {
+        [] => return Err(RestError::NotFound(format!("No set named {name}"))),
+        [matching] => *matching,
+        _ => {
+            let products: Vec<_> = matching.iter().map(|(product, _)| *product).collect();
+            return Err(RestError::BadRequest(format!(
+                "Sets named {name} are in {}, pick one with the product parameter",
+                products.join(", ")
+            )));
+        }
+    };
+    let cards = ctx
+        .cards
+        .iter()
+        .filter(|card| {
+            card.products.iter().any(|card_product| {
+                card_product.sets.iter().flatten().any(|card_set| {
+                    card_set
+                        .set(&ctx)
+                        .is_some_and(|other| std::ptr::eq(other, set))
+                })
+            })
+        })
+        .map(CardResponse::from)
+        .collect();
+
+    Ok(Json(SetResponse {
+        name: &set.name,
+        r#type: &set.r#type,
+        products: vec![product],
+        cards,
+    })
+    .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::HttpBody;
+
+    use super::*;
+
+    fn ctx() -> Ctx {
+        let cards: data::card::Document =
+            toml::from_str(include_str!("../../data/data/core-set.toml")).unwrap();
+        let products: data::product::Document =
+            toml::from_str(include_str!("../../data/data/products.toml")).unwrap();
+        let traits: data::card::r#trait::Document =
+            toml::from_str(include_str!("../../data/data/traits.toml")).unwrap();
+
+        Ctx::new(cards.cards, products.products, traits.traits)
+    }
+
+    async fn card_names(ctx: &Arc<Ctx>, query: &str) -> Vec<String> {
+        let params: CardParams = serde_urlencoded::from_str(query).unwrap();
+        let Ok(response) = cards(State(Arc::clone(ctx)), Ok(QueryString(params))).await else {
+            panic!("{query} was rejected");
+        };
+        assert_eq!(StatusCode::OK, response.status());
+
+        let mut body = response.into_body();
+        let mut bytes = Vec::new();
+        while let Some(chunk) = body.data().await {
+            bytes.extend_from_slice(&chunk.unwrap());
+        }
+        let cards: Vec<serde_json::Value> = serde_json::from_slice(&bytes).unwrap();
+
+        cards
+            .iter()
+            .map(|card| card["sides"][0]["name"].as_str().unwrap().to_owned())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn it_filters_cards_by_set_and_trait() {
+        let ctx = Arc::new(ctx());
+
+        assert_eq!(
+            vec!["Black Cat"],
+            card_names(&ctx, "set=Spider-Man&trait=Hero%20for%20Hire").await
+        );
+        assert_eq!(
+            vec![
+                "Captain Marvel",
+                "Spider-Woman",
+                "Alpha Flight Station",
+                "Mockingbird"
+            ],
+            card_names(&ctx, "trait=S.H.I.E.L.D.,Spy&unique=true&product=MC01en").await
+        );
+    }
+
+    #[tokio::test]
+    async fn it_filters_cards_by_aspect_and_keyword() {
+        let ctx = Arc::new(ctx());
+
+        assert_eq!(vec!["Mockingbird"], card_names(&ctx, "aspect=Basic").await);
+        assert_eq!(
+            vec!["Vulture"],
+            card_names(&ctx, "keyword=Quickstrike").await
+        );
+    }
+
+    #[tokio::test]
+    async fn it_rejects_unknown_traits() {
+        let ctx = Arc::new(ctx());
+        let params: CardParams = serde_urlencoded::from_str("trait=Hero%20For%20Hire").unwrap();
+
+        assert!(matches!(
+            cards(State(ctx), Ok(QueryString(params))).await,
+            Err(RestError::BadRequest(_))
+        ));
+    }
+
+    #[test]
+    fn it_rejects_invalid_keywords() {
+        let params: CardParams = serde_urlencoded::from_str("keyword=Flying").unwrap();
+
+        assert!(CardInput::try_from(params).is_err());
+    }
+
+    #[test]
+    fn it_filters_products() {
+        let ctx = ctx();
+        let params: ProductParams =
+            serde_urlencoded::from_str("type=Core%20Set&set_type=Villain").unwrap();
+        let input = ProductInput::from(params);
+
+        assert_eq!(
+            1,
+            ctx.products
+                .iter()
+                .filter(|product| product.included(&input))
+                .count()
+        );
+    }
+
+    #[tokio::test]
+    async fn it_tells_sets_sharing_a_name_apart_by_product() {
+        let cards: data::card::Document =
+            toml::from_str(include_str!("../../data/data/core-set.toml")).unwrap();
+        let mut products: data::product::Document =
+            toml::from_str(include_str!("../../data/data/products.toml")).unwrap();
+        let reprint: data::product::Document = toml::from_str(
+            r#"
+            [[product]]
+            name = "Spider-Man Reprint"
+            release_date = "2020-01-01"
+            type = "Custom"
+            code = "SP01en"
+            wave = 1
+
+            [[product.set]]
+            name = "Spider-Man"
+            type = "Nemesis"
+            "#,
+        )
+        .unwrap();
+        products.products.extend(reprint.products);
+        let ctx = Arc::new(Ctx::new(cards.cards, products.products, vec![]));
+        let get = |product: Option<&str>| {
+            set(
+                State(Arc::clone(&ctx)),
+                Path(String::from("Spider-Man")),
+                Ok(QueryString(SetParams {
+                    product: product.map(String::from),
+                })),
+            )
+        };
+
+        assert!(matches!(get(None).await, Err(RestError::BadRequest(_))));
+        assert!(matches!(
+            get(Some("MC01en")).await,
+            Ok(response) if response.status() == StatusCode::OK
+        ));
+        assert!(matches!(
+            get(Some("MC02en")).await,
+            Err(RestError::NotFound(_))
+        ));
+    }
+}
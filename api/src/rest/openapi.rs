@@ -0,0 +1,127 @@
+use super::{CardResponse, ErrorResponse, SetResponse};
+use axum::Json;
+use data::{
+    card::{
+        card_side::{CardSide, CardSideVariant, Side},
+        Aspect, BasicPower, Card, CardProduct, CardSet, Cost, HitPoints, Keyword, Resource,
+        SideSchemeIcon,
+    },
+    product::{Product, ProductType, Set, SetType},
+};
+use utoipa::OpenApi;
+
+/// The OpenAPI document, derived from the REST handlers, their parameters and the types they
+/// serialize
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Shield HQ"),
+    paths(super::cards, super::card, super::products, super::product, super::set),
+    components(schemas(
+        CardResponse,
+        SetResponse,
+        ErrorResponse,
+        Card,
+        CardProduct,
+        CardSet,
+        CardSide,
+        CardSideVariant,
+        Side,
+        Aspect,
+        Resource,
+        SideSchemeIcon,
+        BasicPower,
+        Cost,
+        HitPoints,
+        Keyword,
+        Product,
+        ProductType,
+        Set,
+        SetType,
+    ))
+)]
+pub struct ApiDoc;
+
+pub async fn openapi() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    fn document() -> Value {
+        serde_json::to_value(ApiDoc::openapi()).unwrap()
+    }
+
+    fn query_parameters(document: &Value, path: &str) -> Vec<String> {
+        document["paths"][path]["get"]["parameters"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|parameter| String::from(parameter["name"].as_str().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn it_documents_every_path() {
+        let document = document();
+
+        for path in [
+            "/api/cards",
+            "/api/cards/{code}",
+            "/api/products",
+            "/api/products/{code}",
+            "/api/sets/{name}",
+        ] {
+            assert!(document["paths"][path]["get"].is_object(), "{path}");
+        }
+    }
+
+    #[test]
+    fn it_documents_parameters_by_their_query_names() {
+        let document = document();
+
+        assert_eq!(
+            vec![
+                "aspect", "product", "wave", "set", "set_type", "name", "trait", "keyword",
+                "unique"
+            ],
+            query_parameters(&document, "/api/cards")
+        );
+        assert_eq!(
+            vec!["name", "type", "wave", "set", "set_type"],
+            query_parameters(&document, "/api/products")
+        );
+    }
+
+    #[test]
+    fn it_documents_serialized_names() {
+        let document = document();
+        let schemas = &document["components"]["schemas"];
+
+        assert!(schemas["Card"]["properties"]["products"].is_object());
+        assert!(schemas["CardProduct"]["properties"]["sets"].is_object());
+        assert!(schemas["Product"]["properties"]["sets"].is_object());
+        assert!(schemas["SetType"]["enum"]
+            .as_array()
+            .unwrap()
+            .contains(&Value::from("Hero Signature")));
+        assert!(schemas["Resource"]["enum"]
+            .as_array()
+            .unwrap()
+            .contains(&Value::from("Energy")));
+    }
+
+    #[test]
+    fn it_serializes_what_it_documents() {
+        let cards: data::card::Document =
+            toml::from_str(include_str!("../../../data/data/core-set.toml")).unwrap();
+        let card = serde_json::to_value(&cards.cards[0]).unwrap();
+        let properties = &document()["components"]["schemas"]["Card"]["properties"];
+
+        for (name, _) in card.as_object().unwrap() {
+            assert!(properties[name].is_object(), "{name}");
+        }
+    }
+}
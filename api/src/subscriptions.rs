@@ -0,0 +1,168 @@
+use crate::{
+    limits::{self, QueryLimits},
+    reload::LiveData,
+};
+use axum::extract::ws::{Message, WebSocket};
+use data::graphql::{Ctx, SHQScalarValue, Schema};
+use futures::{channel::mpsc, future, stream, StreamExt};
+use juniper::{OperationType, Variables};
+use juniper_graphql_ws::{ArcSchema, ClientMessage, Connection, ConnectionConfig};
+use serde_json::{json, Value};
+use std::{convert::Infallible, sync::Arc};
+use tracing::debug;
+
+/// Subprotocol of the `subscriptions-transport-ws` protocol implemented by `juniper_graphql_ws`
+pub const PROTOCOL: &str = "graphql-ws";
+/// Subscriptions a single connection can have running at once
+const MAX_IN_FLIGHT_OPERATIONS: usize = 16;
+
+/// A text frame from the client, parsed by the connection so it can report invalid messages
+struct TextMessage(String);
+
+impl TryFrom<TextMessage> for ClientMessage<SHQScalarValue> {
+    type Error = serde_json::Error;
+
+    fn try_from(message: TextMessage) -> Result<Self, Self::Error> {
+        serde_json::from_str(&message.0)
+    }
+}
+
+/// Serves GraphQL subscriptions over a WebSocket until either side closes it
+pub async fn serve(
+    socket: WebSocket,
+    schema: Arc<Schema>,
+    data: Arc<LiveData>,
+    limits: Arc<QueryLimits>,
+) {
+    debug!("subscription connection opened");
+    // events carry the data they were published from, so the connection only needs the channel
+    let init = move |_: Variables<SHQScalarValue>| {
+        let ctx = Ctx::default().with_events(data.events());
+
+        future::ready(Ok::<_, Infallible>(
+            ConnectionConfig::new(ctx).with_max_in_flight_operations(MAX_IN_FLIGHT_OPERATIONS),
+        ))
+    };
+    let (socket_sink, socket_stream) = socket.split();
+    let (connection_sink, connection_stream) =
+        Connection::new(ArcSchema(Arc::clone(&schema)), init).split();
+    let (rejections, rejected) = mpsc::unbounded();
+
+    let incoming = socket_stream
+        .take_while(|message| {
+            future::ready(matches!(message, Ok(message) if !matches!(message, Message::Close(_))))
+        })
+        .filter_map(|message| {
+            let text = match message {
+                Ok(Message::Text(text)) => text,
+                _ => return future::ready(None),
+            };
+            match reject(&schema, &limits, &text) {
+                Some(rejection) => {
+                    let _ = rejections.unbounded_send(rejection);
+                    future::ready(None)
+                }
+                None => future::ready(Some(Ok::<_, Infallible>(TextMessage(text)))),
+            }
+        })
+        .forward(connection_sink);
+    let outgoing = stream::select(
+        connection_stream.map(|message| serde_json::to_string(&message)),
+        rejected.map(|rejection: Value| Ok(rejection.to_string())),
+    )
+    .map(|text| text.map(Message::Text).map_err(axum::Error::new))
+    .forward(socket_sink);
+
+    let _ = future::join(incoming, outgoing).await;
+    debug!("subscription connection closed");
+}
+
+/// The error sent in place of running an operation the client started, when it's over the query
+/// limits or isn't a subscription. Queries and mutations are sent over HTTP, where they run
+/// against the current data.
+fn reject(schema: &Schema, limits: &QueryLimits, text: &str) -> Option<Value> {
+    let message: Value = serde_json::from_str(text).ok()?;
+    if message["type"] != "start" {
+        return None;
+    }
+    let query = message["payload"]["query"].as_str()?;
+    let operation_name = message["payload"]["operationName"].as_str();
+
+    let (error, code) = match limits.check(schema, query, operation_name) {
+        Err(error) => (error.to_string(), error.code()),
+        Ok(()) => match limits::operation_type(schema, query, operation_name) {
+            Some(OperationType::Query) | Some(OperationType::Mutation) => (
+                String::from("Only subscriptions are served over WebSockets"),
+                "SUBSCRIPTIONS_ONLY",
+            ),
+            _ => return None,
+        },
+    };
+
+    Some(json!({
+        "type": "error",
+        "id": message["id"],
+        "payload": { "message": error, "extensions": { "code": code } },
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use data::graphql::schema;
+
+    fn start(query: &str) -> String {
+        json!({ "type": "start", "id": "1", "payload": { "query": query } }).to_string()
+    }
+
+    #[test]
+    fn it_lets_subscriptions_within_the_limits_through() {
+        let limits = QueryLimits::default();
+
+        assert_eq!(
+            None,
+            reject(
+                &schema(),
+                &limits,
+                &start("subscription { dataReloaded { version } }")
+            )
+        );
+        assert_eq!(
+            None,
+            reject(&schema(), &limits, r#"{"type": "connection_init"}"#)
+        );
+    }
+
+    #[test]
+    fn it_rejects_subscriptions_over_the_limits() {
+        let limits = QueryLimits {
+            max_depth: 2,
+            ..QueryLimits::default()
+        };
+        let rejection = reject(
+            &schema(),
+            &limits,
+            &start("subscription { cardChanged { products { sets { name } } } }"),
+        )
+        .unwrap();
+
+        assert_eq!("error", rejection["type"]);
+        assert_eq!("1", rejection["id"]);
+        assert_eq!("QUERY_TOO_DEEP", rejection["payload"]["extensions"]["code"]);
+    }
+
+    #[test]
+    fn it_rejects_queries() {
+        let rejection = reject(
+            &schema(),
+            &QueryLimits::default(),
+            &start("{ traits { name } }"),
+        )
+        .unwrap();
+
+        assert_eq!(
+            "SUBSCRIPTIONS_ONLY",
+            rejection["payload"]["extensions"]["code"]
+        );
+    }
+}
@@ -0,0 +1,39 @@
+use data::{
+    card::{self, Card},
+    product::{self, Product},
+};
+
+/// The core set copied `copies` times under new product codes and set names, so every card
+/// refers to a distinct product and distinct sets
+pub fn synthetic(copies: usize) -> (Vec<Card>, Vec<Product>) {
+    let core_cards: card::Document =
+        toml::from_str(include_str!("../../data/core-set.toml")).unwrap();
+    let core_products: product::Document =
+        toml::from_str(include_str!("../../data/products.toml")).unwrap();
+    let core_set = &core_products.products[0];
+
+    let mut cards = vec![];
+    let mut products = vec![];
+    for copy in 0..copies {
+        let code = format!("SY{copy:04}en");
+        let mut product = core_set.clone();
+        product.code = code.clone();
+        for set in product.sets.iter_mut() {
+            set.name = format!("{} {copy}", set.name);
+        }
+        products.push(product);
+
+        for card in core_cards.cards.iter() {
+            let mut card = card.clone();
+            for card_product in card.products.iter_mut() {
+                card_product.code = code.clone();
+                for set in card_product.sets.iter_mut().flatten() {
+                    set.product = code.clone();
+                }
+            }
+            cards.push(card);
+        }
+    }
+
+    (cards, products)
+}
@@ -0,0 +1,84 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use data::{
+    card::{
+        card_side::{CardSideInputBuilder, CardSideKind},
+        Aspect, CardInput, CardProductInputBuilder, Cost,
+    },
+    graphql::Ctx,
+};
+
+mod common;
+
+use common::synthetic;
+
+fn inputs() -> Vec<(&'static str, CardInput)> {
+    vec![
+        (
+            "aspect",
+            CardInput {
+                aspect: Some(Some(Aspect::Basic)),
+                products: None,
+                sides: None,
+            },
+        ),
+        (
+            "kind_and_cost",
+            CardInput {
+                aspect: None,
+                products: None,
+                sides: Some(vec![CardSideInputBuilder::default()
+                    .kind(Some(CardSideKind::Ally))
+                    .cost(Some(Some(Cost::Number(3))))
+                    .build()
+                    .unwrap()]),
+            },
+        ),
+        (
+            "trait",
+            CardInput {
+                aspect: None,
+                products: None,
+                sides: Some(vec![CardSideInputBuilder::default()
+                    .traits(Some(Some(vec![String::from("S.H.I.E.L.D.")])))
+                    .build()
+                    .unwrap()]),
+            },
+        ),
+        (
+            "product_code",
+            CardInput {
+                aspect: None,
+                products: Some(vec![CardProductInputBuilder::default()
+                    .code(Some(String::from("SY0042en")))
+                    .build()
+                    .unwrap()]),
+                sides: None,
+            },
+        ),
+    ]
+}
+
+/// Every card checked against the input, against only the candidates from the card indexes
+fn filter(c: &mut Criterion) {
+    let (cards, products) = synthetic(100);
+    let ctx = Ctx::new(cards, products, vec![]);
+
+    let mut group = c.benchmark_group("filter");
+    for (name, input) in inputs() {
+        group.bench_function(format!("scan/{name}"), |b| {
+            b.iter(|| {
+                ctx.cards
+                    .iter()
+                    .filter(|card| card.included(black_box(&input), &ctx))
+                    .count()
+            })
+        });
+        group.bench_function(format!("planned/{name}"), |b| {
+            b.iter(|| ctx.filter_cards(black_box(&input)).unwrap().len())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, filter);
+criterion_main!(benches);
@@ -0,0 +1,57 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use data::{
+    card::{CardInput, CardProductInputBuilder},
+    graphql::Ctx,
+    product::ProductType,
+};
+
+mod common;
+
+use common::synthetic;
+
+fn load(c: &mut Criterion) {
+    let mut group = c.benchmark_group("load");
+    for copies in [10, 100] {
+        let (cards, products) = synthetic(copies);
+        group.bench_with_input(
+            BenchmarkId::new("ctx", cards.len()),
+            &(cards, products),
+            |b, (cards, products)| {
+                b.iter(|| {
+                    Ctx::new(
+                        black_box(cards.clone()),
+                        black_box(products.clone()),
+                        vec![],
+                    )
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+fn resolve(c: &mut Criterion) {
+    let (cards, products) = synthetic(100);
+    let ctx = Ctx::new(cards, products, vec![]);
+    // filtering on product fields resolves every card's products through the context
+    let input = CardInput {
+        aspect: None,
+        products: Some(vec![CardProductInputBuilder::default()
+            .r#type(Some(ProductType::CoreSet))
+            .build()
+            .unwrap()]),
+        sides: None,
+    };
+
+    c.bench_function("resolve/product_type", |b| {
+        b.iter(|| {
+            ctx.cards
+                .iter()
+                .filter(|card| card.included(black_box(&input), &ctx))
+                .count()
+        })
+    });
+}
+
+criterion_group!(benches, load, resolve);
+criterion_main!(benches);
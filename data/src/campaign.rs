@@ -0,0 +1,500 @@
+use crate::{
+    card::Card,
+    graphql::{Ctx, SHQScalarValue},
+    scenario::{Campaign, Scenario},
+    store::{Store, StoreError},
+};
+use juniper::{graphql_object, GraphQLEnum, GraphQLInputObject, GraphQLObject};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum CampaignError {
+    #[error("Could not find campaign {0}")]
+    UnknownCampaign(String),
+    #[error("Could not find campaign log {0}")]
+    UnknownLog(String),
+    #[error("There already is a campaign log named {0}")]
+    DuplicateLog(String),
+    #[error("A campaign needs at least one hero")]
+    NoHeroes,
+    #[error("{0} is not a hero")]
+    NotAHero(String),
+    #[error("{hero} is not played in {log}")]
+    HeroNotInCampaign { hero: String, log: String },
+    #[error("{scenario} is not a scenario of {campaign}")]
+    NotInCampaign { scenario: String, campaign: String },
+    #[error("{scenario} can't be played yet, the next scenario is {next}")]
+    OutOfOrder { scenario: String, next: String },
+    #[error("{0} is already complete")]
+    CampaignComplete(String),
+    #[error("Campaign logs: {0}")]
+    Store(#[from] StoreError),
+}
+
+/// Progress through a campaign, from one scenario to the next
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct CampaignLog {
+    pub name: String,
+    /// Name of the campaign being played
+    pub campaign: String,
+    /// Codes of the heroes played
+    pub heroes: Vec<String>,
+    /// Names of the completed scenarios, in order
+    #[serde(default)]
+    pub completed: Vec<String>,
+    #[serde(default)]
+    pub upgrades: Vec<Upgrade>,
+    #[serde(default)]
+    pub counters: Vec<Counter>,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, GraphQLEnum, PartialEq, Serialize)]
+pub enum UpgradeKind {
+    Upgrade,
+    Modification,
+}
+
+/// An upgrade or modification earned during a campaign, by one hero or by the whole team
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Upgrade {
+    pub name: String,
+    pub kind: UpgradeKind,
+    /// Code of the hero who earned it
+    pub hero: Option<String>,
+}
+
+#[derive(GraphQLInputObject)]
+#[graphql(scalar = SHQScalarValue)]
+pub struct UpgradeInput {
+    pub name: String,
+    pub kind: UpgradeKind,
+    pub hero: Option<String>,
+}
+
+/// A campaign specific counter, i.e. the number of civilians rescued
+#[derive(Clone, Debug, Deserialize, GraphQLObject, PartialEq, Serialize)]
+#[graphql(scalar = SHQScalarValue)]
+#[serde(deny_unknown_fields)]
+pub struct Counter {
+    pub name: String,
+    pub value: i32,
+}
+
+#[graphql_object(Context = Ctx, scalar = SHQScalarValue)]
+impl CampaignLog {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn campaign(&self, context: &Ctx) -> Option<&Campaign> {
+        context.campaign(&self.campaign)
+    }
+
+    fn heroes(&self, context: &Ctx) -> Vec<&Card> {
+        self.heroes
+            .iter()
+            .filter_map(|code| context.card(code))
+            .collect()
+    }
+
+    fn completed_scenarios(&self, context: &Ctx) -> Vec<&Scenario> {
+        self.completed
+            .iter()
+            .filter_map(|name| context.scenario(name))
+            .collect()
+    }
+
+    fn next_scenario(&self, context: &Ctx) -> Option<&Scenario> {
+        self.next(context).and_then(|name| context.scenario(name))
+    }
+
+    fn is_complete(&self, context: &Ctx) -> bool {
+        self.next(context).is_none()
+    }
+
+    fn upgrades(&self) -> &Vec<Upgrade> {
+        &self.upgrades
+    }
+
+    fn counters(&self) -> &Vec<Counter> {
+        &self.counters
+    }
+}
+
+#[graphql_object(Context = Ctx, scalar = SHQScalarValue)]
+impl Upgrade {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn kind(&self) -> UpgradeKind {
+        self.kind
+    }
+
+    fn hero(&self, context: &Ctx) -> Option<&Card> {
+        self.hero.as_ref().and_then(|code| context.card(code))
+    }
+}
+
+impl CampaignLog {
+    /// Starts a log for `campaign` played with the heroes `heroes`, by card code
+    pub fn new(
+        name: String,
+        campaign: String,
+        heroes: Vec<String>,
+        ctx: &Ctx,
+    ) -> Result<Self, CampaignError> {
+        if ctx.campaign(&campaign).is_none() {
+            return Err(CampaignError::UnknownCampaign(campaign));
+        }
+        if heroes.is_empty() {
+            return Err(CampaignError::NoHeroes);
+        }
+        if let Some(code) = heroes
+            .iter()
+            .find(|code| !ctx.card(code).is_some_and(Card::is_hero))
+        {
+            return Err(CampaignError::NotAHero(code.clone()));
+        }
+
+        Ok(Self {
+            name,
+            campaign,
+            heroes,
+            completed: vec![],
+            upgrades: vec![],
+            counters: vec![],
+        })
+    }
+
+    fn scenarios<'a>(&self, ctx: &'a Ctx) -> Result<&'a [String], CampaignError> {
+        ctx.campaign(&self.campaign)
+            .map(|campaign| campaign.scenarios.as_slice())
+            .ok_or_else(|| CampaignError::UnknownCampaign(self.campaign.clone()))
+    }
+
+    /// Name of the scenario to play next, `None` once the campaign is complete
+    pub fn next<'a>(&self, ctx: &'a Ctx) -> Option<&'a String> {
+        self.scenarios(ctx).ok()?.get(self.completed.len())
+    }
+
+    /// Records `scenario` as completed. Scenarios are played in the campaign's order.
+    pub fn complete(&mut self, scenario: String, ctx: &Ctx) -> Result<(), CampaignError> {
+        let scenarios = self.scenarios(ctx)?;
+        if !scenarios.contains(&scenario) {
+            return Err(CampaignError::NotInCampaign {
+                scenario,
+                campaign: self.campaign.clone(),
+            });
+        }
+        match self.next(ctx) {
+            None => Err(CampaignError::CampaignComplete(self.name.clone())),
+            Some(next) if next != &scenario => Err(CampaignError::OutOfOrder {
+                scenario,
+                next: next.clone(),
+            }),
+            Some(_) => {
+                self.completed.push(scenario);
+                Ok(())
+            }
+        }
+    }
+
+    pub fn add_upgrade(&mut self, upgrade: UpgradeInput) -> Result<(), CampaignError> {
+        if let Some(hero) = upgrade
+            .hero
+            .as_ref()
+            .filter(|hero| !self.heroes.contains(hero))
+        {
+            return Err(CampaignError::HeroNotInCampaign {
+                hero: hero.clone(),
+                log: self.name.clone(),
+            });
+        }
+
+        self.upgrades.push(Upgrade {
+            name: upgrade.name,
+            kind: upgrade.kind,
+            hero: upgrade.hero,
+        });
+
+        Ok(())
+    }
+
+    /// Sets the counter `name` to `value`, adding it if it's new
+    pub fn set_counter(&mut self, name: String, value: i32) {
+        match self
+            .counters
+            .iter_mut()
+            .find(|counter| counter.name == name)
+        {
+            Some(counter) => counter.value = value,
+            None => self.counters.push(Counter { name, value }),
+        }
+    }
+}
+
+/// Campaign logs, saved to a file after every change when they were loaded from one
+#[derive(Debug)]
+pub struct CampaignLogs(Store<CampaignLog>);
+
+impl Default for CampaignLogs {
+    fn default() -> Self {
+        Self(Store::in_memory(KEY))
+    }
+}
+
+/// Name of the tables holding the logs in a campaign logs file
+const KEY: &str = "log";
+
+impl CampaignLogs {
+    /// Loads the logs in `path`, which doesn't need to exist yet
+    pub fn load(path: impl Into<PathBuf>) -> Result<Self, CampaignError> {
+        Ok(Self(Store::load(KEY, path)?))
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.0.path()
+    }
+
+    pub fn revision(&self) -> u64 {
+        self.0.revision()
+    }
+
+    pub fn all(&self) -> Vec<CampaignLog> {
+        self.0.all()
+    }
+
+    pub fn get(&self, name: &str) -> Option<CampaignLog> {
+        self.0.find(|log| log.name == name)
+    }
+
+    pub fn start(&self, log: CampaignLog) -> Result<CampaignLog, CampaignError> {
+        self.0.change(|logs| {
+            if logs.iter().any(|existing| existing.name == log.name) {
+                return Err(CampaignError::DuplicateLog(log.name));
+            }
+            logs.push(log.clone());
+
+            Ok(log)
+        })
+    }
+
+    /// Changes the log `name` with `update`, keeping it as it was when `update` fails
+    pub fn update(
+        &self,
+        name: &str,
+        update: impl FnOnce(&mut CampaignLog) -> Result<(), CampaignError>,
+    ) -> Result<CampaignLog, CampaignError> {
+        self.0.change(|logs| {
+            let log = logs
+                .iter_mut()
+                .find(|log| log.name == name)
+                .ok_or_else(|| CampaignError::UnknownLog(String::from(name)))?;
+            update(log)?;
+
+            Ok(log.clone())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{card, product, scenario};
+    use std::fs;
+
+    /// The core set with a made up campaign of two Rhino scenarios
+    fn ctx() -> Ctx {
+        let cards: card::Document = toml::from_str(include_str!("../data/core-set.toml")).unwrap();
+        let products: product::Document =
+            toml::from_str(include_str!("../data/products.toml")).unwrap();
+        let mut scenarios: scenario::Document =
+            toml::from_str(include_str!("../data/scenarios.toml")).unwrap();
+        let mut rematch = scenarios.scenarios[0].clone();
+        rematch.name = String::from("Rhino Rematch");
+        scenarios.scenarios.push(rematch);
+        scenarios.campaigns.push(Campaign {
+            name: String::from("Rhino Rampage"),
+            product: String::from("MC01en"),
+            scenarios: vec![String::from("Rhino"), String::from("Rhino Rematch")],
+        });
+
+        Ctx::new(cards.cards, products.products, vec![]).with_scenarios(scenarios)
+    }
+
+    fn log(ctx: &Ctx) -> CampaignLog {
+        CampaignLog::new(
+            String::from("Peter's campaign"),
+            String::from("Rhino Rampage"),
+            vec![String::from("MC01en-1")],
+            ctx,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn it_starts_campaigns_with_heroes() {
+        let ctx = ctx();
+        let start = |campaign: &str, heroes: &[&str]| {
+            CampaignLog::new(
+                String::from("log"),
+                String::from(campaign),
+                heroes.iter().map(|hero| String::from(*hero)).collect(),
+                &ctx,
+            )
+        };
+
+        assert!(start("Rhino Rampage", &["MC01en-1", "MC01en-10"]).is_ok());
+        assert!(matches!(
+            start("Nope", &["MC01en-1"]),
+            Err(CampaignError::UnknownCampaign(_))
+        ));
+        assert!(matches!(
+            start("Rhino Rampage", &[]),
+            Err(CampaignError::NoHeroes)
+        ));
+        assert!(matches!(
+            start("Rhino Rampage", &["MC01en-2"]),
+            Err(CampaignError::NotAHero(code)) if code == "MC01en-2"
+        ));
+    }
+
+    #[test]
+    fn it_completes_scenarios_in_order() {
+        let ctx = ctx();
+        let mut log = log(&ctx);
+
+        assert!(matches!(
+            log.complete(String::from("Rhino Rematch"), &ctx),
+            Err(CampaignError::OutOfOrder { next, .. }) if next == "Rhino"
+        ));
+        assert!(matches!(
+            log.complete(String::from("Klaw"), &ctx),
+            Err(CampaignError::NotInCampaign { .. })
+        ));
+
+        log.complete(String::from("Rhino"), &ctx).unwrap();
+        assert_eq!(Some(&String::from("Rhino Rematch")), log.next(&ctx));
+        log.complete(String::from("Rhino Rematch"), &ctx).unwrap();
+        assert_eq!(None, log.next(&ctx));
+        assert!(matches!(
+            log.complete(String::from("Rhino"), &ctx),
+            Err(CampaignError::CampaignComplete(_))
+        ));
+    }
+
+    #[test]
+    fn it_tracks_upgrades_and_counters() {
+        let ctx = ctx();
+        let mut log = log(&ctx);
+
+        log.add_upgrade(UpgradeInput {
+            name: String::from("Web-Shooter Mk II"),
+            kind: UpgradeKind::Modification,
+            hero: Some(String::from("MC01en-1")),
+        })
+        .unwrap();
+        assert!(matches!(
+            log.add_upgrade(UpgradeInput {
+                name: String::from("Gamma Boost"),
+                kind: UpgradeKind::Upgrade,
+                hero: Some(String::from("MC01en-19")),
+            }),
+            Err(CampaignError::HeroNotInCampaign { .. })
+        ));
+        log.set_counter(String::from("Civilians rescued"), 2);
+        log.set_counter(String::from("Civilians rescued"), 3);
+
+        assert_eq!(1, log.upgrades.len());
+        assert_eq!(
+            vec![Counter {
+                name: String::from("Civilians rescued"),
+                value: 3
+            }],
+            log.counters
+        );
+    }
+
+    #[test]
+    fn it_saves_and_loads_logs() {
+        let ctx = ctx();
+        let path =
+            std::env::temp_dir().join(format!("shq-campaign-logs-{}.toml", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let logs = CampaignLogs::load(&path).unwrap();
+        assert!(logs.all().is_empty());
+        logs.start(log(&ctx)).unwrap();
+        assert!(matches!(
+            logs.start(log(&ctx)),
+            Err(CampaignError::DuplicateLog(_))
+        ));
+        logs.update("Peter's campaign", |log| {
+            log.complete(String::from("Rhino"), &ctx)
+        })
+        .unwrap();
+        // a failed update leaves the log as it was
+        assert!(logs
+            .update("Peter's campaign", |log| {
+                log.set_counter(String::from("Threat"), 1);
+                log.complete(String::from("Rhino"), &ctx)
+            })
+            .is_err());
+        assert_eq!(2, logs.revision());
+
+        let loaded = CampaignLogs::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(logs.all(), loaded.all());
+        assert_eq!(
+            vec![String::from("Rhino")],
+            loaded.get("Peter's campaign").unwrap().completed
+        );
+        assert!(loaded.get("Peter's campaign").unwrap().counters.is_empty());
+    }
+
+    #[test]
+    fn it_advances_campaigns_through_mutations() {
+        use juniper::{graphql_value, Variables};
+
+        let ctx = ctx();
+        let execute = |query: &str| {
+            juniper::execute_sync(
+                query,
+                None,
+                &crate::graphql::schema(),
+                &Variables::new(),
+                &ctx,
+            )
+            .unwrap()
+        };
+
+        let (_, errors) = execute(
+            r#"mutation { startCampaign(name: "solo", campaign: "Rhino Rampage", heroes: ["MC01en-1"]) { name } }"#,
+        );
+        assert!(errors.is_empty());
+        let (_, errors) = execute(
+            r#"mutation { completeScenario(log: "solo", scenario: "Rhino Rematch") { name } }"#,
+        );
+        assert_eq!(1, errors.len());
+
+        let (value, errors) = execute(
+            r#"mutation { completeScenario(log: "solo", scenario: "Rhino") { completedScenarios { name } nextScenario { name } isComplete heroes { sides { name } } } }"#,
+        );
+        assert!(errors.is_empty());
+        assert_eq!(
+            graphql_value!({ "completeScenario": {
+                "completedScenarios": [{ "name": "Rhino" }],
+                "nextScenario": { "name": "Rhino Rematch" },
+                "isComplete": false,
+                "heroes": [{ "sides": [{ "name": "Spider-Man" }, { "name": "Peter Parker" }] }],
+            } }),
+            value
+        );
+        assert_eq!(1, ctx.campaign_logs().all()[0].completed.len());
+    }
+}
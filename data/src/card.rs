@@ -1,17 +1,21 @@
+use crate::graphql::filter;
 use crate::graphql::{Ctx, SHQScalarValue};
-use card_side::{CardSide, CardSideInput};
-use juniper::{GraphQLEnum, GraphQLInputObject, GraphQLObject};
-use serde::Deserialize;
+use crate::product::Set as ProductSet;
+use card_side::{CardSide, CardSideInput, CardSideVariant, Side};
+use juniper::{graphql_object, GraphQLEnum, GraphQLInputObject};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
 mod basic_power;
 mod card_product;
 mod card_set;
 mod cost;
 mod hit_points;
+mod index;
 mod keyword;
-mod r#trait;
 
 pub mod card_side;
+pub mod r#trait;
 pub use basic_power::BasicPower;
 pub use card_product::{
     CardProduct, CardProductInput, CardProductInputBuilder, CardProductInputBuilderError,
@@ -19,25 +23,29 @@ pub use card_product::{
 pub use card_set::{CardSet, CardSetInput, CardSetInputBuilder, CardSetInputBuilderError};
 pub use cost::Cost;
 pub use hit_points::HitPoints;
-pub use keyword::Keyword;
-pub use r#trait::Trait;
+pub use index::{set_names, CardIndex, Key, Plan, SetNames};
+pub use keyword::{Keyword, ParseKeywordError};
+pub use r#trait::{Trait, TraitError};
 
 #[derive(Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct Document {
-    #[serde(rename = "card")]
+    #[serde(rename = "card", default)]
     pub cards: Vec<Card>,
 }
 
-#[derive(Clone, Deserialize, GraphQLObject)]
-#[graphql(Context = Ctx, scalar = SHQScalarValue)]
+#[derive(Clone, Deserialize, Serialize, ToSchema)]
 #[serde(deny_unknown_fields)]
 pub struct Card {
-    #[serde(rename = "product")]
+    #[serde(rename(deserialize = "product"), alias = "products")]
     pub products: Vec<CardProduct>,
-    #[serde(rename = "side")]
+    #[serde(rename(deserialize = "side"), alias = "sides")]
     pub sides: Vec<CardSide>,
     pub aspect: Option<Aspect>,
+    /// Code of the card this reprints, for printings that can't be matched by name such as
+    /// translations
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reprint_of: Option<String>,
 }
 
 #[derive(GraphQLInputObject)]
@@ -48,7 +56,232 @@ pub struct CardInput {
     pub sides: Option<Vec<CardSideInput>>,
 }
 
-#[derive(Clone, Deserialize, GraphQLEnum, PartialEq)]
+impl CardInput {
+    /// Checks that the traits sides are filtered on are in the trait registry, as a misspelled
+    /// trait would match no cards
+    pub fn check_traits(&self, context: &Ctx) -> Result<(), TraitError> {
+        let names = self
+            .sides
+            .iter()
+            .flatten()
+            .flat_map(|side| side.traits.iter().flatten().flatten());
+        for name in names {
+            context.known_trait(name)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[graphql_object(Context = Ctx, scalar = SHQScalarValue)]
+impl Card {
+    fn products(&self) -> &Vec<CardProduct> {
+        &self.products
+    }
+
+    fn sides(&self) -> &Vec<CardSide> {
+        &self.sides
+    }
+
+    fn aspect(&self) -> Option<&Aspect> {
+        self.aspect.as_ref()
+    }
+
+    /// The hero this card belongs to through its signature or nemesis set
+    fn hero(&self, context: &Ctx) -> Option<&Card> {
+        self.hero(context)
+    }
+
+    /// The alter-ego side a hero flips to
+    fn alter_ego(&self) -> Option<&CardSide> {
+        self.alter_ego()
+    }
+
+    /// Cards in the hero's signature set, other than the hero and its obligation
+    fn signature_cards(&self, context: &Ctx) -> Vec<&Card> {
+        self.signature_cards(context)
+    }
+
+    fn nemesis_set(&self, context: &Ctx) -> Option<&ProductSet> {
+        self.nemesis_set(context)
+    }
+
+    fn obligation(&self, context: &Ctx) -> Option<&Card> {
+        self.obligation(context)
+    }
+
+    /// Every product the card was printed in, including reprints, earliest first
+    fn printings(&self, context: &Ctx) -> Vec<&CardProduct> {
+        self.printings(context)
+    }
+
+    /// The original printing, when this card is a reprint
+    fn reprint_of(&self, context: &Ctx) -> Option<&Card> {
+        self.reprint_of(context)
+    }
+
+    fn reprints(&self, context: &Ctx) -> Vec<&Card> {
+        self.reprints(context)
+    }
+}
+
+impl Card {
+    /// Identifies a card by its first printing, i.e. MC01en-1
+    pub fn code(&self) -> Option<String> {
+        self.products
+            .first()
+            .and_then(|product| product.card_code())
+    }
+
+    /// Key shared by every printing of the same card, made from its aspect and the name, text
+    /// and values of its sides. Flavor text and illustrators are left out since reprints can
+    /// change them, and distinct cards sharing a name still differ in text or values.
+    pub fn identity(&self) -> String {
+        let sides: Vec<_> = self
+            .sides
+            .iter()
+            .map(|side| (&side.name, &side.text, &side.variant))
+            .collect();
+
+        serde_json::to_string(&(&self.aspect, sides)).expect("cards serialize to JSON")
+    }
+
+    /// Whether this is the very same printing as `card`, and not merely a printing of the same
+    /// card. Cards are compared by code, as a card store may hand out copies, and by address when
+    /// a card without products has no code to compare.
+    pub fn is_same_printing(&self, card: &Card) -> bool {
+        match (self.code(), card.code()) {
+            (Some(code), Some(other)) => code == other,
+            _ => std::ptr::eq(self, card),
+        }
+    }
+
+    pub fn printings<'a>(&'a self, context: &'a Ctx) -> Vec<&'a CardProduct> {
+        let cards = context.printings(self);
+
+        if cards.is_empty() {
+            self.products.iter().collect()
+        } else {
+            cards.iter().flat_map(|card| card.products.iter()).collect()
+        }
+    }
+
+    pub fn reprint_of<'a>(&self, context: &'a Ctx) -> Option<&'a Card> {
+        context
+            .printings(self)
+            .first()
+            .copied()
+            .filter(|original| !original.is_same_printing(self))
+    }
+
+    pub fn reprints<'a>(&self, context: &'a Ctx) -> Vec<&'a Card> {
+        match context.printings(self).split_first() {
+            Some((original, reprints)) if original.is_same_printing(self) => reprints.to_vec(),
+            _ => vec![],
+        }
+    }
+
+    pub fn included(&self, input: &CardInput, context: &Ctx) -> bool {
+        let mut filter = true;
+
+        filter!(filter, &self.aspect => input.aspect);
+
+        if let Some(products) = &input.products {
+            filter = filter
+                && self.products.iter().any(|card_product| {
+                    products
+                        .iter()
+                        .any(|input_product| card_product.included(input_product, context))
+                });
+        }
+        if let Some(sides) = &input.sides {
+            filter = filter
+                && self.sides.iter().any(|card_side| {
+                    sides
+                        .iter()
+                        .any(|input_side| card_side.included(input_side))
+                });
+        }
+
+        filter
+    }
+
+    /// Whether this is a hero identity, with a hero and an alter-ego side
+    pub fn is_hero(&self) -> bool {
+        self.sides
+            .iter()
+            .any(|side| matches!(side.variant, CardSideVariant::Hero { .. }))
+    }
+
+    pub fn is_obligation(&self) -> bool {
+        self.sides
+            .iter()
+            .any(|side| matches!(side.variant, CardSideVariant::Obligation { .. }))
+    }
+
+    /// Hand size of a hero in `form`, i.e. side A for the hero and B for the alter-ego
+    pub fn hand_size(&self, form: &Side) -> Option<u32> {
+        self.sides
+            .iter()
+            .find(|side| side.side() == Some(form))
+            .and_then(|side| side.hand_size())
+            .copied()
+    }
+
+    pub fn alter_ego(&self) -> Option<&CardSide> {
+        self.sides
+            .iter()
+            .find(|side| matches!(side.variant, CardSideVariant::AlterEgo { .. }))
+    }
+
+    /// The product sets the card is in, across all of its printings
+    pub(crate) fn product_sets<'s, 'a: 's>(
+        &'s self,
+        context: &'a Ctx,
+    ) -> impl Iterator<Item = &'a ProductSet> + 's {
+        self.products
+            .iter()
+            .flat_map(|product| product.sets.iter().flatten())
+            .filter_map(|set| set.set(context))
+    }
+
+    /// Name of the hero whose signature or nemesis set the card is in
+    pub fn hero_name<'a>(&self, context: &'a Ctx) -> Option<&'a str> {
+        self.product_sets(context).find_map(|set| set.hero())
+    }
+
+    /// Cards in the signature set of the hero this card belongs to
+    fn signature<'a>(&self, context: &'a Ctx) -> impl Iterator<Item = &'a Card> {
+        self.hero_name(context)
+            .into_iter()
+            .flat_map(|hero| context.signature(hero))
+    }
+
+    /// The hero this card belongs to, or `None` for a hero itself
+    pub fn hero<'a>(&self, context: &'a Ctx) -> Option<&'a Card> {
+        if self.is_hero() {
+            return None;
+        }
+
+        self.signature(context).find(|card| card.is_hero())
+    }
+
+    pub fn signature_cards<'a>(&self, context: &'a Ctx) -> Vec<&'a Card> {
+        self.signature(context)
+            .filter(|card| !card.is_hero() && !card.is_obligation())
+            .collect()
+    }
+
+    pub fn obligation<'a>(&self, context: &'a Ctx) -> Option<&'a Card> {
+        self.signature(context).find(|card| card.is_obligation())
+    }
+
+    pub fn nemesis_set<'a>(&self, context: &'a Ctx) -> Option<&'a ProductSet> {
+        context.nemesis_set(self.hero_name(context)?)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, GraphQLEnum, Hash, PartialEq, Serialize, ToSchema)]
 pub enum Aspect {
     Basic,
     Aggression,
@@ -68,32 +301,54 @@ pub enum CardType {
     Upgrade,
 }
 
-#[derive(Clone, Deserialize, GraphQLEnum)]
+#[derive(Clone, Copy, Debug, Deserialize, Eq, GraphQLEnum, PartialEq, Serialize, ToSchema)]
 pub enum Resource {
-    #[serde(rename = ":energy:")]
+    #[serde(rename(deserialize = ":energy:"), alias = "Energy")]
     Energy,
-    #[serde(rename = ":mental:")]
+    #[serde(rename(deserialize = ":mental:"), alias = "Mental")]
     Mental,
-    #[serde(rename = ":physical:")]
+    #[serde(rename(deserialize = ":physical:"), alias = "Physical")]
     Physical,
-    #[serde(rename = ":wild:")]
+    #[serde(rename(deserialize = ":wild:"), alias = "Wild")]
     Wild,
 }
 
-#[derive(Clone, Deserialize, GraphQLEnum)]
+#[derive(Clone, Deserialize, GraphQLEnum, Serialize, ToSchema)]
 pub enum SideSchemeIcon {
-    #[serde(rename = ":acceleration:")]
+    #[serde(rename(deserialize = ":acceleration:"), alias = "Acceleration")]
     Acceleration,
-    #[serde(rename = ":crisis:")]
+    #[serde(rename(deserialize = ":crisis:"), alias = "Crisis")]
     Crisis,
-    #[serde(rename = ":hazard:")]
+    #[serde(rename(deserialize = ":hazard:"), alias = "Hazard")]
     Hazard,
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::product;
+    use crate::product::{self, SetType as ProductSetType};
+
+    fn ctx() -> Ctx {
+        let products: product::Document =
+            toml::from_str(include_str!("../data/products.toml")).unwrap();
+        let document: Document = toml::from_str(include_str!("../data/core-set.toml")).unwrap();
+
+        Ctx::new(document.cards, products.products, vec![])
+    }
+
+    fn find<'a>(ctx: &'a Ctx, name: &str) -> &'a Card {
+        ctx.cards
+            .iter()
+            .find(|card| card.sides[0].name == name)
+            .unwrap()
+    }
+
+    fn names(cards: Vec<&Card>) -> Vec<&str> {
+        cards
+            .iter()
+            .map(|card| card.sides[0].name.as_str())
+            .collect()
+    }
 
     #[test]
     fn it_parses_cards() {
@@ -102,29 +357,260 @@ mod tests {
 
         let document: Result<Document, _> = toml::from_str(include_str!("../data/core-set.toml"));
 
+        let trait_document: Result<r#trait::Document, _> =
+            toml::from_str(include_str!("../data/traits.toml"));
+
         let products: Vec<product::Product> = product_document.unwrap().products;
         let cards: Vec<Card> = document.unwrap().cards;
+        let traits: Vec<Trait> = trait_document.unwrap().traits;
+
+        if let Err(errors) = r#trait::validate(&cards, &traits) {
+            let errors: Vec<_> = errors.iter().map(ToString::to_string).collect();
+            panic!(
+                "Found {} invalid traits:\n{}",
+                errors.len(),
+                errors.join("\n")
+            );
+        }
 
         for card in cards.iter() {
             for card_product in card.products.iter() {
                 // check that every card product exists
-                if let Some(product) = products
-                    .iter()
-                    .find(|product| product.code == card_product.code)
-                {
-                    if let Some(sets) = &card_product.sets {
-                        for card_set in sets.iter() {
-                            assert!(
-                                product.sets.iter().any(|set| set.name == card_set.name),
-                                "Could not find product set {}",
-                                card_set.name
-                            );
-                        }
-                    }
-                } else {
-                    panic!("Could not find product {}", card_product.code);
-                }
+                assert!(
+                    products
+                        .iter()
+                        .any(|product| product.code == card_product.code),
+                    "Could not find product {}",
+                    card_product.code
+                );
             }
         }
+        // and every card set
+        if let Err(errors) = product::validate(&products, &cards) {
+            panic!("Found invalid sets: {errors:?}");
+        }
+    }
+
+    #[test]
+    fn it_links_heroes_to_their_sets() {
+        let ctx = ctx();
+
+        for (hero, alter_ego, obligation) in [
+            ("Spider-Man", "Peter Parker", "Eviction Notice"),
+            ("She-Hulk", "Jennifer Walters", "Legal Work"),
+            ("Captain Marvel", "Carol Danvers", "Family Emergency"),
+        ] {
+            let card = find(&ctx, hero);
+            let nemesis = format!("{hero} Nemesis");
+
+            assert!(card.hero(&ctx).is_none());
+            assert_eq!(alter_ego, card.alter_ego().unwrap().name);
+            assert_eq!(
+                obligation,
+                card.obligation(&ctx).unwrap().sides[0].name.as_str()
+            );
+            assert_eq!(nemesis, card.nemesis_set(&ctx).unwrap().name);
+
+            let signature_cards = card.signature_cards(&ctx);
+            assert!(!signature_cards.is_empty());
+            for signature_card in signature_cards {
+                assert_eq!(hero, signature_card.hero_name(&ctx).unwrap());
+                assert_eq!(
+                    hero,
+                    signature_card.hero(&ctx).unwrap().sides[0].name.as_str()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn it_links_cards_back_to_their_hero() {
+        let ctx = ctx();
+        let spider_man = find(&ctx, "Spider-Man");
+
+        assert!(names(spider_man.signature_cards(&ctx)).contains(&"Black Cat"));
+        for name in ["Eviction Notice", "Black Cat"] {
+            assert!(std::ptr::eq(
+                spider_man,
+                find(&ctx, name).hero(&ctx).unwrap()
+            ));
+        }
+        let nemesis = ctx
+            .cards
+            .iter()
+            .find(|card| {
+                card.product_sets(&ctx)
+                    .any(|set| set.name == "Spider-Man Nemesis")
+            })
+            .unwrap();
+        assert!(std::ptr::eq(spider_man, nemesis.hero(&ctx).unwrap()));
+    }
+
+    #[test]
+    fn it_indexes_signature_cards_like_a_scan() {
+        let ctx = ctx();
+
+        for hero in ["Spider-Man", "She-Hulk", "Captain Marvel"] {
+            let scanned: Vec<_> = ctx
+                .cards
+                .iter()
+                .filter(|card| {
+                    card.product_sets(&ctx).any(|set| {
+                        set.r#type == ProductSetType::HeroSignature && set.hero() == Some(hero)
+                    })
+                })
+                .map(|card| card.code())
+                .collect();
+            let indexed: Vec<_> = ctx.signature(hero).map(Card::code).collect();
+
+            assert!(!indexed.is_empty());
+            assert_eq!(scanned, indexed);
+        }
+        assert_eq!(0, ctx.signature("Rhino").count());
+        assert!(ctx.nemesis_set("Rhino").is_none());
+    }
+
+    #[test]
+    fn it_has_no_hero_outside_hero_sets() {
+        let ctx = ctx();
+        let basic = ctx
+            .cards
+            .iter()
+            .find(|card| card.aspect == Some(Aspect::Basic))
+            .unwrap();
+
+        assert!(basic.hero(&ctx).is_none());
+        assert!(basic.obligation(&ctx).is_none());
+        assert!(basic.nemesis_set(&ctx).is_none());
+        assert!(basic.signature_cards(&ctx).is_empty());
+    }
+
+    /// The core set with Enhanced Spider-Sense reprinted in a later product and translated
+    fn reprints_ctx() -> Ctx {
+        let mut products: product::Document =
+            toml::from_str(include_str!("../data/products.toml")).unwrap();
+        let mut document: Document = toml::from_str(include_str!("../data/core-set.toml")).unwrap();
+        let mut reprint_product = products.products[0].clone();
+        reprint_product.code = String::from("MC99en");
+        reprint_product.release_date = reprint_product.release_date.succ_opt().unwrap();
+        let mut translation_product = reprint_product.clone();
+        translation_product.code = String::from("MC01fr");
+        products.products.push(reprint_product);
+        products.products.push(translation_product);
+
+        let original = document
+            .cards
+            .iter()
+            .find(|card| card.sides[0].name == "Enhanced Spider-Sense")
+            .unwrap();
+        let mut reprint = original.clone();
+        reprint.products[0].code = String::from("MC99en");
+        let mut translation = original.clone();
+        translation.products[0].code = String::from("MC01fr");
+        translation.sides[0].name = String::from("Sens d'araignée amélioré");
+        translation.reprint_of = original.code();
+        // the reprint comes first to check printings are ordered by release date
+        document.cards.insert(0, reprint);
+        document.cards.push(translation);
+
+        Ctx::new(document.cards, products.products, vec![])
+    }
+
+    #[test]
+    fn it_groups_printings_of_a_card() {
+        let ctx = reprints_ctx();
+        let original = ctx.card("MC01en-4").unwrap();
+        let reprint = ctx.card("MC99en-4").unwrap();
+        let translation = ctx.card("MC01fr-4").unwrap();
+
+        let codes: Vec<&str> = original
+            .printings(&ctx)
+            .iter()
+            .map(|product| product.code.as_str())
+            .collect();
+        assert_eq!(vec!["MC01en", "MC99en", "MC01fr"], codes);
+        assert!(original.reprint_of(&ctx).is_none());
+        assert_eq!(2, original.reprints(&ctx).len());
+        for card in [reprint, translation] {
+            assert!(card.reprint_of(&ctx).unwrap().is_same_printing(original));
+            assert!(card.reprints(&ctx).is_empty());
+            assert_eq!(3, card.printings(&ctx).len());
+        }
+    }
+
+    #[test]
+    fn it_has_a_single_printing_without_reprints() {
+        let ctx = reprints_ctx();
+        let black_cat = find(&ctx, "Black Cat");
+
+        assert_eq!(1, black_cat.printings(&ctx).len());
+        assert!(black_cat.reprint_of(&ctx).is_none());
+        assert!(black_cat.reprints(&ctx).is_empty());
+    }
+
+    #[test]
+    fn it_keeps_distinct_cards_of_the_same_name_apart() {
+        let products: product::Document =
+            toml::from_str(include_str!("../data/products.toml")).unwrap();
+        let mut document: Document = toml::from_str(include_str!("../data/core-set.toml")).unwrap();
+        let original = document
+            .cards
+            .iter()
+            .find(|card| card.code().as_deref() == Some("MC01en-4"))
+            .unwrap();
+        let mut namesake = original.clone();
+        namesake.products[0].code = String::from("MC99en");
+        namesake.sides[0].text = Some(String::from("Draw 2 cards."));
+        let mut no_code = original.clone();
+        no_code.products.clear();
+        no_code.sides[0].text = Some(String::from("Draw 3 cards."));
+        document.cards.push(namesake);
+        document.cards.push(no_code);
+        let ctx = Ctx::new(document.cards, products.products, vec![]);
+        let original = ctx.card("MC01en-4").unwrap();
+        let namesake = ctx.card("MC99en-4").unwrap();
+        let no_code = ctx.cards.last().unwrap();
+
+        assert_ne!(original.identity(), namesake.identity());
+        assert_eq!(1, original.printings(&ctx).len());
+        assert!(namesake.reprint_of(&ctx).is_none());
+        assert!(original.reprints(&ctx).is_empty());
+        assert!(!no_code.is_same_printing(original));
+        assert!(no_code.reprint_of(&ctx).is_none());
+    }
+
+    #[test]
+    fn it_collapses_printings_of_distinct_cards() {
+        use juniper::{graphql_value, Variables};
+
+        let ctx = reprints_ctx();
+        let codes = |distinct: bool| {
+            let query = format!(
+                r#"{{ cards(where: {{ sides: [{{ name: "Enhanced Spider-Sense" }}] }}, distinct: {distinct}) {{ products {{ code }} }} }}"#
+            );
+            let (value, errors) = juniper::execute_sync(
+                &query,
+                None,
+                &crate::graphql::schema(),
+                &Variables::new(),
+                &ctx,
+            )
+            .unwrap();
+            assert!(errors.is_empty());
+
+            value
+        };
+
+        assert_eq!(
+            graphql_value!({ "cards": [
+                { "products": [{ "code": "MC99en" }] },
+                { "products": [{ "code": "MC01en" }] },
+            ] }),
+            codes(false)
+        );
+        assert_eq!(
+            graphql_value!({ "cards": [{ "products": [{ "code": "MC01en" }] }] }),
+            codes(true)
+        );
     }
 }
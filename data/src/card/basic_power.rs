@@ -4,10 +4,15 @@ use regex::Regex;
 use serde::{
     self,
     de::{self, Deserializer, Unexpected, Visitor},
-    Deserialize,
+    Deserialize, Serialize, Serializer,
 };
+use serde_json::json;
 use std::{fmt, str::FromStr};
 use thiserror::Error;
+use utoipa::{
+    openapi::{ObjectBuilder, RefOr, Schema, SchemaType},
+    ToSchema,
+};
 
 struct BasicPowerVisitor;
 
@@ -80,6 +85,28 @@ impl FromStr for BasicPower {
     }
 }
 
+impl<'s> ToSchema<'s> for BasicPower {
+    fn schema() -> (&'s str, RefOr<Schema>) {
+        let schema = ObjectBuilder::new()
+            .schema_type(SchemaType::String)
+            .description(Some(
+                "A number or X, followed by a star when the power has an effect",
+            ))
+            .example(Some(json!("1*")));
+
+        ("BasicPower", schema.into())
+    }
+}
+
+impl Serialize for BasicPower {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
 impl<'de> Deserialize<'de> for BasicPower {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
@@ -1,13 +1,13 @@
-use crate::graphql::{filter, filter_context, Ctx, SHQScalarValue};
+use crate::graphql::{filter, filter_context, Ctx, ProductId, SHQScalarValue};
 use crate::{
     card::{CardSet, CardSetInput},
-    product::ProductType,
+    product::{Product, ProductType},
 };
 use chrono::NaiveDate;
 use derive_builder::Builder;
 use juniper::{graphql_object, GraphQLInputObject};
-use serde::Deserialize;
-use std::collections::HashSet;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
 #[derive(Builder, Clone, GraphQLInputObject)]
 #[graphql(scalar = SHQScalarValue)]
@@ -29,13 +29,40 @@ pub struct CardProductInput {
     pub wave: Option<u32>,
 }
 
-#[derive(Clone, Deserialize)]
-#[serde(deny_unknown_fields)]
+#[derive(Clone, Deserialize, Serialize, ToSchema)]
+#[serde(from = "CardProductData")]
 pub struct CardProduct {
     pub code: String,
     pub positions: Vec<u32>,
-    #[serde(rename = "set")]
     pub sets: Option<Vec<CardSet>>,
+    /// Id of the product in the context the card was loaded into, set by `Ctx::new`
+    #[serde(skip)]
+    pub id: Option<ProductId>,
+}
+
+/// A card product as written in the data, before its sets know which product they're in
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CardProductData {
+    code: String,
+    positions: Vec<u32>,
+    #[serde(rename = "set", alias = "sets")]
+    sets: Option<Vec<CardSet>>,
+}
+
+impl From<CardProductData> for CardProduct {
+    fn from(mut data: CardProductData) -> Self {
+        for set in data.sets.iter_mut().flatten() {
+            set.product = data.code.clone();
+        }
+
+        Self {
+            code: data.code,
+            positions: data.positions,
+            sets: data.sets,
+            id: None,
+        }
+    }
 }
 
 #[graphql_object(Context = Ctx, scalar = SHQScalarValue)]
@@ -48,14 +75,20 @@ impl CardProduct {
         &self.positions
     }
 
-    fn sets(&self, name: Option<String>) -> Option<Vec<&CardSet>> {
+    fn sets(
+        &self,
+        context: &Ctx,
+        code: Option<String>,
+        name: Option<String>,
+    ) -> Option<Vec<&CardSet>> {
         if let Some(sets) = &self.sets {
             Some(
                 sets.into_iter()
                     .filter(|set| {
                         let mut filter = true;
 
-                        filter!(filter, &set.name => name);
+                        filter!(filter, &set.code => code);
+                        filter_context!(filter, set.name(context) => name);
 
                         filter
                     })
@@ -84,22 +117,35 @@ impl CardProduct {
 }
 
 impl CardProduct {
+    /// Code for the card in this product, made from the product code and its first position
+    pub fn card_code(&self) -> Option<String> {
+        self.positions
+            .first()
+            .map(|position| format!("{}-{position}", self.code))
+    }
+
+    /// The product this card was printed in. Cards loaded into the context already know its id;
+    /// others, like copies from a card store, look it up by code.
+    pub fn product<'a>(&self, context: &'a Ctx) -> Option<&'a Product> {
+        self.id
+            .or_else(|| context.product_id(&self.code))
+            .map(|id| context.product_by_id(id))
+    }
+
     pub fn name<'a>(&self, context: &'a Ctx) -> Option<&'a String> {
-        context.product(&self.code).map(|product| &product.name)
+        self.product(context).map(|product| &product.name)
     }
 
     pub fn release_date<'a>(&self, context: &'a Ctx) -> Option<&'a NaiveDate> {
-        context
-            .product(&self.code)
-            .map(|product| &product.release_date)
+        self.product(context).map(|product| &product.release_date)
     }
 
     pub fn r#type<'a>(&self, context: &'a Ctx) -> Option<&'a ProductType> {
-        context.product(&self.code).map(|product| &product.r#type)
+        self.product(context).map(|product| &product.r#type)
     }
 
     pub fn wave<'a>(&self, context: &'a Ctx) -> Option<&'a u32> {
-        context.product(&self.code).map(|product| &product.wave)
+        self.product(context).map(|product| &product.wave)
     }
 
     pub fn included(&self, input: &CardProductInput, context: &Ctx) -> bool {
@@ -107,10 +153,10 @@ impl CardProduct {
 
         filter!(filter, &self.code => input.code);
         if let Some(positions) = &input.positions {
-            let a: HashSet<&u32> = positions.iter().collect();
-            let b: HashSet<&u32> = self.positions.iter().collect();
-
-            filter = filter && a.intersection(&b).next().is_some();
+            filter = filter
+                && positions
+                    .iter()
+                    .any(|position| self.positions.contains(position));
         }
         if let Some(input_sets) = &input.sets {
             if let Some(input_sets) = input_sets {
@@ -128,11 +174,12 @@ impl CardProduct {
                 }
             }
         }
+        let product = self.product(context);
         filter_context!(filter,
-            self.name(context) => input.name,
-            self.release_date(context) => input.release_date,
-            self.r#type(context) => input.r#type,
-            self.wave(context) => input.wave
+            product.map(|product| &product.name) => input.name,
+            product.map(|product| &product.release_date) => input.release_date,
+            product.map(|product| &product.r#type) => input.r#type,
+            product.map(|product| &product.wave) => input.wave
         );
 
         filter
@@ -147,13 +194,16 @@ mod tests {
     #[test]
     fn included_none() {
         let card_set = CardSet {
-            name: String::from("Something Sinister"),
+            code: String::from("something-sinister"),
             positions: Some(vec![1, 2]),
+            product: String::from("MC01en"),
+            id: None,
         };
         let card_product = CardProduct {
             code: String::from("MC01en"),
             positions: vec![83, 84],
             sets: Some(vec![card_set]),
+            id: None,
         };
         let input_none = CardProductInputBuilder::default().build().unwrap();
 
@@ -164,13 +214,16 @@ mod tests {
     fn included_code() {
         let ctx = Ctx::default();
         let card_set = CardSet {
-            name: String::from("Something Sinister"),
+            code: String::from("something-sinister"),
             positions: Some(vec![1, 2]),
+            product: String::from("MC01en"),
+            id: None,
         };
         let card_product = CardProduct {
             code: String::from("MC01en"),
             positions: vec![83, 84],
             sets: Some(vec![card_set]),
+            id: None,
         };
         let code_input_included = CardProductInputBuilder::default()
             .code(Some(String::from("MC01en")))
@@ -189,13 +242,16 @@ mod tests {
     fn included_positions() {
         let ctx = Ctx::default();
         let card_set = CardSet {
-            name: String::from("Something Sinister"),
+            code: String::from("something-sinister"),
             positions: Some(vec![1, 2]),
+            product: String::from("MC01en"),
+            id: None,
         };
         let card_product = CardProduct {
             code: String::from("MC01en"),
             positions: vec![83, 84],
             sets: Some(vec![card_set]),
+            id: None,
         };
         let positions_input_included = CardProductInputBuilder::default()
             .positions(Some(vec![83]))
@@ -222,24 +278,29 @@ mod tests {
     }
 
     #[test]
-    fn input_included_sets_name() {
+    fn input_included_sets_code() {
         let ctx = Ctx::default();
         let sinister_set = CardSet {
-            name: String::from("Something Sinister"),
+            code: String::from("something-sinister"),
             positions: Some(vec![1, 2]),
+            product: String::from("MC01en"),
+            id: None,
         };
         let sit_com_set = CardSet {
-            name: String::from("Sit-Com"),
+            code: String::from("sit-com"),
             positions: Some(vec![3, 4]),
+            product: String::from("MC01en"),
+            id: None,
         };
         let card_product = CardProduct {
             code: String::from("MC01en"),
             positions: vec![83, 84],
             sets: Some(vec![sinister_set, sit_com_set]),
+            id: None,
         };
         let input = CardProductInputBuilder::default()
             .sets(Some(Some(vec![CardSetInputBuilder::default()
-                .name(Some(String::from("Something Sinister")))
+                .code(Some(String::from("something-sinister")))
                 .build()
                 .unwrap()])))
             .build()
@@ -247,44 +308,49 @@ mod tests {
         let input_or = CardProductInputBuilder::default()
             .sets(Some(Some(vec![
                 CardSetInputBuilder::default()
-                    .name(Some(String::from("Something Sinister")))
+                    .code(Some(String::from("something-sinister")))
                     .build()
                     .unwrap(),
                 CardSetInputBuilder::default()
-                    .name(Some(String::from("None")))
+                    .code(Some(String::from("none")))
                     .build()
                     .unwrap(),
             ])))
             .build()
             .unwrap();
 
-        assert_eq!(true, card_product.included(&input, &ctx));
-        assert_eq!(true, card_product.included(&input_or, &ctx));
+        assert!(card_product.included(&input, &ctx));
+        assert!(card_product.included(&input_or, &ctx));
     }
 
     #[test]
-    fn input_not_included_sets_name() {
+    fn input_not_included_sets_code() {
         let sinister_set = CardSet {
-            name: String::from("Something Sinister"),
+            code: String::from("something-sinister"),
             positions: Some(vec![1, 2]),
+            product: String::from("MC01en"),
+            id: None,
         };
         let sit_com_set = CardSet {
-            name: String::from("Sit-Com"),
+            code: String::from("sit-com"),
             positions: Some(vec![3, 4]),
+            product: String::from("MC01en"),
+            id: None,
         };
         let card_product = CardProduct {
             code: String::from("MC01en"),
             positions: vec![83, 84],
             sets: Some(vec![sinister_set, sit_com_set]),
+            id: None,
         };
         let input = CardProductInputBuilder::default()
             .sets(Some(Some(vec![CardSetInputBuilder::default()
-                .name(Some(String::from("Not Included")))
+                .code(Some(String::from("not-included")))
                 .build()
                 .unwrap()])))
             .build()
             .unwrap();
 
-        assert_eq!(false, card_product.included(&input, &Ctx::default()));
+        assert!(!card_product.included(&input, &Ctx::default()));
     }
 }
@@ -1,15 +1,17 @@
 use crate::{
-    graphql::{filter, filter_context, Ctx, SHQScalarValue},
-    product::SetType as ProductSetType,
+    graphql::{filter, filter_context, Ctx, SHQScalarValue, SetId},
+    product::{Set as ProductSet, SetType as ProductSetType},
 };
 use derive_builder::Builder;
 use juniper::{graphql_object, GraphQLInputObject};
-use serde::Deserialize;
-use std::collections::HashSet;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
 #[derive(Builder, Clone, GraphQLInputObject)]
 #[graphql(scalar = SHQScalarValue)]
 pub struct CardSetInput {
+    #[builder(default)]
+    pub code: Option<String>,
     #[builder(default)]
     pub name: Option<String>,
     #[builder(default)]
@@ -18,17 +20,28 @@ pub struct CardSetInput {
     pub r#type: Option<ProductSetType>,
 }
 
-#[derive(Clone, Deserialize)]
+#[derive(Clone, Deserialize, Serialize, ToSchema)]
 #[serde(deny_unknown_fields)]
 pub struct CardSet {
-    pub name: String,
+    /// Code of the set within the product the card is printed in
+    pub code: String,
     pub positions: Option<Vec<u32>>,
+    /// Code of that product, filled in from the `CardProduct` the set is listed in
+    #[serde(skip)]
+    pub product: String,
+    /// Id of the set in the context the card was loaded into, set by `Ctx::new`
+    #[serde(skip)]
+    pub id: Option<SetId>,
 }
 
 #[graphql_object(Context = Ctx, scalar = SHQScalarValue)]
 impl CardSet {
-    fn name(&self) -> &str {
-        &self.name
+    fn code(&self) -> &str {
+        &self.code
+    }
+
+    fn name(&self, context: &Ctx) -> Option<&String> {
+        self.name(context)
     }
 
     fn positions(&self) -> Option<&Vec<u32>> {
@@ -41,28 +54,46 @@ impl CardSet {
 }
 
 impl CardSet {
+    /// The product set this refers to. Cards loaded into the context already know its id;
+    /// others, like copies from a card store, look it up by code.
+    pub fn set<'a>(&self, context: &'a Ctx) -> Option<&'a ProductSet> {
+        self.id
+            .or_else(|| context.set_id(&self.product, &self.code))
+            .map(|id| context.set_by_id(id))
+    }
+
+    pub fn name<'a>(&self, context: &'a Ctx) -> Option<&'a String> {
+        self.set(context).map(|set| &set.name)
+    }
+
     pub fn r#type<'a>(&self, context: &'a Ctx) -> Option<&'a ProductSetType> {
-        context.set(&self.name).as_ref().map(|set| &set.r#type)
+        self.set(context).map(|set| &set.r#type)
     }
 
     pub fn included(&self, input: &CardSetInput, context: &Ctx) -> bool {
         let mut filter = true;
 
-        filter!(filter, &self.name => input.name);
+        filter!(filter, &self.code => input.code);
         if let Some(input_positions) = &input.positions {
             if input_positions.is_some() && self.positions.is_some() {
                 let input_positions = input_positions.as_ref().unwrap();
-                let a: HashSet<&u32> = input_positions.iter().collect();
-                let b: HashSet<&u32> = self.positions.as_ref().unwrap().iter().collect();
+                let positions = self.positions.as_ref().unwrap();
 
-                filter = a.intersection(&b).next().is_some() && filter;
+                filter = input_positions
+                    .iter()
+                    .any(|position| positions.contains(position))
+                    && filter;
             } else if input_positions.is_none() && self.positions.is_none() {
                 filter = true && filter;
             } else {
                 filter = false;
             }
         }
-        filter_context!(filter, self.r#type(context) => input.r#type);
+        let set = self.set(context);
+        filter_context!(filter,
+            set.map(|set| &set.name) => input.name,
+            set.map(|set| &set.r#type) => input.r#type
+        );
 
         filter
     }
@@ -71,44 +102,53 @@ impl CardSet {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{card::Document as CardDocument, product::Document as ProductDocument};
 
     #[test]
-    fn included_name() {
+    fn included_code() {
         let sinister_set = CardSet {
-            name: String::from("Something Sinister"),
+            code: String::from("something-sinister"),
             positions: Some(vec![1, 2]),
+            product: String::from("MC01en"),
+            id: None,
         };
         let input = CardSetInputBuilder::default()
-            .name(Some(String::from("Something Sinister")))
+            .code(Some(String::from("something-sinister")))
             .build()
             .unwrap();
 
-        assert_eq!(true, sinister_set.included(&input, &Ctx::default()));
+        assert!(sinister_set.included(&input, &Ctx::default()));
     }
 
     #[test]
-    fn not_included_name() {
+    fn not_included_code() {
         let sinister_set = CardSet {
-            name: String::from("Something Sinister"),
+            code: String::from("something-sinister"),
             positions: Some(vec![1, 2]),
+            product: String::from("MC01en"),
+            id: None,
         };
         let input = CardSetInputBuilder::default()
-            .name(Some(String::from("Not Included")))
+            .code(Some(String::from("not-included")))
             .build()
             .unwrap();
 
-        assert_eq!(false, sinister_set.included(&input, &Ctx::default()));
+        assert!(!sinister_set.included(&input, &Ctx::default()));
     }
 
     #[test]
     fn included_positions() {
         let sinister_set = CardSet {
-            name: String::from("Something Sinister"),
+            code: String::from("something-sinister"),
             positions: Some(vec![1, 2]),
+            product: String::from("MC01en"),
+            id: None,
         };
         let none_set = CardSet {
-            name: String::from("None Set"),
+            code: String::from("none-set"),
             positions: None,
+            product: String::from("MC01en"),
+            id: None,
         };
         let input = CardSetInputBuilder::default()
             .positions(Some(Some(vec![1])))
@@ -132,12 +172,16 @@ mod tests {
     #[test]
     fn not_included_positions() {
         let sinister_set = CardSet {
-            name: String::from("Something Sinister"),
+            code: String::from("something-sinister"),
             positions: Some(vec![1, 2]),
+            product: String::from("MC01en"),
+            id: None,
         };
         let none_set = CardSet {
-            name: String::from("None Set"),
+            code: String::from("none-set"),
             positions: None,
+            product: String::from("MC01en"),
+            id: None,
         };
         let input = CardSetInputBuilder::default()
             .positions(Some(Some(vec![3])))
@@ -157,11 +201,69 @@ mod tests {
     #[test]
     fn included_none() {
         let sinister_set = CardSet {
-            name: String::from("Soemthing Sinister"),
+            code: String::from("soemthing-sinister"),
             positions: Some(vec![1, 2]),
+            product: String::from("MC01en"),
+            id: None,
         };
         let input = CardSetInputBuilder::default().build().unwrap();
 
         assert_eq!(true, sinister_set.included(&input, &Ctx::default()));
     }
+
+    #[test]
+    fn it_resolves_sets_within_their_product() {
+        let products: ProductDocument = toml::from_str(
+            r#"
+            [[product]]
+            name = "Core Set"
+            release_date = "2019-11-01"
+            type = "Core Set"
+            code = "MC01en"
+            wave = 1
+
+            [[product.set]]
+            name = "Spider-Man"
+            type = "Hero Signature"
+
+            [[product]]
+            name = "Spider-Man Reprint"
+            release_date = "2020-01-01"
+            type = "Custom"
+            code = "SP01en"
+            wave = 1
+
+            [[product.set]]
+            name = "Spider-Man"
+            type = "Nemesis"
+            "#,
+        )
+        .unwrap();
+        let cards: CardDocument = toml::from_str(
+            r#"
+            [[card]]
+            [[card.product]]
+            code = "SP01en"
+            positions = [1]
+            [[card.product.set]]
+            code = "spider-man"
+            [[card.side]]
+            type = "Obligation"
+            name = "Eviction Notice"
+            "#,
+        )
+        .unwrap();
+        let ctx = Ctx::new(cards.cards, products.products, vec![]);
+        let card_set = &ctx.cards[0].products[0].sets.as_ref().unwrap()[0];
+
+        assert_eq!("SP01en", card_set.product);
+        assert_eq!(ctx.set_id("SP01en", "spider-man"), card_set.id);
+        assert_eq!(ctx.product_id("SP01en"), ctx.cards[0].products[0].id);
+        assert_eq!(Some(&ProductSetType::Nemesis), card_set.r#type(&ctx));
+        assert!(ctx.set_by_name("Spider-Man").is_none());
+        assert_eq!(
+            Some(&ProductSetType::HeroSignature),
+            ctx.set("MC01en", "spider-man").map(|set| &set.r#type)
+        );
+    }
 }
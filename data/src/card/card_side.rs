@@ -1,13 +1,13 @@
 use crate::{
     card::{BasicPower, Cost, HitPoints, Keyword, Resource, SideSchemeIcon, Trait},
-    graphql::{filter, filter_option, filter_vec, SHQScalarValue},
+    graphql::{filter, filter_option, filter_vec, Ctx, SHQScalarValue},
 };
 use derive_builder::Builder;
-use juniper::{graphql_object, GraphQLEnum, GraphQLInputObject};
-use serde::Deserialize;
-use std::collections::HashSet;
+use juniper::{graphql_object, FieldResult, GraphQLEnum, GraphQLInputObject};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
-#[derive(Clone, Deserialize)]
+#[derive(Clone, Deserialize, Serialize, ToSchema)]
 pub struct CardSide {
     pub name: String,
     pub text: Option<String>,
@@ -50,10 +50,16 @@ pub struct CardSideInput {
     #[builder(default)]
     pub hit_points: Option<Option<HitPoints>>,
     #[builder(default)]
-    pub traits: Option<Option<Vec<Trait>>>,
+    pub traits: Option<Option<Vec<String>>>,
+    #[builder(default)]
+    pub keywords: Option<Option<Vec<Keyword>>>,
+    #[builder(default)]
+    pub kind: Option<CardSideKind>,
+    #[builder(default)]
+    pub cost: Option<Option<Cost>>,
 }
 
-#[derive(Clone, Deserialize)]
+#[derive(Clone, Deserialize, Serialize, ToSchema)]
 #[serde(tag = "type")]
 #[serde(deny_unknown_fields)]
 pub enum CardSideVariant {
@@ -67,7 +73,9 @@ pub enum CardSideVariant {
         hand_size: u32,
         hit_points: HitPoints,
         #[serde(default)]
-        traits: Vec<Trait>,
+        traits: Vec<String>,
+        #[serde(default)]
+        keywords: Vec<Keyword>,
     },
     #[serde(rename = "Alter-Ego")]
     AlterEgo {
@@ -78,7 +86,9 @@ pub enum CardSideVariant {
         hand_size: u32,
         hit_points: HitPoints,
         #[serde(default)]
-        traits: Vec<Trait>,
+        traits: Vec<String>,
+        #[serde(default)]
+        keywords: Vec<Keyword>,
     },
     Ally {
         subname: Option<String>,
@@ -90,18 +100,24 @@ pub enum CardSideVariant {
         atk_consequential: u32,
         hit_points: HitPoints,
         #[serde(default)]
-        traits: Vec<Trait>,
+        traits: Vec<String>,
         resources: Vec<Resource>,
+        #[serde(default)]
+        keywords: Vec<Keyword>,
     },
     Event {
         cost: Cost,
         #[serde(default)]
-        traits: Vec<Trait>,
+        traits: Vec<String>,
         resources: Vec<Resource>,
+        #[serde(default)]
+        keywords: Vec<Keyword>,
     },
     Obligation {
         #[serde(default)]
         boost_icons: u8,
+        #[serde(default)]
+        keywords: Vec<Keyword>,
     },
     Resource {
         resources: Vec<Resource>,
@@ -111,8 +127,10 @@ pub enum CardSideVariant {
         #[serde(default)]
         unique: bool,
         #[serde(default)]
-        traits: Vec<Trait>,
+        traits: Vec<String>,
         resources: Vec<Resource>,
+        #[serde(default)]
+        keywords: Vec<Keyword>,
     },
     Upgrade {
         cost: Cost,
@@ -120,12 +138,16 @@ pub enum CardSideVariant {
         unique: bool,
         resources: Vec<Resource>,
         #[serde(default)]
-        traits: Vec<Trait>,
+        traits: Vec<String>,
+        #[serde(default)]
+        keywords: Vec<Keyword>,
     },
     Attachment {
         boost_icons: u8,
         #[serde(default)]
-        traits: Vec<Trait>,
+        traits: Vec<String>,
+        #[serde(default)]
+        keywords: Vec<Keyword>,
     },
     Minion {
         unique: bool,
@@ -133,7 +155,7 @@ pub enum CardSideVariant {
         atk: BasicPower,
         hit_points: HitPoints,
         #[serde(default)]
-        traits: Vec<Trait>,
+        traits: Vec<String>,
         #[serde(default)]
         boost_icons: u8,
         #[serde(default)]
@@ -146,13 +168,15 @@ pub enum CardSideVariant {
     SideScheme {
         icons: Option<Vec<SideSchemeIcon>>,
         #[serde(default)]
-        traits: Vec<Trait>,
+        traits: Vec<String>,
         starting_threat: String,
         #[serde(default)]
         boost_icons: u8,
         #[serde(default)]
         boost_star_icon: bool,
         boost_text: Option<String>,
+        #[serde(default)]
+        keywords: Vec<Keyword>,
     },
     Treachery {
         #[serde(default)]
@@ -160,6 +184,8 @@ pub enum CardSideVariant {
         #[serde(default)]
         boost_star_icon: bool,
         boost_text: Option<String>,
+        #[serde(default)]
+        keywords: Vec<Keyword>,
     },
 }
 
@@ -170,11 +196,13 @@ impl CardSide {
         filter!(filter,
             &self.name => input.name,
             &self.text => input.text,
-            &self.flavor_text => input.flavor_text
+            &self.flavor_text => input.flavor_text,
+            &self.kind() => input.kind
         );
         filter_vec!(filter,
             self.illustrators.as_ref() => &input.illustrators,
-            self.traits() => &input.traits
+            self.traits() => &input.traits,
+            self.keywords() => &input.keywords
         );
         filter_option!(filter,
             self.side() => input.side,
@@ -186,13 +214,14 @@ impl CardSide {
             self.def() => input.def,
             self.rec() => input.rec,
             self.hand_size() => input.hand_size,
-            self.hit_points() => input.hit_points
+            self.hit_points() => input.hit_points,
+            self.cost() => input.cost
         );
 
         filter
     }
 
-    fn side(&self) -> Option<&Side> {
+    pub fn side(&self) -> Option<&Side> {
         match &self.variant {
             CardSideVariant::Hero { side, .. } => Some(side),
             CardSideVariant::AlterEgo { side, .. } => Some(side),
@@ -212,7 +241,7 @@ impl CardSide {
         }
     }
 
-    fn thw(&self) -> Option<&BasicPower> {
+    pub fn thw(&self) -> Option<&BasicPower> {
         match &self.variant {
             CardSideVariant::Hero { thw, .. } => Some(thw),
             CardSideVariant::Ally { thw, .. } => Some(thw),
@@ -229,7 +258,7 @@ impl CardSide {
         }
     }
 
-    fn atk(&self) -> Option<&BasicPower> {
+    pub fn atk(&self) -> Option<&BasicPower> {
         match &self.variant {
             CardSideVariant::Hero { atk, .. } => Some(atk),
             CardSideVariant::Ally { atk, .. } => Some(atk),
@@ -261,7 +290,7 @@ impl CardSide {
         }
     }
 
-    fn hand_size(&self) -> Option<&u32> {
+    pub fn hand_size(&self) -> Option<&u32> {
         match &self.variant {
             CardSideVariant::Hero { hand_size, .. } => Some(hand_size),
             CardSideVariant::AlterEgo { hand_size, .. } => Some(hand_size),
@@ -269,7 +298,7 @@ impl CardSide {
         }
     }
 
-    fn hit_points(&self) -> Option<&HitPoints> {
+    pub fn hit_points(&self) -> Option<&HitPoints> {
         match &self.variant {
             CardSideVariant::Hero { hit_points, .. } => Some(hit_points),
             CardSideVariant::AlterEgo { hit_points, .. } => Some(hit_points),
@@ -279,7 +308,7 @@ impl CardSide {
         }
     }
 
-    fn traits(&self) -> Option<&Vec<Trait>> {
+    pub fn traits(&self) -> Option<&Vec<String>> {
         match &self.variant {
             CardSideVariant::Hero { traits, .. } => Some(traits),
             CardSideVariant::AlterEgo { traits, .. } => Some(traits),
@@ -293,9 +322,98 @@ impl CardSide {
             _ => None,
         }
     }
+    pub fn cost(&self) -> Option<&Cost> {
+        match &self.variant {
+            CardSideVariant::Ally { cost, .. } => Some(cost),
+            CardSideVariant::Event { cost, .. } => Some(cost),
+            CardSideVariant::Support { cost, .. } => Some(cost),
+            CardSideVariant::Upgrade { cost, .. } => Some(cost),
+            _ => None,
+        }
+    }
+
+    pub fn resources(&self) -> Option<&Vec<Resource>> {
+        match &self.variant {
+            CardSideVariant::Ally { resources, .. } => Some(resources),
+            CardSideVariant::Event { resources, .. } => Some(resources),
+            CardSideVariant::Resource { resources } => Some(resources),
+            CardSideVariant::Support { resources, .. } => Some(resources),
+            CardSideVariant::Upgrade { resources, .. } => Some(resources),
+            _ => None,
+        }
+    }
+
+    pub fn boost_icons(&self) -> Option<u8> {
+        match &self.variant {
+            CardSideVariant::Obligation { boost_icons, .. } => Some(*boost_icons),
+            CardSideVariant::Attachment { boost_icons, .. } => Some(*boost_icons),
+            CardSideVariant::Minion { boost_icons, .. } => Some(*boost_icons),
+            CardSideVariant::SideScheme { boost_icons, .. } => Some(*boost_icons),
+            CardSideVariant::Treachery { boost_icons, .. } => Some(*boost_icons),
+            _ => None,
+        }
+    }
+
+    pub fn boost_star_icon(&self) -> bool {
+        match &self.variant {
+            CardSideVariant::Minion {
+                boost_star_icon, ..
+            } => *boost_star_icon,
+            CardSideVariant::SideScheme {
+                boost_star_icon, ..
+            } => *boost_star_icon,
+            CardSideVariant::Treachery {
+                boost_star_icon, ..
+            } => *boost_star_icon,
+            _ => false,
+        }
+    }
+
+    pub fn boost_text(&self) -> Option<&String> {
+        match &self.variant {
+            CardSideVariant::Minion { boost_text, .. } => boost_text.as_ref(),
+            CardSideVariant::SideScheme { boost_text, .. } => boost_text.as_ref(),
+            CardSideVariant::Treachery { boost_text, .. } => boost_text.as_ref(),
+            _ => None,
+        }
+    }
+
+    pub fn kind(&self) -> CardSideKind {
+        match &self.variant {
+            CardSideVariant::Hero { .. } => CardSideKind::Hero,
+            CardSideVariant::AlterEgo { .. } => CardSideKind::AlterEgo,
+            CardSideVariant::Ally { .. } => CardSideKind::Ally,
+            CardSideVariant::Event { .. } => CardSideKind::Event,
+            CardSideVariant::Obligation { .. } => CardSideKind::Obligation,
+            CardSideVariant::Resource { .. } => CardSideKind::Resource,
+            CardSideVariant::Support { .. } => CardSideKind::Support,
+            CardSideVariant::Upgrade { .. } => CardSideKind::Upgrade,
+            CardSideVariant::Attachment { .. } => CardSideKind::Attachment,
+            CardSideVariant::Minion { .. } => CardSideKind::Minion,
+            CardSideVariant::SideScheme { .. } => CardSideKind::SideScheme,
+            CardSideVariant::Treachery { .. } => CardSideKind::Treachery,
+        }
+    }
+
+    pub fn keywords(&self) -> Option<&Vec<Keyword>> {
+        match &self.variant {
+            CardSideVariant::Hero { keywords, .. } => Some(keywords),
+            CardSideVariant::AlterEgo { keywords, .. } => Some(keywords),
+            CardSideVariant::Ally { keywords, .. } => Some(keywords),
+            CardSideVariant::Event { keywords, .. } => Some(keywords),
+            CardSideVariant::Obligation { keywords, .. } => Some(keywords),
+            CardSideVariant::Support { keywords, .. } => Some(keywords),
+            CardSideVariant::Upgrade { keywords, .. } => Some(keywords),
+            CardSideVariant::Attachment { keywords, .. } => Some(keywords),
+            CardSideVariant::Minion { keywords, .. } => Some(keywords),
+            CardSideVariant::SideScheme { keywords, .. } => Some(keywords),
+            CardSideVariant::Treachery { keywords, .. } => Some(keywords),
+            CardSideVariant::Resource { .. } => None,
+        }
+    }
 }
 
-#[graphql_object(Scalar = SHQScalarValue)]
+#[graphql_object(Context = Ctx, Scalar = SHQScalarValue)]
 impl CardSide {
     fn name(&self) -> &String {
         &self.name
@@ -349,12 +467,69 @@ impl CardSide {
         self.hit_points()
     }
 
-    fn traits(&self) -> Option<&Vec<Trait>> {
+    /// Traits of the side, an error when one isn't in the trait registry
+    fn traits(&self, context: &Ctx) -> FieldResult<Option<Vec<&Trait>>> {
         self.traits()
+            .map(|traits| {
+                traits
+                    .iter()
+                    .map(|name| {
+                        context
+                            .known_trait(name)
+                            .map_err(|error| format!("{error} on {}", self.name).into())
+                    })
+                    .collect()
+            })
+            .transpose()
+    }
+
+    fn keywords(&self) -> Option<&Vec<Keyword>> {
+        self.keywords()
+    }
+
+    fn kind(&self) -> CardSideKind {
+        self.kind()
+    }
+
+    fn boost_icons(&self) -> Option<i32> {
+        self.boost_icons().map(i32::from)
+    }
+
+    fn boost_star_icon(&self) -> bool {
+        self.boost_star_icon()
+    }
+
+    fn boost_text(&self) -> Option<&String> {
+        self.boost_text()
+    }
+
+    fn cost(&self) -> Option<&Cost> {
+        self.cost()
+    }
+
+    fn resources(&self) -> Option<&Vec<Resource>> {
+        self.resources()
     }
 }
 
-#[derive(Clone, Deserialize, GraphQLEnum, PartialEq)]
+/// The type of a card side, without its stats
+#[derive(Clone, Copy, Debug, Eq, GraphQLEnum, Hash, Ord, PartialEq, PartialOrd)]
+pub enum CardSideKind {
+    Hero,
+    AlterEgo,
+    Ally,
+    Event,
+    Obligation,
+    Resource,
+    Support,
+    Upgrade,
+    Attachment,
+    Minion,
+    SideScheme,
+    Treachery,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, GraphQLEnum, PartialEq, Serialize, ToSchema)]
 pub enum Side {
     A,
     B,
@@ -365,7 +540,6 @@ pub enum Side {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::card::Trait;
 
     fn setup_card_side() -> CardSide {
         CardSide {
@@ -381,7 +555,8 @@ mod tests {
                 def: BasicPower::Number(3),
                 hand_size: 5,
                 hit_points: HitPoints::Number(10),
-                traits: vec![Trait::Avenger],
+                traits: vec![String::from("Avenger")],
+                keywords: vec![],
             },
         }
     }
@@ -484,4 +659,29 @@ mod tests {
         assert_eq!(false, card_side.included(&input_none));
         assert_eq!(false, card_side.included(&input));
     }
+
+    #[test]
+    fn keywords_included() {
+        let mut card_side = setup_card_side();
+        if let CardSideVariant::Hero { keywords, .. } = &mut card_side.variant {
+            keywords.push(Keyword::Retaliate(1));
+        }
+        let input = CardSideInputBuilder::default()
+            .keywords(Some(Some(vec![Keyword::Retaliate(1), Keyword::Steady])))
+            .build()
+            .unwrap();
+
+        assert!(card_side.included(&input));
+    }
+
+    #[test]
+    fn keywords_not_included() {
+        let card_side = setup_card_side();
+        let input = CardSideInputBuilder::default()
+            .keywords(Some(Some(vec![Keyword::Retaliate(1)])))
+            .build()
+            .unwrap();
+
+        assert!(!card_side.included(&input));
+    }
 }
@@ -1,10 +1,15 @@
 use juniper::{graphql_scalar, ParseScalarResult, ParseScalarValue, Value};
 use serde::{
     de::{self, Deserializer, Unexpected, Visitor},
-    Deserialize,
+    Deserialize, Serialize, Serializer,
 };
+use serde_json::json;
 use std::{fmt, str::FromStr};
 use thiserror::Error;
+use utoipa::{
+    openapi::{ObjectBuilder, RefOr, Schema, SchemaType},
+    ToSchema,
+};
 
 struct CostVisitor;
 
@@ -35,7 +40,7 @@ impl<'de> Visitor<'de> for CostVisitor {
 #[error("{0} is not a Number or X")]
 pub struct ParseCostError(String);
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub enum Cost {
     Number(u8),
     X,
@@ -65,6 +70,26 @@ impl FromStr for Cost {
     }
 }
 
+impl<'s> ToSchema<'s> for Cost {
+    fn schema() -> (&'s str, RefOr<Schema>) {
+        let schema = ObjectBuilder::new()
+            .schema_type(SchemaType::String)
+            .description(Some("Cost of a card, a number or X"))
+            .example(Some(json!("2")));
+
+        ("Cost", schema.into())
+    }
+}
+
+impl Serialize for Cost {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
 impl<'de> Deserialize<'de> for Cost {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
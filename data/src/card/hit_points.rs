@@ -3,10 +3,15 @@ use lazy_static::lazy_static;
 use regex::Regex;
 use serde::{
     de::{self, Deserializer, Unexpected, Visitor},
-    Deserialize,
+    Deserialize, Serialize, Serializer,
 };
+use serde_json::json;
 use std::{fmt, str::FromStr};
 use thiserror::Error;
+use utoipa::{
+    openapi::{ObjectBuilder, RefOr, Schema, SchemaType},
+    ToSchema,
+};
 
 struct HitPointsVisitor;
 
@@ -78,6 +83,26 @@ impl FromStr for HitPoints {
     }
 }
 
+impl<'s> ToSchema<'s> for HitPoints {
+    fn schema() -> (&'s str, RefOr<Schema>) {
+        let schema = ObjectBuilder::new()
+            .schema_type(SchemaType::String)
+            .description(Some("A number, optionally per player"))
+            .example(Some(json!("3 per Player")));
+
+        ("HitPoints", schema.into())
+    }
+}
+
+impl Serialize for HitPoints {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
 impl<'de> Deserialize<'de> for HitPoints {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
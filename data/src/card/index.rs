@@ -0,0 +1,439 @@
+use crate::{
+    card::{
+        card_side::{CardSideInput, CardSideKind},
+        Aspect, Card, CardInput, CardProductInput, Cost,
+    },
+    product::Product,
+};
+use std::{borrow::Borrow, cmp::Ordering, collections::HashMap, hash::Hash, sync::Arc};
+
+/// Positions of cards, sorted and without duplicates. `None` stands for every card.
+type Candidates = Option<Vec<usize>>;
+
+/// A value cards are indexed by, which `CardInput` filters on
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Key {
+    Aspect(Option<Aspect>),
+    Kind(CardSideKind),
+    Trait(String),
+    /// The code of a product the card is printed in
+    Product(String),
+    /// The code of a set the card is in
+    SetCode(String),
+    /// The name of a set the card is in
+    SetName(String),
+    Cost(Option<Cost>),
+}
+
+impl Key {
+    /// Every key `card` is indexed by, naming its sets through `set_names`
+    pub fn of(card: &Card, set_names: &SetNames) -> Vec<Key> {
+        let mut keys = vec![Key::Aspect(card.aspect.clone())];
+        for product in card.products.iter() {
+            keys.push(Key::Product(product.code.clone()));
+            for set in product.sets.iter().flatten() {
+                keys.push(Key::SetCode(set.code.clone()));
+                if let Some(name) = set_names.get(&(product.code.as_str(), set.code.as_str())) {
+                    keys.push(Key::SetName(String::from(*name)));
+                }
+            }
+        }
+        for side in card.sides.iter() {
+            keys.push(Key::Kind(side.kind()));
+            keys.push(Key::Cost(side.cost().cloned()));
+            for name in side.traits().into_iter().flatten() {
+                keys.push(Key::Trait(name.clone()));
+            }
+        }
+
+        keys
+    }
+
+    /// The key as a column name and a value, as stored in a database. Values only have to match
+    /// the ones the same build stores, since the data is imported again on every load.
+    pub fn column(&self) -> (&'static str, String) {
+        match self {
+            Key::Aspect(aspect) => ("aspect", format!("{aspect:?}")),
+            Key::Kind(kind) => ("kind", format!("{kind:?}")),
+            Key::Trait(name) => ("trait", name.clone()),
+            Key::Product(code) => ("product", code.clone()),
+            Key::SetCode(code) => ("set_code", code.clone()),
+            Key::SetName(name) => ("set_name", name.clone()),
+            Key::Cost(cost) => ("cost", format!("{cost:?}")),
+        }
+    }
+}
+
+/// Set names by the codes of their product and set
+pub type SetNames<'a> = HashMap<(&'a str, &'a str), &'a str>;
+
+/// Names of the sets in `products`
+pub fn set_names<P: Borrow<Product>>(products: &[P]) -> SetNames<'_> {
+    products
+        .iter()
+        .map(Borrow::borrow)
+        .flat_map(|product| {
+            product.sets.iter().map(move |set| {
+                (
+                    (product.code.as_str(), set.code.as_str()),
+                    set.name.as_str(),
+                )
+            })
+        })
+        .collect()
+}
+
+/// The cards that can match a `CardInput`, by the keys they're indexed by. A card store finds
+/// the candidates for a plan, which are then checked against the whole input.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Plan {
+    /// Nothing narrows the cards down
+    Every,
+    Key(Key),
+    /// Cards matching every plan
+    All(Vec<Plan>),
+    /// Cards matching any plan, or no card when there are none
+    Any(Vec<Plan>),
+}
+
+impl Plan {
+    pub fn new(input: &CardInput) -> Self {
+        let mut plans = vec![];
+        if let Some(aspect) = &input.aspect {
+            plans.push(Plan::Key(Key::Aspect(aspect.clone())));
+        }
+        if let Some(products) = &input.products {
+            plans.push(Plan::any(products.iter().map(Plan::product)));
+        }
+        if let Some(sides) = &input.sides {
+            plans.push(Plan::any(sides.iter().map(Plan::side)));
+        }
+
+        Plan::all(plans)
+    }
+
+    fn product(input: &CardProductInput) -> Self {
+        let mut plans = vec![];
+        if let Some(code) = &input.code {
+            plans.push(Plan::Key(Key::Product(code.clone())));
+        }
+        if let Some(Some(sets)) = &input.sets {
+            plans.push(Plan::any(sets.iter().map(|set| {
+                let code = set.code.clone().map(|code| Plan::Key(Key::SetCode(code)));
+                let name = set.name.clone().map(|name| Plan::Key(Key::SetName(name)));
+
+                Plan::all(code.into_iter().chain(name))
+            })));
+        }
+
+        Plan::all(plans)
+    }
+
+    fn side(input: &CardSideInput) -> Self {
+        let mut plans = vec![];
+        if let Some(kind) = &input.kind {
+            plans.push(Plan::Key(Key::Kind(*kind)));
+        }
+        if let Some(cost) = &input.cost {
+            plans.push(Plan::Key(Key::Cost(cost.clone())));
+        }
+        if let Some(Some(traits)) = &input.traits {
+            plans.push(Plan::any(
+                traits
+                    .iter()
+                    .map(|name| Plan::Key(Key::Trait(name.clone()))),
+            ));
+        }
+
+        Plan::all(plans)
+    }
+
+    /// Cards matching every one of `plans`, leaving out the ones that don't narrow them down
+    fn all(plans: impl IntoIterator<Item = Plan>) -> Self {
+        let mut plans: Vec<_> = plans
+            .into_iter()
+            .filter(|plan| *plan != Plan::Every)
+            .collect();
+
+        match plans.len() {
+            0 => Plan::Every,
+            1 => plans.remove(0),
+            _ => Plan::All(plans),
+        }
+    }
+
+    /// Cards matching any of `plans`, which is every card when any of them is
+    fn any(plans: impl IntoIterator<Item = Plan>) -> Self {
+        let mut plans: Vec<_> = plans.into_iter().collect();
+        if plans.contains(&Plan::Every) {
+            return Plan::Every;
+        }
+
+        match plans.len() {
+            1 => plans.remove(0),
+            _ => Plan::Any(plans),
+        }
+    }
+}
+
+/// Positions of cards by the keys they're indexed by, built once when the data loads so a
+/// query only runs its predicates on cards that can match
+#[derive(Clone, Default)]
+pub struct CardIndex {
+    keys: HashMap<Key, Vec<usize>>,
+}
+
+impl CardIndex {
+    /// Indexes `cards`, naming their sets through `set_names`
+    pub fn new(cards: &[Arc<Card>], set_names: &SetNames) -> Self {
+        let mut index = Self::default();
+        for (position, card) in cards.iter().enumerate() {
+            for key in Key::of(card, set_names) {
+                add(&mut index.keys, key, position);
+            }
+        }
+
+        index
+    }
+
+    /// Positions of the cards that can match `plan`. `None` when it doesn't narrow them down.
+    pub fn candidates(&self, plan: &Plan) -> Candidates {
+        match plan {
+            Plan::Every => None,
+            Plan::Key(key) => Some(lookup(&self.keys, key)),
+            Plan::All(plans) => plans.iter().fold(None, |candidates, plan| {
+                intersect(candidates, self.candidates(plan))
+            }),
+            Plan::Any(plans) => union(plans.iter().map(|plan| self.candidates(plan))),
+        }
+    }
+}
+
+fn add<K: Eq + Hash>(index: &mut HashMap<K, Vec<usize>>, key: K, position: usize) {
+    let positions = index.entry(key).or_default();
+    // a card is visited once, so a repeat can only be the last position
+    if positions.last() != Some(&position) {
+        positions.push(position);
+    }
+}
+
+fn lookup<K: Eq + Hash>(index: &HashMap<K, Vec<usize>>, key: &K) -> Vec<usize> {
+    index.get(key).cloned().unwrap_or_default()
+}
+
+fn intersect(a: Candidates, b: Candidates) -> Candidates {
+    match (a, b) {
+        (Some(a), Some(b)) => {
+            let (mut i, mut j) = (0, 0);
+            let mut positions = vec![];
+            while i < a.len() && j < b.len() {
+                match a[i].cmp(&b[j]) {
+                    Ordering::Less => i += 1,
+                    Ordering::Greater => j += 1,
+                    Ordering::Equal => {
+                        positions.push(a[i]);
+                        i += 1;
+                        j += 1;
+                    }
+                }
+            }
+
+            Some(positions)
+        }
+        (a, None) => a,
+        (None, b) => b,
+    }
+}
+
+/// Cards in any of `candidates`, which is every card when any of them is
+fn union(candidates: impl IntoIterator<Item = Candidates>) -> Candidates {
+    let mut positions = vec![];
+    for candidate in candidates {
+        positions.extend(candidate?);
+    }
+    positions.sort_unstable();
+    positions.dedup();
+
+    Some(positions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        card::{
+            card_side::CardSideInputBuilder, CardProductInputBuilder, CardSetInputBuilder, Document,
+        },
+        graphql::Ctx,
+        product,
+    };
+
+    fn ctx() -> Ctx {
+        let cards: Document = toml::from_str(include_str!("../../data/core-set.toml")).unwrap();
+        let products: product::Document =
+            toml::from_str(include_str!("../../data/products.toml")).unwrap();
+
+        Ctx::new(cards.cards, products.products, vec![])
+    }
+
+    fn side(input: &mut CardSideInputBuilder) -> CardInput {
+        CardInput {
+            aspect: None,
+            products: None,
+            sides: Some(vec![input.build().unwrap()]),
+        }
+    }
+
+    fn product(input: &mut CardProductInputBuilder) -> CardInput {
+        CardInput {
+            aspect: None,
+            products: Some(vec![input.build().unwrap()]),
+            sides: None,
+        }
+    }
+
+    /// Filtering through the index finds exactly the cards a scan does
+    fn assert_plans(ctx: &Ctx, input: &CardInput) {
+        let scanned: Vec<_> = ctx
+            .cards
+            .iter()
+            .filter(|card| card.included(input, ctx))
+            .map(|card| card.code())
+            .collect();
+        let planned: Vec<_> = ctx
+            .filter_cards(input)
+            .unwrap()
+            .into_iter()
+            .map(|card| card.code())
+            .collect();
+
+        assert_eq!(scanned, planned);
+    }
+
+    #[test]
+    fn it_plans_the_same_cards_as_a_scan() {
+        let ctx = ctx();
+        let inputs = [
+            CardInput {
+                aspect: Some(Some(Aspect::Basic)),
+                products: None,
+                sides: None,
+            },
+            CardInput {
+                aspect: Some(Some(Aspect::Justice)),
+                products: None,
+                sides: None,
+            },
+            CardInput {
+                aspect: Some(None),
+                products: None,
+                sides: None,
+            },
+            side(CardSideInputBuilder::default().kind(Some(CardSideKind::Ally))),
+            side(CardSideInputBuilder::default().cost(Some(Some(Cost::Number(2))))),
+            side(CardSideInputBuilder::default().cost(Some(None))),
+            side(
+                CardSideInputBuilder::default()
+                    .kind(Some(CardSideKind::Event))
+                    .traits(Some(Some(vec![String::from("Thwip")]))),
+            ),
+            side(CardSideInputBuilder::default().traits(Some(Some(vec![
+                String::from("S.H.I.E.L.D."),
+                String::from("Avenger"),
+            ])))),
+            product(CardProductInputBuilder::default().code(Some(String::from("MC01en")))),
+            product(CardProductInputBuilder::default().sets(Some(Some(vec![
+                CardSetInputBuilder::default()
+                    .name(Some(String::from("Spider-Man")))
+                    .build()
+                    .unwrap(),
+            ])))),
+            product(CardProductInputBuilder::default().positions(Some(vec![1, 2]))),
+        ];
+
+        for input in inputs.iter() {
+            assert_plans(&ctx, input);
+        }
+    }
+
+    #[test]
+    fn it_narrows_candidates() {
+        let ctx = ctx();
+        let index = CardIndex::new(&ctx.cards, &set_names(&ctx.products));
+        let input = side(CardSideInputBuilder::default().kind(Some(CardSideKind::Hero)));
+
+        let candidates = index.candidates(&Plan::new(&input)).unwrap();
+
+        assert!(!candidates.is_empty());
+        assert!(candidates.len() < ctx.cards.len());
+        assert!(candidates
+            .iter()
+            .all(|&position| ctx.cards[position].is_hero()));
+    }
+
+    #[test]
+    fn it_scans_inputs_without_an_index() {
+        assert_eq!(
+            Plan::Every,
+            Plan::new(&product(
+                CardProductInputBuilder::default().positions(Some(vec![1]))
+            ))
+        );
+        assert_eq!(
+            Plan::Every,
+            Plan::new(&CardInput {
+                aspect: None,
+                products: Some(vec![
+                    CardProductInputBuilder::default()
+                        .code(Some(String::from("MC01en")))
+                        .build()
+                        .unwrap(),
+                    CardProductInputBuilder::default().build().unwrap(),
+                ]),
+                sides: None,
+            })
+        );
+    }
+
+    #[test]
+    fn it_plans_inputs_by_their_keys() {
+        let input = side(
+            CardSideInputBuilder::default()
+                .kind(Some(CardSideKind::Event))
+                .traits(Some(Some(vec![String::from("Thwip"), String::from("Spy")]))),
+        );
+
+        assert_eq!(
+            Plan::All(vec![
+                Plan::Key(Key::Kind(CardSideKind::Event)),
+                Plan::Any(vec![
+                    Plan::Key(Key::Trait(String::from("Thwip"))),
+                    Plan::Key(Key::Trait(String::from("Spy"))),
+                ]),
+            ]),
+            Plan::new(&input)
+        );
+        assert_eq!(
+            Plan::Any(vec![]),
+            Plan::new(&CardInput {
+                aspect: None,
+                products: Some(vec![]),
+                sides: None,
+            })
+        );
+    }
+
+    #[test]
+    fn it_intersects_and_unions_sorted_positions() {
+        assert_eq!(
+            Some(vec![2, 5]),
+            intersect(Some(vec![1, 2, 5, 7]), Some(vec![2, 3, 5]))
+        );
+        assert_eq!(Some(vec![1]), intersect(None, Some(vec![1])));
+        assert_eq!(
+            Some(vec![1, 2, 3]),
+            union([Some(vec![1, 3]), Some(vec![2, 3])])
+        );
+        assert_eq!(None, union([Some(vec![1]), None]));
+    }
+}
@@ -1,12 +1,16 @@
 use juniper::{graphql_scalar, ParseScalarResult, ParseScalarValue, Value};
-use lazy_static::lazy_static;
-use regex::Regex;
 use serde::{
-    de::{self, Deserializer, IntoDeserializer, Unexpected},
-    Deserialize,
+    de::{self, Deserializer},
+    Deserialize, Serialize, Serializer,
 };
+use serde_json::json;
 use std::{fmt, str::FromStr};
+use strum::{EnumIter, IntoEnumIterator, IntoStaticStr};
 use thiserror::Error;
+use utoipa::{
+    openapi::{ObjectBuilder, RefOr, Schema, SchemaType},
+    ToSchema,
+};
 
 #[derive(Debug, Error, PartialEq)]
 pub enum ParseKeywordError {
@@ -14,30 +18,78 @@ pub enum ParseKeywordError {
     UnknownVariant(String),
     #[error("This keyword takes X, which is a natural number: {0}")]
     InvalidValueX(String),
+    #[error("This keyword takes a value in parentheses: {0}")]
+    InvalidParenthetical(String),
+    #[error("This keyword does not take a value: {0}")]
+    UnexpectedValue(String),
 }
 
-#[derive(Clone, Debug, Deserialize, PartialEq)]
-#[serde(remote = "Keyword")]
+/// Keywords are named through strum, so parsing and printing share the variants as one table of
+/// names. Parsing finds the keyword by name with its values defaulted, then fills them in.
+#[derive(Clone, Debug, EnumIter, IntoStaticStr, PartialEq, Eq, Hash)]
 pub enum Keyword {
-    Incite(u8),
+    Assault,
+    Guard,
     Hinder(u8),
+    Incite(u8),
+    Overkill,
+    Patrol,
+    Peril,
+    Permanent,
+    Piercing,
     Quickstrike,
+    Ranged,
+    Restricted,
+    Retaliate(u8),
     Stalwart,
     Steady,
+    Surge,
+    #[strum(serialize = "Team-Up")]
+    TeamUp(String, String),
+    Teamwork(String),
+    Thwart,
     Toughness,
+    /// Number of counters and the kind of counter, i.e. Uses (3 web counters)
+    Uses(u8, String),
+    Victory(u8),
+    Villainous,
 }
 
 impl fmt::Display for Keyword {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let str = match self {
-            Keyword::Incite(n) => format!("Incite {n}"),
-            Keyword::Hinder(n) => format!("Hinder {n}"),
-            Keyword::Quickstrike => String::from("Quickstrike"),
-            Keyword::Stalwart => String::from("Stalwart"),
-            Keyword::Steady => String::from("Steady"),
-            Keyword::Toughness => String::from("Toughness"),
-        };
-        write!(f, "{str}")
+        let name: &str = self.into();
+        match self {
+            Keyword::Hinder(n)
+            | Keyword::Incite(n)
+            | Keyword::Retaliate(n)
+            | Keyword::Victory(n) => {
+                write!(f, "{name} {n}")
+            }
+            Keyword::TeamUp(a, b) => write!(f, "{name} ({a} and {b})"),
+            Keyword::Teamwork(r#trait) => write!(f, "{name} ({trait})"),
+            Keyword::Uses(1, counter) => write!(f, "{name} (1 {counter} counter)"),
+            Keyword::Uses(n, counter) => write!(f, "{name} ({n} {counter} counters)"),
+            _ => write!(f, "{name}"),
+        }
+    }
+}
+
+/// What follows a keyword's name, i.e. the 2 in Incite 2 or the Avenger in Teamwork (Avenger)
+enum Arg<'a> {
+    None,
+    Value(&'a str),
+    Parenthetical(&'a str),
+}
+
+impl<'a> From<Option<&'a str>> for Arg<'a> {
+    fn from(arg: Option<&'a str>) -> Self {
+        match arg {
+            None => Arg::None,
+            Some(arg) => match arg.strip_prefix('(').and_then(|arg| arg.strip_suffix(')')) {
+                Some(text) => Arg::Parenthetical(text.trim()),
+                None => Arg::Value(arg),
+            },
+        }
     }
 }
 
@@ -45,33 +97,79 @@ impl FromStr for Keyword {
     type Err = ParseKeywordError;
 
     fn from_str(value: &str) -> Result<Self, Self::Err> {
-        lazy_static! {
-            static ref KEYWORD_RE: Regex = Regex::new(r"([\w]+) ([\d]+)").unwrap();
-        }
-        if let Some(caps) = KEYWORD_RE.captures(value) {
-            // b/c the regex has been captured, these 2 values should be there
-            let keyword = caps.get(1).unwrap();
-            let x_value = caps
-                .get(2)
-                .unwrap()
-                .as_str()
-                .parse::<u8>()
-                .map_err(|_| ParseKeywordError::InvalidValueX(String::from(value)))?;
-
-            match keyword.as_str() {
-                "Hinder" => Ok(Keyword::Hinder(x_value)),
-                "Incite" => Ok(Keyword::Incite(x_value)),
-                _ => Err(ParseKeywordError::UnknownVariant(String::from(value))),
+        let (name, arg) = match value.trim().split_once(' ') {
+            Some((name, arg)) => (name, Some(arg.trim())),
+            None => (value.trim(), None),
+        };
+        let mut keyword = Keyword::from_name(name)
+            .ok_or_else(|| ParseKeywordError::UnknownVariant(String::from(value)))?;
+        let invalid_x = || ParseKeywordError::InvalidValueX(String::from(value));
+        let invalid_parenthetical = || ParseKeywordError::InvalidParenthetical(String::from(value));
+
+        match (&mut keyword, Arg::from(arg)) {
+            (
+                Keyword::Hinder(n)
+                | Keyword::Incite(n)
+                | Keyword::Retaliate(n)
+                | Keyword::Victory(n),
+                arg,
+            ) => match arg {
+                Arg::Value(x) => *n = x.parse().map_err(|_| invalid_x())?,
+                _ => return Err(invalid_x()),
+            },
+            (Keyword::TeamUp(a, b), Arg::Parenthetical(text)) => {
+                let (first, second) = text.split_once(" and ").ok_or_else(invalid_parenthetical)?;
+                *a = String::from(first);
+                *b = String::from(second);
             }
-        } else {
-            match value {
-                "Quickstrike" => Ok(Keyword::Quickstrike),
-                "Stalwart" => Ok(Keyword::Stalwart),
-                "Steady" => Ok(Keyword::Steady),
-                "Toughness" => Ok(Keyword::Toughness),
-                _ => Err(ParseKeywordError::UnknownVariant(String::from(value))),
+            (Keyword::Teamwork(r#trait), Arg::Parenthetical(text)) if !text.is_empty() => {
+                *r#trait = String::from(text);
+            }
+            (Keyword::Uses(n, counter), Arg::Parenthetical(text)) => {
+                let (count, rest) = text.split_once(' ').ok_or_else(invalid_parenthetical)?;
+                let kind = rest
+                    .strip_suffix(" counters")
+                    .or_else(|| rest.strip_suffix(" counter"))
+                    .filter(|kind| !kind.is_empty())
+                    .ok_or_else(invalid_parenthetical)?;
+                *n = count.parse().map_err(|_| invalid_parenthetical())?;
+                *counter = String::from(kind);
+            }
+            (Keyword::TeamUp(..) | Keyword::Teamwork(_) | Keyword::Uses(..), _) => {
+                return Err(invalid_parenthetical())
             }
+            (_, Arg::None) => {}
+            (_, _) => return Err(ParseKeywordError::UnexpectedValue(String::from(value))),
         }
+
+        Ok(keyword)
+    }
+}
+
+impl Keyword {
+    /// The keyword called `name`, with any values it takes defaulted
+    fn from_name(name: &str) -> Option<Self> {
+        Keyword::iter().find(|keyword| <&str>::from(keyword) == name)
+    }
+}
+
+impl<'s> ToSchema<'s> for Keyword {
+    fn schema() -> (&'s str, RefOr<Schema>) {
+        let schema = ObjectBuilder::new()
+            .schema_type(SchemaType::String)
+            .description(Some("A keyword and its value, if it takes one"))
+            .example(Some(json!("Incite 2")));
+
+        ("Keyword", schema.into())
+    }
+}
+
+impl Serialize for Keyword {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
     }
 }
 
@@ -80,36 +178,9 @@ impl<'de> Deserialize<'de> for Keyword {
     where
         D: Deserializer<'de>,
     {
-        lazy_static! {
-            static ref KEYWORD_RE: Regex = Regex::new(r"([\w]+) ([\d]+)").unwrap();
-        }
         let s = String::deserialize(deserializer)?;
 
-        if let Some(caps) = KEYWORD_RE.captures(&s) {
-            let keyword = caps.get(1).ok_or_else(|| {
-                de::Error::invalid_type(
-                    Unexpected::Str(&s),
-                    &"Keyword expects a word to precede an integer, i.e. Incite 1",
-                )
-            })?;
-            let cap_value = caps.get(2).ok_or_else(|| {
-                de::Error::invalid_value(
-                    Unexpected::Str(&s),
-                    &"Keyword expects an integer to follow, i.e. Incite 1",
-                )
-            })?;
-            let value = cap_value.as_str().parse::<u8>().map_err(|_| {
-                de::Error::invalid_value(Unexpected::Str(&s), &"'X', where X is a number.")
-            })?;
-
-            match keyword.as_str() {
-                "Hinder" => Ok(Keyword::Hinder(value)),
-                "Incite" => Ok(Keyword::Incite(value)),
-                _ => Err(de::Error::unknown_variant(&s, &["Hinder", "Incite"])),
-            }
-        } else {
-            Keyword::deserialize(s.into_deserializer())
-        }
+        <Keyword as FromStr>::from_str(&s).map_err(de::Error::custom)
     }
 }
 
@@ -183,6 +254,99 @@ mod tests {
         assert_eq!(Keyword::Toughness, toml_keyword("Toughness"));
     }
 
+    #[test]
+    fn it_parses_victory() {
+        assert_eq!(Keyword::Victory(1), toml_keyword("Victory 1"));
+    }
+
+    #[test]
+    fn it_parses_retaliate() {
+        assert_eq!(Keyword::Retaliate(1), toml_keyword("Retaliate 1"));
+    }
+
+    #[test]
+    fn it_parses_uses() {
+        assert_eq!(
+            Keyword::Uses(3, String::from("web")),
+            toml_keyword("Uses (3 web counters)")
+        );
+        assert_eq!(
+            Keyword::Uses(1, String::from("charge")),
+            toml_keyword("Uses (1 charge counter)")
+        );
+    }
+
+    #[test]
+    fn it_parses_team_up() {
+        assert_eq!(
+            Keyword::TeamUp(String::from("Spider-Man"), String::from("Black Cat")),
+            toml_keyword("Team-Up (Spider-Man and Black Cat)")
+        );
+    }
+
+    #[test]
+    fn it_parses_teamwork() {
+        assert_eq!(
+            Keyword::Teamwork(String::from("Avenger")),
+            toml_keyword("Teamwork (Avenger)")
+        );
+    }
+
+    #[test]
+    fn it_parses_flags() {
+        for keyword in [
+            Keyword::Assault,
+            Keyword::Guard,
+            Keyword::Overkill,
+            Keyword::Patrol,
+            Keyword::Peril,
+            Keyword::Permanent,
+            Keyword::Piercing,
+            Keyword::Ranged,
+            Keyword::Restricted,
+            Keyword::Surge,
+            Keyword::Thwart,
+            Keyword::Villainous,
+        ] {
+            assert_eq!(keyword, toml_keyword(&keyword.to_string()));
+        }
+    }
+
+    #[test]
+    fn it_round_trips_display() {
+        for keyword in [
+            Keyword::Incite(2),
+            Keyword::Uses(3, String::from("web")),
+            Keyword::Uses(1, String::from("charge")),
+            Keyword::TeamUp(String::from("Spider-Man"), String::from("Black Cat")),
+            Keyword::Teamwork(String::from("Avenger")),
+        ] {
+            assert_eq!(Ok(keyword.clone()), keyword.to_string().parse());
+        }
+    }
+
+    #[test]
+    fn it_rejects_invalid_keywords() {
+        assert_eq!(
+            Err(ParseKeywordError::UnknownVariant(String::from("Flying"))),
+            <Keyword as FromStr>::from_str("Flying")
+        );
+        assert_eq!(
+            Err(ParseKeywordError::InvalidValueX(String::from("Victory"))),
+            <Keyword as FromStr>::from_str("Victory")
+        );
+        assert_eq!(
+            Err(ParseKeywordError::UnexpectedValue(String::from("Guard 1"))),
+            <Keyword as FromStr>::from_str("Guard 1")
+        );
+        assert_eq!(
+            Err(ParseKeywordError::InvalidParenthetical(String::from(
+                "Uses (web counters)"
+            ))),
+            <Keyword as FromStr>::from_str("Uses (web counters)")
+        );
+    }
+
     #[test]
     fn it_parses_quickstrike_from_str() {
         assert_eq!(
@@ -1,32 +1,253 @@
-use juniper::GraphQLEnum;
+use crate::{
+    card::Card,
+    graphql::{Ctx, SHQScalarValue},
+};
+use juniper::graphql_object;
 use serde::Deserialize;
+use thiserror::Error;
 
-#[derive(Clone, Deserialize, GraphQLEnum, Hash, PartialEq, Eq)]
-pub enum Trait {
-    Aerial,
-    Armor,
-    Attack,
-    Attorney,
-    Avenger,
-    Brute,
-    Condition,
-    Criminal,
-    Defense,
-    Elite,
-    Gamma,
-    Genius,
-    #[serde(rename = "Hero for Hire")]
-    HeroForHire,
-    Kree,
-    Item,
-    Location,
-    Persona,
-    Skill,
-    #[serde(rename = "S.H.I.E.L.D.")]
-    Shield,
-    Soldier,
-    Spy,
-    Superpower,
-    Tech,
-    Thwart,
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Document {
+    #[serde(rename = "trait")]
+    pub traits: Vec<Trait>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Trait {
+    pub name: String,
+    pub slug: String,
+}
+
+#[graphql_object(Context = Ctx, scalar = SHQScalarValue)]
+impl Trait {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn slug(&self) -> &str {
+        &self.slug
+    }
+
+    fn cards_count(&self, context: &Ctx) -> i32 {
+        context.trait_cards_count(&self.name) as i32
+    }
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum TraitError {
+    #[error("Trait {name} is defined more than once")]
+    Duplicate { name: String },
+    #[error("Trait slug {slug} is used by more than one trait")]
+    DuplicateSlug { slug: String },
+    #[error(
+        "Unknown trait \"{name}\" on {card}{}",
+        suggestion.as_ref().map(|s| format!(", did you mean \"{s}\"?")).unwrap_or_default()
+    )]
+    Unknown {
+        card: String,
+        name: String,
+        suggestion: Option<String>,
+    },
+    #[error(
+        "Unknown trait \"{name}\"{}",
+        suggestion.as_ref().map(|s| format!(", did you mean \"{s}\"?")).unwrap_or_default()
+    )]
+    Undefined {
+        name: String,
+        suggestion: Option<String>,
+    },
+}
+
+/// Checks that every trait on every card side is defined in the trait registry.
+pub fn validate(cards: &[Card], traits: &[Trait]) -> Result<(), Vec<TraitError>> {
+    let mut errors = Vec::new();
+
+    for (i, r#trait) in traits.iter().enumerate() {
+        if traits[..i].iter().any(|other| other.name == r#trait.name) {
+            errors.push(TraitError::Duplicate {
+                name: r#trait.name.clone(),
+            });
+        } else if traits[..i].iter().any(|other| other.slug == r#trait.slug) {
+            errors.push(TraitError::DuplicateSlug {
+                slug: r#trait.slug.clone(),
+            });
+        }
+    }
+
+    for side in cards.iter().flat_map(|card| card.sides.iter()) {
+        for name in side.traits().into_iter().flatten() {
+            if !traits.iter().any(|r#trait| &r#trait.name == name) {
+                errors.push(TraitError::Unknown {
+                    card: side.name.clone(),
+                    name: name.clone(),
+                    suggestion: suggest(name, traits).map(|r#trait| r#trait.name.clone()),
+                });
+            }
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// Finds the closest trait by edit distance, ignoring case, if it's close enough to be a typo.
+pub(crate) fn suggest<'a>(name: &str, traits: &'a [Trait]) -> Option<&'a Trait> {
+    let name = name.to_lowercase();
+    let max_distance = (name.chars().count() / 3).max(2);
+
+    traits
+        .iter()
+        .map(|r#trait| (levenshtein(&name, &r#trait.name.to_lowercase()), r#trait))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, r#trait)| r#trait)
+}
+
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, a_char) in a.chars().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous + usize::from(a_char != *b_char);
+            previous = row[j + 1];
+            row[j + 1] = substitution.min(previous + 1).min(row[j] + 1);
+        }
+    }
+
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn traits() -> Vec<Trait> {
+        let document: Document = toml::from_str(include_str!("../../data/traits.toml")).unwrap();
+
+        document.traits
+    }
+
+    #[test]
+    fn it_parses_traits() {
+        let traits = traits();
+
+        assert!(traits
+            .iter()
+            .any(|r#trait| r#trait.name == "S.H.I.E.L.D." && r#trait.slug == "shield"));
+    }
+
+    #[test]
+    fn it_suggests_close_traits() {
+        let traits = traits();
+
+        assert_eq!(
+            Some("Avenger"),
+            suggest("Avengr", &traits).map(|r#trait| r#trait.name.as_str())
+        );
+        assert_eq!(
+            Some("Hero for Hire"),
+            suggest("hero for hir", &traits).map(|r#trait| r#trait.name.as_str())
+        );
+        assert_eq!(None, suggest("Mutant", &traits));
+    }
+
+    #[test]
+    fn it_counts_cards_of_every_trait_once() {
+        let cards: crate::card::Document =
+            toml::from_str(include_str!("../../data/core-set.toml")).unwrap();
+        let ctx = Ctx::new(cards.cards, vec![], traits());
+
+        for r#trait in ctx.traits.iter() {
+            let scanned = ctx
+                .cards
+                .iter()
+                .filter(|card| {
+                    card.sides
+                        .iter()
+                        .any(|side| side.traits().is_some_and(|t| t.contains(&r#trait.name)))
+                })
+                .count();
+            assert_eq!(
+                scanned,
+                ctx.trait_cards_count(&r#trait.name),
+                "{}",
+                r#trait.name
+            );
+        }
+        assert!(ctx.trait_cards_count("Avenger") > 0);
+    }
+
+    #[test]
+    fn it_rejects_unknown_traits_in_filters() {
+        use crate::card::{card_side::CardSideInputBuilder, CardInput};
+
+        let ctx = Ctx::new(vec![], vec![], traits());
+        let input = |name: &str| CardInput {
+            aspect: None,
+            products: None,
+            sides: Some(vec![CardSideInputBuilder::default()
+                .traits(Some(Some(vec![String::from(name)])))
+                .build()
+                .unwrap()]),
+        };
+
+        assert_eq!(Ok(()), input("Avenger").check_traits(&ctx));
+        assert_eq!(
+            Err(TraitError::Undefined {
+                name: String::from("Avengr"),
+                suggestion: Some(String::from("Avenger")),
+            }),
+            input("Avengr").check_traits(&ctx)
+        );
+    }
+
+    #[test]
+    fn it_reports_duplicate_traits() {
+        let mut traits = traits();
+        traits.push(traits[0].clone());
+
+        assert_eq!(
+            Err(vec![TraitError::Duplicate {
+                name: traits[0].name.clone()
+            }]),
+            validate(&[], &traits)
+        );
+    }
+
+    #[test]
+    fn it_reports_duplicate_slugs() {
+        let mut traits = traits();
+        let mut copy = traits[0].clone();
+        copy.name = String::from("Something Else");
+        traits.push(copy);
+
+        assert_eq!(
+            Err(vec![TraitError::DuplicateSlug {
+                slug: traits[0].slug.clone()
+            }]),
+            validate(&[], &traits)
+        );
+    }
+
+    #[test]
+    fn it_formats_unknown_trait_errors() {
+        let error = TraitError::Unknown {
+            card: String::from("Spider-Man"),
+            name: String::from("Avengr"),
+            suggestion: Some(String::from("Avenger")),
+        };
+
+        assert_eq!(
+            r#"Unknown trait "Avengr" on Spider-Man, did you mean "Avenger"?"#,
+            error.to_string()
+        );
+    }
 }
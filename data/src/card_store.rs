@@ -0,0 +1,176 @@
+use crate::{
+    card::{Card, Plan},
+    product::Product,
+};
+use std::sync::Arc;
+use thiserror::Error;
+
+mod memory;
+mod sqlite;
+
+pub use memory::MemoryStore;
+pub use sqlite::{SqliteStore, SqliteVersion};
+
+#[derive(Debug, Error)]
+pub enum CardStoreError {
+    #[error("Invalid TOML data: {0}")]
+    Toml(#[from] toml::de::Error),
+    #[error("Database error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+    #[error("Could not convert a stored record: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// Where cards and products are read from. Cards and products are returned in the order they
+/// were added.
+pub trait CardStore: Send + Sync {
+    fn cards(&self) -> Result<Vec<Arc<Card>>, CardStoreError>;
+
+    /// Finds a card by the code of any of its printings, i.e. MC01en-1
+    fn card(&self, code: &str) -> Result<Option<Arc<Card>>, CardStoreError>;
+
+    /// Cards that can match the input `plan` was made from, in order. They're only narrowed down
+    /// by the keys in `plan`, so the caller still checks them against the input.
+    fn candidates(&self, plan: &Plan) -> Result<Vec<Arc<Card>>, CardStoreError>;
+
+    fn products(&self) -> Result<Vec<Arc<Product>>, CardStoreError>;
+
+    fn product(&self, code: &str) -> Result<Option<Arc<Product>>, CardStoreError>;
+}
+
+/// Tests every `CardStore` implementation runs against the embedded data
+#[cfg(test)]
+pub(crate) mod conformance {
+    use super::*;
+    use crate::{
+        card::{self, card_side::CardSideKind, set_names, Aspect, Cost, Key},
+        product,
+    };
+    use serde::Serialize;
+
+    pub fn cards() -> Vec<Card> {
+        let document: card::Document =
+            toml::from_str(include_str!("../data/core-set.toml")).unwrap();
+
+        document.cards
+    }
+
+    pub fn products() -> Vec<Product> {
+        let document: product::Document =
+            toml::from_str(include_str!("../data/products.toml")).unwrap();
+
+        document.products
+    }
+
+    fn json<T: Serialize>(items: impl IntoIterator<Item = T>) -> Vec<serde_json::Value> {
+        items
+            .into_iter()
+            .map(|item| serde_json::to_value(item).unwrap())
+            .collect()
+    }
+
+    /// Runs the suite against the store `new` builds from the cards and products it's given
+    pub fn run<S: CardStore>(new: impl Fn(Vec<Card>, Vec<Product>) -> S) {
+        lists_cards_and_products_in_order(&new);
+        finds_cards_by_printing_code(&new);
+        finds_products_by_code(&new);
+        finds_candidates_by_key(&new);
+        starts_empty(&new);
+    }
+
+    fn lists_cards_and_products_in_order<S: CardStore>(new: impl Fn(Vec<Card>, Vec<Product>) -> S) {
+        let store = new(cards(), products());
+
+        let stored_cards = store.cards().unwrap();
+        let stored_products = store.products().unwrap();
+
+        assert_eq!(json(cards()), json(stored_cards.iter().map(Arc::as_ref)));
+        assert_eq!(
+            json(products()),
+            json(stored_products.iter().map(Arc::as_ref))
+        );
+    }
+
+    fn finds_cards_by_printing_code<S: CardStore>(new: impl Fn(Vec<Card>, Vec<Product>) -> S) {
+        let store = new(cards(), products());
+
+        for card in cards() {
+            for code in card
+                .products
+                .iter()
+                .filter_map(|product| product.card_code())
+            {
+                let found = store.card(&code).unwrap();
+                assert_eq!(json([&card]), json(found.as_deref()), "{code}");
+            }
+        }
+        assert!(store.card("MC01en-999").unwrap().is_none());
+    }
+
+    fn finds_products_by_code<S: CardStore>(new: impl Fn(Vec<Card>, Vec<Product>) -> S) {
+        let store = new(cards(), products());
+
+        let core_set = store.product("MC01en").unwrap().unwrap();
+        assert_eq!("Core Set", core_set.name);
+        assert!(store.product("MC99en").unwrap().is_none());
+    }
+
+    /// Whether a card indexed by `keys` is a candidate for `plan`
+    fn is_candidate(plan: &Plan, keys: &[Key]) -> bool {
+        match plan {
+            Plan::Every => true,
+            Plan::Key(key) => keys.contains(key),
+            Plan::All(plans) => plans.iter().all(|plan| is_candidate(plan, keys)),
+            Plan::Any(plans) => plans.iter().any(|plan| is_candidate(plan, keys)),
+        }
+    }
+
+    fn finds_candidates_by_key<S: CardStore>(new: impl Fn(Vec<Card>, Vec<Product>) -> S) {
+        let store = new(cards(), products());
+        let products = products();
+        let set_names = set_names(&products);
+        let key = |key| Plan::Key(key);
+        let plans = [
+            Plan::Every,
+            key(Key::Kind(CardSideKind::Hero)),
+            key(Key::Aspect(None)),
+            key(Key::Cost(Some(Cost::Number(2)))),
+            key(Key::SetName(String::from("Spider-Man"))),
+            Plan::All(vec![
+                key(Key::Kind(CardSideKind::Ally)),
+                key(Key::Aspect(Some(Aspect::Justice))),
+            ]),
+            Plan::Any(vec![
+                key(Key::Trait(String::from("Avenger"))),
+                Plan::All(vec![key(Key::Product(String::from("MC01en"))), Plan::Every]),
+            ]),
+            Plan::Any(vec![]),
+        ];
+
+        for plan in plans.iter() {
+            let expected: Vec<_> = cards()
+                .into_iter()
+                .filter(|card| is_candidate(plan, &Key::of(card, &set_names)))
+                .collect();
+            let candidates = store.candidates(plan).unwrap();
+
+            assert_eq!(
+                json(&expected),
+                json(candidates.iter().map(Arc::as_ref)),
+                "{plan:?}"
+            );
+        }
+        assert!(!store
+            .candidates(&key(Key::Kind(CardSideKind::Hero)))
+            .unwrap()
+            .is_empty());
+    }
+
+    fn starts_empty<S: CardStore>(new: impl Fn(Vec<Card>, Vec<Product>) -> S) {
+        let store = new(vec![], vec![]);
+
+        assert!(store.cards().unwrap().is_empty());
+        assert!(store.products().unwrap().is_empty());
+        assert!(store.card("MC01en-1").unwrap().is_none());
+    }
+}
@@ -0,0 +1,121 @@
+use super::{CardStore, CardStoreError};
+use crate::{
+    card::{self, set_names, Card, CardIndex, Plan},
+    product::{self, Product},
+};
+use std::{collections::HashMap, sync::Arc};
+
+/// Cards and products held in memory, as loaded from the TOML data
+#[derive(Default)]
+pub struct MemoryStore {
+    cards: Vec<Arc<Card>>,
+    products: Vec<Arc<Product>>,
+    /// Index into `cards` by the code of every printing
+    cards_index: HashMap<String, usize>,
+    products_index: HashMap<String, usize>,
+    index: CardIndex,
+}
+
+impl MemoryStore {
+    pub fn new(cards: Vec<Card>, products: Vec<Product>) -> Self {
+        Self::shared(
+            cards.into_iter().map(Arc::new).collect(),
+            products.into_iter().map(Arc::new).collect(),
+        )
+    }
+
+    /// Builds the store from cards and products that are already shared, i.e. with a `Ctx`
+    pub fn shared(cards: Vec<Arc<Card>>, products: Vec<Arc<Product>>) -> Self {
+        let cards_index = cards
+            .iter()
+            .enumerate()
+            .flat_map(|(index, card)| {
+                card.products
+                    .iter()
+                    .filter_map(move |product| Some((product.card_code()?, index)))
+            })
+            .collect();
+        let products_index = products
+            .iter()
+            .enumerate()
+            .map(|(index, product)| (product.code.clone(), index))
+            .collect();
+        let index = CardIndex::new(&cards, &set_names(&products));
+
+        Self {
+            cards,
+            products,
+            cards_index,
+            products_index,
+            index,
+        }
+    }
+
+    /// Builds the store from card files and a products file
+    pub fn from_toml(cards: &[&str], products: &str) -> Result<Self, CardStoreError> {
+        let mut all_cards = vec![];
+        for source in cards {
+            all_cards.extend(toml::from_str::<card::Document>(source)?.cards);
+        }
+        let products = toml::from_str::<product::Document>(products)?.products;
+
+        Ok(Self::new(all_cards, products))
+    }
+}
+
+impl CardStore for MemoryStore {
+    fn cards(&self) -> Result<Vec<Arc<Card>>, CardStoreError> {
+        Ok(self.cards.clone())
+    }
+
+    fn card(&self, code: &str) -> Result<Option<Arc<Card>>, CardStoreError> {
+        Ok(self
+            .cards_index
+            .get(code)
+            .map(|&index| Arc::clone(&self.cards[index])))
+    }
+
+    fn candidates(&self, plan: &Plan) -> Result<Vec<Arc<Card>>, CardStoreError> {
+        Ok(match self.index.candidates(plan) {
+            Some(positions) => positions
+                .into_iter()
+                .map(|position| Arc::clone(&self.cards[position]))
+                .collect(),
+            None => self.cards.clone(),
+        })
+    }
+
+    fn products(&self) -> Result<Vec<Arc<Product>>, CardStoreError> {
+        Ok(self.products.clone())
+    }
+
+    fn product(&self, code: &str) -> Result<Option<Arc<Product>>, CardStoreError> {
+        Ok(self
+            .products_index
+            .get(code)
+            .map(|&index| Arc::clone(&self.products[index])))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::card_store::conformance;
+
+    #[test]
+    fn it_conforms() {
+        conformance::run(MemoryStore::new);
+    }
+
+    #[test]
+    fn it_loads_from_toml() {
+        let store = MemoryStore::from_toml(
+            &[include_str!("../../data/core-set.toml")],
+            include_str!("../../data/products.toml"),
+        )
+        .unwrap();
+
+        assert_eq!(conformance::cards().len(), store.cards().unwrap().len());
+        assert!(MemoryStore::from_toml(&["[[card]]"], "").is_err());
+    }
+}
@@ -0,0 +1,385 @@
+use super::{CardStore, CardStoreError};
+use crate::{
+    card::{set_names, Card, Key, Plan},
+    product::Product,
+};
+use rusqlite::{params, params_from_iter, types::Value, Connection};
+use serde::de::DeserializeOwned;
+use std::{
+    borrow::Borrow,
+    collections::HashSet,
+    path::Path,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+/// Layout of the tables below, kept in the database's `user_version`
+const SCHEMA_VERSION: i64 = 1;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS versions (
+    version INTEGER PRIMARY KEY
+);
+CREATE TABLE IF NOT EXISTS products (
+    version INTEGER NOT NULL,
+    position INTEGER NOT NULL,
+    code TEXT NOT NULL,
+    data TEXT NOT NULL,
+    PRIMARY KEY (version, position),
+    UNIQUE (version, code)
+);
+CREATE TABLE IF NOT EXISTS cards (
+    version INTEGER NOT NULL,
+    position INTEGER NOT NULL,
+    data TEXT NOT NULL,
+    PRIMARY KEY (version, position)
+);
+CREATE TABLE IF NOT EXISTS card_codes (
+    version INTEGER NOT NULL,
+    code TEXT NOT NULL,
+    card INTEGER NOT NULL,
+    PRIMARY KEY (version, code)
+);
+CREATE TABLE IF NOT EXISTS card_keys (
+    version INTEGER NOT NULL,
+    key TEXT NOT NULL,
+    value TEXT NOT NULL,
+    card INTEGER NOT NULL,
+    PRIMARY KEY (version, key, value, card)
+);
+";
+
+/// Tables holding a version of the data, in the order its rows are deleted
+const TABLES: [&str; 5] = ["card_keys", "card_codes", "cards", "products", "versions"];
+
+struct Database {
+    connection: Connection,
+    /// Versions served by a `SqliteVersion` that hasn't been dropped
+    live: HashSet<i64>,
+}
+
+impl Database {
+    /// Deletes every version nothing serves anymore, except the latest, which is kept so the
+    /// database holds the last data it was given
+    fn sweep(&mut self) -> Result<(), CardStoreError> {
+        let versions: Vec<i64> = self
+            .connection
+            .prepare_cached("SELECT version FROM versions ORDER BY version")?
+            .query_map([], |row| row.get(0))?
+            .collect::<Result<_, _>>()?;
+        let transaction = self.connection.transaction()?;
+        for version in versions.iter().rev().skip(1) {
+            if !self.live.contains(version) {
+                for table in TABLES {
+                    transaction.execute(
+                        &format!("DELETE FROM {table} WHERE version = ?1"),
+                        [version],
+                    )?;
+                }
+            }
+        }
+
+        Ok(transaction.commit()?)
+    }
+}
+
+/// Cards and products in an embedded SQLite database, stored as JSON and looked up by code and
+/// by the keys cards are filtered on. Every import adds a version of the data, so the data a
+/// snapshot reads doesn't change under it when the next one is imported.
+pub struct SqliteStore {
+    database: Arc<Mutex<Database>>,
+}
+
+impl SqliteStore {
+    /// Opens the database at `path`, creating it when it doesn't exist
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, CardStoreError> {
+        Self::with_connection(Connection::open(path)?)
+    }
+
+    pub fn in_memory() -> Result<Self, CardStoreError> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(connection: Connection) -> Result<Self, CardStoreError> {
+        let schema_version: i64 =
+            connection.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        if schema_version != SCHEMA_VERSION {
+            // the database only holds data imported from the data files, so tables in another
+            // layout are dropped rather than migrated
+            for table in TABLES {
+                connection.execute_batch(&format!("DROP TABLE IF EXISTS {table};"))?;
+            }
+            connection.execute_batch(&format!("PRAGMA user_version = {SCHEMA_VERSION};"))?;
+        }
+        connection.execute_batch(SCHEMA)?;
+        let mut database = Database {
+            connection,
+            live: HashSet::new(),
+        };
+        // versions left by an earlier run aren't served by anything
+        database.sweep()?;
+
+        Ok(Self {
+            database: Arc::new(Mutex::new(database)),
+        })
+    }
+
+    /// Imports `cards` and `products` as a new version of the data, served by the returned store.
+    /// Versions imported earlier are left as they are until their stores are dropped.
+    pub fn import(
+        &self,
+        cards: &[impl Borrow<Card>],
+        products: &[impl Borrow<Product>],
+    ) -> Result<SqliteVersion, CardStoreError> {
+        let mut database = self.database.lock().unwrap_or_else(PoisonError::into_inner);
+        let transaction = database.connection.transaction()?;
+        transaction.execute("INSERT INTO versions DEFAULT VALUES", [])?;
+        let version = transaction.last_insert_rowid();
+        {
+            let mut insert_product = transaction.prepare(
+                "INSERT OR REPLACE INTO products (version, position, code, data)
+                VALUES (?1, ?2, ?3, ?4)",
+            )?;
+            for (position, product) in products.iter().map(Borrow::borrow).enumerate() {
+                insert_product.execute(params![
+                    version,
+                    position,
+                    product.code,
+                    serde_json::to_string(product)?
+                ])?;
+            }
+
+            let set_names = set_names(products);
+            let mut insert_card = transaction
+                .prepare("INSERT INTO cards (version, position, data) VALUES (?1, ?2, ?3)")?;
+            // like the in-memory index, a later card with the same code wins
+            let mut insert_code = transaction.prepare(
+                "INSERT OR REPLACE INTO card_codes (version, code, card) VALUES (?1, ?2, ?3)",
+            )?;
+            let mut insert_key = transaction.prepare(
+                "INSERT OR IGNORE INTO card_keys (version, key, value, card) VALUES (?1, ?2, ?3, ?4)",
+            )?;
+            for (position, card) in cards.iter().map(Borrow::borrow).enumerate() {
+                insert_card.execute(params![version, position, serde_json::to_string(card)?])?;
+                for code in card
+                    .products
+                    .iter()
+                    .filter_map(|product| product.card_code())
+                {
+                    insert_code.execute(params![version, code, position])?;
+                }
+                for key in Key::of(card, &set_names) {
+                    let (name, value) = key.column();
+                    insert_key.execute(params![version, name, value, position])?;
+                }
+            }
+        }
+        transaction.commit()?;
+        database.live.insert(version);
+        database.sweep()?;
+
+        Ok(SqliteVersion {
+            database: Arc::clone(&self.database),
+            version,
+        })
+    }
+}
+
+/// A version of the data in a `SqliteStore`. Its rows are deleted once it's dropped and a later
+/// version was imported.
+pub struct SqliteVersion {
+    database: Arc<Mutex<Database>>,
+    version: i64,
+}
+
+impl SqliteVersion {
+    fn query<T: DeserializeOwned>(
+        &self,
+        sql: &str,
+        values: Vec<Value>,
+    ) -> Result<Vec<Arc<T>>, CardStoreError> {
+        let database = self.database.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut statement = database.connection.prepare_cached(sql)?;
+        let rows = statement.query_map(params_from_iter(values), |row| row.get::<_, String>(0))?;
+
+        rows.map(|data| Ok(Arc::new(serde_json::from_str(&data?)?)))
+            .collect()
+    }
+
+    /// Values for a query of this version by `code`
+    fn code(&self, code: &str) -> Vec<Value> {
+        vec![
+            Value::Integer(self.version),
+            Value::Text(String::from(code)),
+        ]
+    }
+}
+
+impl CardStore for SqliteVersion {
+    fn cards(&self) -> Result<Vec<Arc<Card>>, CardStoreError> {
+        self.query(
+            "SELECT data FROM cards WHERE version = ?1 ORDER BY position",
+            vec![Value::Integer(self.version)],
+        )
+    }
+
+    fn card(&self, code: &str) -> Result<Option<Arc<Card>>, CardStoreError> {
+        let mut cards = self.query(
+            "SELECT cards.data FROM card_codes JOIN cards
+                ON cards.version = card_codes.version AND cards.position = card_codes.card
+            WHERE card_codes.version = ?1 AND card_codes.code = ?2",
+            self.code(code),
+        )?;
+
+        Ok(cards.pop())
+    }
+
+    fn candidates(&self, plan: &Plan) -> Result<Vec<Arc<Card>>, CardStoreError> {
+        let mut values = vec![Value::Integer(self.version)];
+        let condition = condition(plan, &mut values);
+
+        self.query(
+            &format!("SELECT data FROM cards WHERE version = ?1 AND {condition} ORDER BY position"),
+            values,
+        )
+    }
+
+    fn products(&self) -> Result<Vec<Arc<Product>>, CardStoreError> {
+        self.query(
+            "SELECT data FROM products WHERE version = ?1 ORDER BY position",
+            vec![Value::Integer(self.version)],
+        )
+    }
+
+    fn product(&self, code: &str) -> Result<Option<Arc<Product>>, CardStoreError> {
+        let mut products = self.query(
+            "SELECT data FROM products WHERE version = ?1 AND code = ?2",
+            self.code(code),
+        )?;
+
+        Ok(products.pop())
+    }
+}
+
+impl Drop for SqliteVersion {
+    fn drop(&mut self) {
+        let mut database = self.database.lock().unwrap_or_else(PoisonError::into_inner);
+        database.live.remove(&self.version);
+        // rows that can't be deleted now are deleted by the next import or when the database is
+        // opened again
+        let _ = database.sweep();
+    }
+}
+
+/// SQL condition on `cards` for the cards matching `plan`, adding the values it binds to `values`
+/// after the version, which is always the first
+fn condition(plan: &Plan, values: &mut Vec<Value>) -> String {
+    let join = |plans: &[Plan], separator: &str, empty: &str, values: &mut Vec<Value>| {
+        if plans.is_empty() {
+            return String::from(empty);
+        }
+        let conditions: Vec<String> = plans.iter().map(|plan| condition(plan, values)).collect();
+
+        format!("({})", conditions.join(separator))
+    };
+
+    match plan {
+        Plan::Every => String::from("1"),
+        Plan::Key(key) => {
+            let (name, value) = key.column();
+            values.push(Value::Text(String::from(name)));
+            values.push(Value::Text(value));
+
+            format!(
+                "position IN (SELECT card FROM card_keys WHERE version = ?1 AND key = ?{} AND value = ?{})",
+                values.len() - 1,
+                values.len()
+            )
+        }
+        Plan::All(plans) => join(plans, " AND ", "1", values),
+        Plan::Any(plans) => join(plans, " OR ", "0", values),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::card_store::conformance;
+
+    fn versions(store: &SqliteStore) -> Vec<i64> {
+        let database = store.database.lock().unwrap();
+        let mut statement = database
+            .connection
+            .prepare("SELECT DISTINCT version FROM cards ORDER BY version")
+            .unwrap();
+        let versions = statement.query_map([], |row| row.get(0)).unwrap();
+
+        versions.collect::<Result<_, _>>().unwrap()
+    }
+
+    #[test]
+    fn it_conforms() {
+        conformance::run(|cards, products| {
+            SqliteStore::in_memory()
+                .unwrap()
+                .import(&cards, &products)
+                .unwrap()
+        });
+    }
+
+    #[test]
+    fn it_keeps_a_version_until_it_is_dropped() {
+        let store = SqliteStore::in_memory().unwrap();
+        let first = store
+            .import(&conformance::cards(), &conformance::products())
+            .unwrap();
+        let second = store
+            .import(&conformance::cards()[..1], &conformance::products())
+            .unwrap();
+
+        // importing doesn't change the data an earlier version serves
+        assert_eq!(conformance::cards().len(), first.cards().unwrap().len());
+        assert!(first.card("MC01en-2").unwrap().is_some());
+        assert_eq!(1, second.cards().unwrap().len());
+        assert!(second.card("MC01en-2").unwrap().is_none());
+        assert_eq!(vec![first.version, second.version], versions(&store));
+
+        drop(first);
+        assert_eq!(vec![second.version], versions(&store));
+    }
+
+    #[test]
+    fn it_persists_the_latest_version_to_a_file() {
+        let path = std::env::temp_dir().join(format!("shq-cards-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let version = SqliteStore::open(&path)
+            .unwrap()
+            .import(&conformance::cards(), &conformance::products())
+            .unwrap()
+            .version;
+
+        let store = SqliteStore::open(&path).unwrap();
+        let cards = SqliteVersion {
+            database: Arc::clone(&store.database),
+            version,
+        }
+        .cards()
+        .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(conformance::cards().len(), cards.len());
+    }
+
+    #[test]
+    fn it_drops_tables_of_another_layout() {
+        let connection = Connection::open_in_memory().unwrap();
+        connection
+            .execute_batch("CREATE TABLE cards (position INTEGER PRIMARY KEY, data TEXT NOT NULL);")
+            .unwrap();
+
+        let store = SqliteStore::with_connection(connection).unwrap();
+
+        assert!(store
+            .import(&conformance::cards(), &conformance::products())
+            .is_ok());
+    }
+}
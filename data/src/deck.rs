@@ -0,0 +1,161 @@
+use crate::{
+    card::Card,
+    graphql::{Ctx, SHQScalarValue},
+};
+use juniper::GraphQLInputObject;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+pub mod odds;
+mod stats;
+
+pub use odds::{draw_odds, DrawOdds, DrawTargets, OddsError, Probability};
+pub use stats::{stats, AspectStats, CostCount, DeckStats, KindCount, ResourceCount, TraitCount};
+
+/// Most copies of a card a deck can have. The game allows 3, with room for house rules.
+pub const MAX_COPIES: i32 = 4;
+/// Most cards a deck can have. The game allows 50, with room for house rules.
+pub const MAX_CARDS: u32 = 60;
+
+/// A card in a deck list, by the code of any of its printings, i.e. MC01en-2
+#[derive(Clone, Debug, Deserialize, GraphQLInputObject, PartialEq, Serialize)]
+#[graphql(scalar = SHQScalarValue)]
+pub struct DeckCard {
+    pub code: String,
+    pub quantity: i32,
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum DeckError {
+    #[error("Could not find card {0}")]
+    UnknownCard(String),
+    #[error("{code} has a quantity of {quantity}, which must be between 1 and {MAX_COPIES}")]
+    Quantity { code: String, quantity: i32 },
+    #[error("The deck has {0} cards, which is more than {MAX_CARDS}")]
+    TooManyCards(u32),
+}
+
+/// A deck list with its cards looked up
+pub struct Deck<'a> {
+    pub cards: Vec<(&'a Card, u32)>,
+}
+
+impl<'a> Deck<'a> {
+    pub fn new(cards: &[DeckCard], context: &'a Ctx) -> Result<Self, DeckError> {
+        let cards = cards
+            .iter()
+            .map(|deck_card| {
+                let card = context
+                    .card(&deck_card.code)
+                    .ok_or_else(|| DeckError::UnknownCard(deck_card.code.clone()))?;
+                if !(1..=MAX_COPIES).contains(&deck_card.quantity) {
+                    return Err(DeckError::Quantity {
+                        code: deck_card.code.clone(),
+                        quantity: deck_card.quantity,
+                    });
+                }
+
+                Ok((card, deck_card.quantity as u32))
+            })
+            .collect::<Result<_, _>>()?;
+        let deck = Self { cards };
+        if deck.len() > MAX_CARDS {
+            return Err(DeckError::TooManyCards(deck.len()));
+        }
+
+        Ok(deck)
+    }
+
+    /// Every copy of every card in the deck
+    pub fn copies(&self) -> impl Iterator<Item = &'a Card> + '_ {
+        self.cards
+            .iter()
+            .flat_map(|&(card, quantity)| std::iter::repeat_n(card, quantity as usize))
+    }
+
+    pub fn len(&self) -> u32 {
+        self.cards.iter().map(|(_, quantity)| quantity).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cards.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::card;
+
+    fn ctx() -> Ctx {
+        let document: card::Document =
+            toml::from_str(include_str!("../data/core-set.toml")).unwrap();
+
+        Ctx::new(document.cards, vec![], vec![])
+    }
+
+    fn deck_card(code: &str, quantity: i32) -> DeckCard {
+        DeckCard {
+            code: String::from(code),
+            quantity,
+        }
+    }
+
+    #[test]
+    fn it_looks_up_deck_cards() {
+        let ctx = ctx();
+        let deck = Deck::new(&[deck_card("MC01en-2", 1), deck_card("MC01en-4", 2)], &ctx).unwrap();
+
+        assert_eq!(3, deck.len());
+        let names: Vec<&str> = deck
+            .copies()
+            .map(|card| card.sides[0].name.as_str())
+            .collect();
+        assert_eq!(
+            vec![
+                "Black Cat",
+                "Enhanced Spider-Sense",
+                "Enhanced Spider-Sense"
+            ],
+            names
+        );
+    }
+
+    #[test]
+    fn it_rejects_unknown_cards_and_quantities() {
+        let ctx = ctx();
+
+        assert_eq!(
+            Some(DeckError::UnknownCard(String::from("MC01en-999"))),
+            Deck::new(&[deck_card("MC01en-999", 1)], &ctx).err()
+        );
+        assert_eq!(
+            Some(DeckError::Quantity {
+                code: String::from("MC01en-2"),
+                quantity: 0
+            }),
+            Deck::new(&[deck_card("MC01en-2", 0)], &ctx).err()
+        );
+        assert_eq!(
+            Some(DeckError::Quantity {
+                code: String::from("MC01en-2"),
+                quantity: MAX_COPIES + 1
+            }),
+            Deck::new(&[deck_card("MC01en-2", MAX_COPIES + 1)], &ctx).err()
+        );
+    }
+
+    #[test]
+    fn it_rejects_decks_that_are_too_large() {
+        let ctx = ctx();
+        let cards: Vec<DeckCard> = (2..=17)
+            .map(|position| deck_card(&format!("MC01en-{position}"), MAX_COPIES))
+            .collect();
+
+        assert_eq!(
+            Some(DeckError::TooManyCards(64)),
+            Deck::new(&cards, &ctx).err()
+        );
+        assert!(Deck::new(&cards[..15], &ctx).is_ok());
+    }
+}
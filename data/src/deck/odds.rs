@@ -0,0 +1,328 @@
+use crate::{
+    card::{card_side::Side, Card},
+    deck::{Deck, DeckError},
+    graphql::{Ctx, SHQScalarValue},
+};
+use juniper::{graphql_object, GraphQLInputObject, GraphQLObject};
+use std::collections::HashSet;
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum OddsError {
+    #[error(transparent)]
+    Deck(#[from] DeckError),
+    #[error("Could not find target card {0}")]
+    UnknownTarget(String),
+    #[error("{0} is not a hero")]
+    NotAHero(String),
+    #[error("{name} must be at least 1, got {value}")]
+    NotPositive { name: &'static str, value: i32 },
+    #[error("The deck is too large to compute exact odds")]
+    Overflow,
+}
+
+/// An exact probability, always in lowest terms
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Probability {
+    numerator: u128,
+    denominator: u128,
+}
+
+impl Probability {
+    pub fn new(numerator: u128, denominator: u128) -> Self {
+        let divisor = gcd(numerator, denominator).max(1);
+
+        Self {
+            numerator: numerator / divisor,
+            denominator: denominator / divisor,
+        }
+    }
+
+    pub fn numerator(&self) -> u128 {
+        self.numerator
+    }
+
+    pub fn denominator(&self) -> u128 {
+        self.denominator
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        self.numerator as f64 / self.denominator as f64
+    }
+}
+
+#[graphql_object(scalar = SHQScalarValue)]
+impl Probability {
+    /// Numerator of the exact fraction, as a string since it can exceed an Int
+    fn numerator(&self) -> String {
+        self.numerator.to_string()
+    }
+
+    fn denominator(&self) -> String {
+        self.denominator.to_string()
+    }
+
+    fn value(&self) -> f64 {
+        self.to_f64()
+    }
+}
+
+fn gcd(mut a: u128, mut b: u128) -> u128 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+
+    a
+}
+
+/// Ways to choose `k` items from `n`, or `None` when it doesn't fit in a u128
+fn binomial(n: u32, k: u32) -> Option<u128> {
+    if k > n {
+        return Some(0);
+    }
+
+    let k = k.min(n - k);
+    (0..k).try_fold(1u128, |result, i| {
+        // divides exactly, since the result is the binomial of n and i + 1
+        Some(result.checked_mul(u128::from(n - i))? / u128::from(i + 1))
+    })
+}
+
+/// Chance of drawing at least `at_least` of the `successes` cards in a deck of `population`
+/// when drawing `draws` cards, following the hypergeometric distribution
+pub fn at_least(population: u32, successes: u32, draws: u32, at_least: u32) -> Option<Probability> {
+    let successes = successes.min(population);
+    let draws = draws.min(population);
+    let total = binomial(population, draws)?;
+
+    // ways to draw fewer than `at_least` successes
+    let misses = (0..at_least.min(draws + 1)).try_fold(0u128, |misses, drawn| {
+        let ways = binomial(successes, drawn)?
+            .checked_mul(binomial(population - successes, draws - drawn)?)?;
+        misses.checked_add(ways)
+    })?;
+
+    Some(Probability::new(total - misses, total))
+}
+
+/// Cards to look for, matching any of the codes or traits
+#[derive(Clone, Debug, Default, GraphQLInputObject)]
+#[graphql(scalar = SHQScalarValue)]
+pub struct DrawTargets {
+    /// Codes of any printing of the cards
+    pub codes: Option<Vec<String>>,
+    pub traits: Option<Vec<String>>,
+}
+
+#[derive(Clone, Debug, GraphQLObject, PartialEq)]
+#[graphql(scalar = SHQScalarValue)]
+pub struct DrawOdds {
+    pub turn: i32,
+    /// Cards seen by the end of the turn's draw
+    pub cards_drawn: i32,
+    pub probability: Probability,
+}
+
+/// Hand size of the hero with the card code `hero` in `form`
+pub fn hand_size(context: &Ctx, hero: &str, form: &Side) -> Result<u32, OddsError> {
+    context
+        .card(hero)
+        .filter(|card| card.is_hero())
+        .and_then(|card| card.hand_size(form))
+        .ok_or_else(|| OddsError::NotAHero(String::from(hero)))
+}
+
+/// Odds of having drawn at least `copies` cards matching `targets` by each of the first `turns`
+/// turns. The hand is assumed to be played out every turn, so each turn draws a full hand of
+/// `hand_size` cards. Turns after the one that draws the last card of the deck are left out.
+pub fn draw_odds(
+    deck: &Deck,
+    hand_size: u32,
+    targets: &DrawTargets,
+    copies: u32,
+    turns: u32,
+    context: &Ctx,
+) -> Result<Vec<DrawOdds>, OddsError> {
+    let mut identities = HashSet::new();
+    for code in targets.codes.iter().flatten() {
+        let card = context
+            .card(code)
+            .ok_or_else(|| OddsError::UnknownTarget(code.clone()))?;
+        identities.insert(context.identity(card));
+    }
+    let traits: HashSet<&String> = targets.traits.iter().flatten().collect();
+    let matches = |card: &Card| {
+        identities.contains(&context.identity(card))
+            || card
+                .sides
+                .first()
+                .and_then(|side| side.traits())
+                .is_some_and(|card_traits| card_traits.iter().any(|name| traits.contains(name)))
+    };
+
+    let population = deck.len();
+    let successes = deck.copies().filter(|card| matches(card)).count() as u32;
+    let last_turn = population.div_ceil(hand_size.max(1)).max(1);
+    (1..=turns.min(last_turn))
+        .map(|turn| {
+            let draws = hand_size.saturating_mul(turn).min(population);
+            let probability =
+                at_least(population, successes, draws, copies).ok_or(OddsError::Overflow)?;
+
+            Ok(DrawOdds {
+                turn: turn as i32,
+                cards_drawn: draws as i32,
+                probability,
+            })
+        })
+        .collect()
+}
+
+/// Converts a GraphQL argument that has to be positive
+pub fn positive(name: &'static str, value: i32) -> Result<u32, OddsError> {
+    u32::try_from(value)
+        .ok()
+        .filter(|&value| value > 0)
+        .ok_or(OddsError::NotPositive { name, value })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{card, deck::DeckCard};
+
+    fn ctx() -> Ctx {
+        let document: card::Document =
+            toml::from_str(include_str!("../../data/core-set.toml")).unwrap();
+
+        Ctx::new(document.cards, vec![], vec![])
+    }
+
+    /// Spider-Man's signature cards and two Mockingbirds, 10 cards in all
+    fn deck(ctx: &Ctx) -> Deck<'_> {
+        let mut cards: Vec<DeckCard> = (2..=9)
+            .map(|position| DeckCard {
+                code: format!("MC01en-{position}"),
+                quantity: 1,
+            })
+            .collect();
+        cards.push(DeckCard {
+            code: String::from("MC01en-83"),
+            quantity: 2,
+        });
+
+        Deck::new(&cards, ctx).unwrap()
+    }
+
+    #[test]
+    fn it_computes_hypergeometric_odds() {
+        // 3 copies in a 40 card deck
+        assert_eq!(Some(Probability::new(667, 1976)), at_least(40, 3, 5, 1));
+        assert_eq!(Some(Probability::new(487, 1235)), at_least(40, 3, 6, 1));
+        assert_eq!(Some(Probability::new(127, 190)), at_least(40, 3, 12, 1));
+        assert_eq!(Some(Probability::new(9, 247)), at_least(40, 3, 5, 2));
+        assert_eq!(Some(Probability::new(1, 1)), at_least(40, 1, 40, 1));
+        assert_eq!(Some(Probability::new(0, 1)), at_least(5, 0, 5, 1));
+        assert_eq!(Some(Probability::new(0, 1)), at_least(40, 3, 5, 4));
+        assert_eq!(Some(Probability::new(1, 1)), at_least(40, 3, 5, 0));
+    }
+
+    #[test]
+    fn it_reduces_probabilities() {
+        let probability = Probability::new(6, 10);
+
+        assert_eq!(3, probability.numerator());
+        assert_eq!(5, probability.denominator());
+        assert_eq!(0.6, probability.to_f64());
+    }
+
+    #[test]
+    fn it_reports_overflow() {
+        assert_eq!(None, at_least(1000, 10, 500, 1));
+    }
+
+    #[test]
+    fn it_uses_the_hand_size_of_the_form() {
+        let ctx = ctx();
+
+        assert_eq!(Ok(5), hand_size(&ctx, "MC01en-1", &Side::A));
+        assert_eq!(Ok(6), hand_size(&ctx, "MC01en-1", &Side::B));
+        assert_eq!(
+            Err(OddsError::NotAHero(String::from("MC01en-2"))),
+            hand_size(&ctx, "MC01en-2", &Side::A)
+        );
+    }
+
+    #[test]
+    fn it_computes_draw_odds_for_a_deck() {
+        let ctx = ctx();
+        let deck = deck(&ctx);
+        let black_cat = DrawTargets {
+            codes: Some(vec![String::from("MC01en-2")]),
+            traits: None,
+        };
+
+        let odds = draw_odds(&deck, 6, &black_cat, 1, 2, &ctx).unwrap();
+        assert_eq!(
+            vec![
+                DrawOdds {
+                    turn: 1,
+                    cards_drawn: 6,
+                    probability: Probability::new(3, 5)
+                },
+                DrawOdds {
+                    turn: 2,
+                    cards_drawn: 10,
+                    probability: Probability::new(1, 1)
+                },
+            ],
+            odds
+        );
+    }
+
+    #[test]
+    fn it_stops_at_the_turn_that_draws_the_deck() {
+        let ctx = ctx();
+        let deck = deck(&ctx);
+        let black_cat = DrawTargets {
+            codes: Some(vec![String::from("MC01en-2")]),
+            traits: None,
+        };
+
+        let odds = draw_odds(&deck, 6, &black_cat, 1, i32::MAX as u32, &ctx).unwrap();
+        assert_eq!(2, odds.len());
+        assert_eq!(10, odds[1].cards_drawn);
+        let odds = draw_odds(&deck, 5, &black_cat, 1, 3, &ctx).unwrap();
+        assert_eq!(2, odds.len());
+    }
+
+    #[test]
+    fn it_matches_targets_by_trait() {
+        let ctx = ctx();
+        let deck = deck(&ctx);
+        // Spider-Tracer and Web-Shooter
+        let tech = DrawTargets {
+            codes: None,
+            traits: Some(vec![String::from("Tech")]),
+        };
+
+        let odds = draw_odds(&deck, 5, &tech, 1, 1, &ctx).unwrap();
+        assert_eq!(Probability::new(7, 9), odds[0].probability);
+        let odds = draw_odds(&deck, 5, &tech, 2, 1, &ctx).unwrap();
+        assert_eq!(Probability::new(2, 9), odds[0].probability);
+    }
+
+    #[test]
+    fn it_rejects_unknown_targets() {
+        let ctx = ctx();
+        let targets = DrawTargets {
+            codes: Some(vec![String::from("MC01en-999")]),
+            traits: None,
+        };
+
+        assert_eq!(
+            Err(OddsError::UnknownTarget(String::from("MC01en-999"))),
+            draw_odds(&deck(&ctx), 5, &targets, 1, 1, &ctx)
+        );
+    }
+}
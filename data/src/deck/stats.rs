@@ -0,0 +1,321 @@
+use crate::{
+    card::{card_side::CardSideKind, Aspect, BasicPower, Card, Cost, Resource},
+    deck::Deck,
+    graphql::SHQScalarValue,
+};
+use juniper::GraphQLObject;
+use std::collections::BTreeMap;
+
+const RESOURCES: [Resource; 4] = [
+    Resource::Energy,
+    Resource::Mental,
+    Resource::Physical,
+    Resource::Wild,
+];
+
+#[derive(Clone, Debug, Default, GraphQLObject, PartialEq)]
+#[graphql(scalar = SHQScalarValue)]
+pub struct DeckStats {
+    pub card_count: i32,
+    /// Copies by printed cost, lowest first. X costs are counted in `x_cost_count` instead.
+    pub cost_curve: Vec<CostCount>,
+    pub x_cost_count: i32,
+    /// Average printed cost of the cards with a numeric cost
+    pub average_cost: Option<f64>,
+    /// Resources generated when discarding every card, including wild
+    pub resources: Vec<ResourceCount>,
+    /// Copies by trait, most common first
+    pub traits: Vec<TraitCount>,
+    pub kinds: Vec<KindCount>,
+    /// Total ATK of the allies in the deck
+    pub ally_atk: i32,
+    /// Total THW of the allies in the deck
+    pub ally_thw: i32,
+    /// The same statistics for the cards of each aspect. Cards without an aspect belong to the
+    /// hero.
+    pub aspects: Vec<AspectStats>,
+}
+
+#[derive(Clone, Debug, GraphQLObject, PartialEq)]
+#[graphql(scalar = SHQScalarValue)]
+pub struct CostCount {
+    pub cost: i32,
+    pub count: i32,
+}
+
+#[derive(Clone, Debug, GraphQLObject, PartialEq)]
+#[graphql(scalar = SHQScalarValue)]
+pub struct ResourceCount {
+    pub resource: Resource,
+    pub count: i32,
+}
+
+#[derive(Clone, Debug, GraphQLObject, PartialEq)]
+#[graphql(scalar = SHQScalarValue)]
+pub struct TraitCount {
+    pub name: String,
+    pub count: i32,
+}
+
+#[derive(Clone, Debug, GraphQLObject, PartialEq)]
+#[graphql(scalar = SHQScalarValue)]
+pub struct KindCount {
+    pub kind: CardSideKind,
+    pub count: i32,
+}
+
+#[derive(Clone, Debug, GraphQLObject, PartialEq)]
+#[graphql(scalar = SHQScalarValue)]
+pub struct AspectStats {
+    pub aspect: Option<Aspect>,
+    pub stats: DeckStats,
+}
+
+/// Computes the statistics of a deck from the front side of its cards
+pub fn stats(deck: &Deck) -> DeckStats {
+    let copies: Vec<&Card> = deck.copies().collect();
+    let mut aspects: Vec<(Option<Aspect>, Vec<&Card>)> = vec![];
+    for card in copies.iter() {
+        match aspects
+            .iter_mut()
+            .find(|(aspect, _)| aspect == &card.aspect)
+        {
+            Some((_, cards)) => cards.push(card),
+            None => aspects.push((card.aspect.clone(), vec![card])),
+        }
+    }
+
+    DeckStats {
+        aspects: aspects
+            .into_iter()
+            .map(|(aspect, cards)| AspectStats {
+                aspect,
+                stats: copies_stats(&cards),
+            })
+            .collect(),
+        ..copies_stats(&copies)
+    }
+}
+
+fn copies_stats(copies: &[&Card]) -> DeckStats {
+    let mut stats = DeckStats {
+        card_count: copies.len() as i32,
+        ..DeckStats::default()
+    };
+    let mut costs: BTreeMap<u8, i32> = BTreeMap::new();
+    let mut resources = [0; RESOURCES.len()];
+    let mut traits: BTreeMap<&str, i32> = BTreeMap::new();
+    let mut kinds: BTreeMap<CardSideKind, i32> = BTreeMap::new();
+
+    for side in copies.iter().filter_map(|card| card.sides.first()) {
+        match side.cost() {
+            Some(Cost::Number(cost)) => *costs.entry(*cost).or_default() += 1,
+            Some(Cost::X) => stats.x_cost_count += 1,
+            None => (),
+        }
+        for resource in side.resources().into_iter().flatten() {
+            if let Some(index) = RESOURCES.iter().position(|r| r == resource) {
+                resources[index] += 1;
+            }
+        }
+        for name in side.traits().into_iter().flatten() {
+            *traits.entry(name).or_default() += 1;
+        }
+        *kinds.entry(side.kind()).or_default() += 1;
+        if side.kind() == CardSideKind::Ally {
+            stats.ally_atk += side.atk().map_or(0, power);
+            stats.ally_thw += side.thw().map_or(0, power);
+        }
+    }
+
+    let costed: i32 = costs.values().sum();
+    if costed > 0 {
+        let total: i32 = costs
+            .iter()
+            .map(|(&cost, &count)| i32::from(cost) * count)
+            .sum();
+        stats.average_cost = Some(f64::from(total) / f64::from(costed));
+    }
+    stats.cost_curve = costs
+        .into_iter()
+        .map(|(cost, count)| CostCount {
+            cost: i32::from(cost),
+            count,
+        })
+        .collect();
+    stats.resources = RESOURCES
+        .into_iter()
+        .zip(resources)
+        .map(|(resource, count)| ResourceCount { resource, count })
+        .collect();
+    let mut traits: Vec<TraitCount> = traits
+        .into_iter()
+        .map(|(name, count)| TraitCount {
+            name: String::from(name),
+            count,
+        })
+        .collect();
+    // stable, so traits with the same count stay in alphabetical order
+    traits.sort_by_key(|r#trait| -r#trait.count);
+    stats.traits = traits;
+    stats.kinds = kinds
+        .into_iter()
+        .map(|(kind, count)| KindCount { kind, count })
+        .collect();
+
+    stats
+}
+
+/// The printed value of a power, with X counting as 0
+fn power(power: &BasicPower) -> i32 {
+    match power {
+        BasicPower::Number(value) | BasicPower::Effect(value) => i32::from(*value),
+        BasicPower::X => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        card::{self, card_side::CardSideVariant},
+        deck::DeckCard,
+        graphql::Ctx,
+    };
+
+    fn ctx() -> Ctx {
+        let mut document: card::Document =
+            toml::from_str(include_str!("../../data/core-set.toml")).unwrap();
+        // no core set card costs X, so make Swinging Web Kick cost X
+        for card in document.cards.iter_mut() {
+            if card.sides[0].name == "Swinging Web Kick" {
+                if let CardSideVariant::Event { cost, .. } = &mut card.sides[0].variant {
+                    *cost = Cost::X;
+                }
+            }
+        }
+
+        Ctx::new(document.cards, vec![], vec![])
+    }
+
+    fn deck_stats(cards: &[(&str, i32)]) -> DeckStats {
+        let ctx = ctx();
+        let cards: Vec<DeckCard> = cards
+            .iter()
+            .map(|&(code, quantity)| DeckCard {
+                code: String::from(code),
+                quantity,
+            })
+            .collect();
+
+        stats(&Deck::new(&cards, &ctx).unwrap())
+    }
+
+    #[test]
+    fn it_computes_the_cost_curve() {
+        // Black Cat (2), Backflip (0), Enhanced Spider-Sense (1) x2, Swinging Web Kick (X),
+        // Energy Absorption (no cost)
+        let stats = deck_stats(&[
+            ("MC01en-2", 1),
+            ("MC01en-3", 1),
+            ("MC01en-4", 2),
+            ("MC01en-5", 1),
+            ("MC01en-14", 1),
+        ]);
+
+        assert_eq!(6, stats.card_count);
+        assert_eq!(
+            vec![
+                CostCount { cost: 0, count: 1 },
+                CostCount { cost: 1, count: 2 },
+                CostCount { cost: 2, count: 1 },
+            ],
+            stats.cost_curve
+        );
+        assert_eq!(1, stats.x_cost_count);
+        assert_eq!(Some(1.0), stats.average_cost);
+    }
+
+    #[test]
+    fn it_counts_resources_traits_and_kinds() {
+        // Black Cat, Spider-Woman x2, Energy Absorption, Web-Shooter
+        let stats = deck_stats(&[
+            ("MC01en-2", 1),
+            ("MC01en-11", 2),
+            ("MC01en-14", 1),
+            ("MC01en-8", 1),
+        ]);
+
+        let counts: Vec<(Resource, i32)> = stats
+            .resources
+            .iter()
+            .map(|count| (count.resource, count.count))
+            .collect();
+        assert_eq!(
+            vec![
+                (Resource::Energy, 4),
+                (Resource::Mental, 0),
+                (Resource::Physical, 1),
+                (Resource::Wild, 2),
+            ],
+            counts
+        );
+        let traits: Vec<(&str, i32)> = stats
+            .traits
+            .iter()
+            .map(|count| (count.name.as_str(), count.count))
+            .collect();
+        assert_eq!(
+            vec![
+                ("Avenger", 2),
+                ("Spy", 2),
+                ("Hero for Hire", 1),
+                ("Item", 1),
+                ("Tech", 1)
+            ],
+            traits
+        );
+        assert_eq!(
+            vec![
+                KindCount {
+                    kind: CardSideKind::Ally,
+                    count: 3
+                },
+                KindCount {
+                    kind: CardSideKind::Resource,
+                    count: 1
+                },
+                KindCount {
+                    kind: CardSideKind::Upgrade,
+                    count: 1
+                },
+            ],
+            stats.kinds
+        );
+        // Black Cat 1/1, Spider-Woman 2/2
+        assert_eq!(5, stats.ally_atk);
+        assert_eq!(5, stats.ally_thw);
+    }
+
+    #[test]
+    fn it_splits_stats_by_aspect() {
+        // Black Cat and Mockingbird (Basic)
+        let stats = deck_stats(&[("MC01en-2", 1), ("MC01en-83", 2)]);
+
+        assert_eq!(2, stats.aspects.len());
+        assert_eq!(None, stats.aspects[0].aspect);
+        assert_eq!(1, stats.aspects[0].stats.card_count);
+        assert_eq!(Some(Aspect::Basic), stats.aspects[1].aspect);
+        assert_eq!(2, stats.aspects[1].stats.card_count);
+        assert_eq!(Some(3.0), stats.aspects[1].stats.average_cost);
+        assert!(stats.aspects[1].stats.aspects.is_empty());
+    }
+
+    #[test]
+    fn it_has_no_average_cost_without_costs() {
+        let stats = deck_stats(&[("MC01en-14", 3)]);
+
+        assert_eq!(None, stats.average_cost);
+        assert!(stats.cost_curve.is_empty());
+    }
+}
@@ -0,0 +1,359 @@
+use crate::{
+    card::{
+        card_side::{CardSideKind, Side},
+        Card, HitPoints,
+    },
+    deck::Deck,
+};
+use thiserror::Error;
+
+pub mod payment;
+mod rng;
+pub mod villain;
+
+pub use payment::{best_payment, payments, Payment, PaymentError};
+pub use rng::Rng;
+pub use villain::{Encounter, Event, MainScheme, Villain};
+
+#[derive(Debug, Error, PartialEq)]
+pub enum GameError {
+    #[error("{0} is not a hero")]
+    NotAHero(String),
+    #[error("Can't {action} during the {phase:?} phase")]
+    WrongPhase { action: &'static str, phase: Phase },
+    #[error("The hero already changed form this turn")]
+    AlreadyFlipped,
+    #[error("There is no card {0} in hand")]
+    NotInHand(usize),
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Phase {
+    Player,
+    Villain,
+}
+
+/// A solo game from the point of view of the player
+pub struct Game<'a> {
+    pub identity: &'a Card,
+    /// Side A is the hero and side B the alter-ego
+    pub form: Side,
+    pub hit_points: u32,
+    /// The top of the deck is the last card
+    pub deck: Vec<&'a Card>,
+    pub hand: Vec<&'a Card>,
+    pub discard: Vec<&'a Card>,
+    pub play_area: Vec<&'a Card>,
+    pub phase: Phase,
+    pub round: u32,
+    /// Times the discard pile was shuffled back into an empty deck
+    pub reshuffles: u32,
+    flipped: bool,
+    rng: Rng,
+}
+
+impl<'a> Game<'a> {
+    /// Sets up a game: the hero starts in alter-ego form, shuffles the deck with `seed` and
+    /// draws up to the alter-ego's hand size
+    pub fn setup(identity: &'a Card, deck: &Deck<'a>, seed: u64) -> Result<Self, GameError> {
+        let not_a_hero = || GameError::NotAHero(identity.sides[0].name.clone());
+        if !identity.is_hero() {
+            return Err(not_a_hero());
+        }
+        let hit_points = match identity.sides[0].hit_points().ok_or_else(not_a_hero)? {
+            // a solo game has one player
+            HitPoints::Number(hit_points) | HitPoints::PerPlayer(hit_points) => {
+                u32::from(*hit_points)
+            }
+        };
+
+        let mut rng = Rng::new(seed);
+        let mut cards: Vec<&Card> = deck.copies().collect();
+        rng.shuffle(&mut cards);
+        let mut game = Self {
+            identity,
+            form: Side::B,
+            hit_points,
+            deck: cards,
+            hand: vec![],
+            discard: vec![],
+            play_area: vec![],
+            phase: Phase::Player,
+            round: 1,
+            reshuffles: 0,
+            flipped: false,
+            rng,
+        };
+        game.draw_up_to_hand_size();
+
+        Ok(game)
+    }
+
+    /// Hand size of the current form
+    pub fn hand_size(&self) -> u32 {
+        self.identity.hand_size(&self.form).unwrap_or(0)
+    }
+
+    pub fn is_defeated(&self) -> bool {
+        self.hit_points == 0
+    }
+
+    /// Draws `count` cards, shuffling the discard pile into the deck when it runs out
+    pub fn draw(&mut self, count: u32) {
+        for _ in 0..count {
+            if self.deck.is_empty() {
+                if self.discard.is_empty() {
+                    return;
+                }
+                self.deck.append(&mut self.discard);
+                self.rng.shuffle(&mut self.deck);
+                self.reshuffles += 1;
+            }
+            if let Some(card) = self.deck.pop() {
+                self.hand.push(card);
+            }
+        }
+    }
+
+    fn draw_up_to_hand_size(&mut self) {
+        let missing = (self.hand_size() as usize).saturating_sub(self.hand.len());
+        self.draw(missing as u32);
+    }
+
+    fn player_phase(&self, action: &'static str) -> Result<(), GameError> {
+        match self.phase {
+            Phase::Player => Ok(()),
+            phase => Err(GameError::WrongPhase { action, phase }),
+        }
+    }
+
+    /// Changes between hero and alter-ego form, once per turn
+    pub fn flip(&mut self) -> Result<(), GameError> {
+        self.player_phase("flip")?;
+        if self.flipped {
+            return Err(GameError::AlreadyFlipped);
+        }
+
+        self.form = match self.form {
+            Side::A => Side::B,
+            _ => Side::A,
+        };
+        self.flipped = true;
+
+        Ok(())
+    }
+
+    /// Plays the card at `index` in hand. Events are discarded, other cards stay in play.
+    pub fn play(&mut self, index: usize) -> Result<&'a Card, GameError> {
+        self.player_phase("play a card")?;
+        let card = self.take_from_hand(index)?;
+        match card.sides.first().map(|side| side.kind()) {
+            Some(CardSideKind::Event) => self.discard.push(card),
+            _ => self.play_area.push(card),
+        }
+
+        Ok(card)
+    }
+
+    pub fn discard_from_hand(&mut self, index: usize) -> Result<&'a Card, GameError> {
+        let card = self.take_from_hand(index)?;
+        self.discard.push(card);
+
+        Ok(card)
+    }
+
+    fn take_from_hand(&mut self, index: usize) -> Result<&'a Card, GameError> {
+        if index >= self.hand.len() {
+            return Err(GameError::NotInHand(index));
+        }
+
+        Ok(self.hand.remove(index))
+    }
+
+    /// Deals damage to the hero, down to 0 hit points
+    pub fn damage(&mut self, amount: u32) {
+        self.hit_points = self.hit_points.saturating_sub(amount);
+    }
+
+    /// Ends the player phase: discards the cards at `discards` in hand and draws up to the hand
+    /// size of the current form
+    pub fn end_player_phase(&mut self, discards: &[usize]) -> Result<(), GameError> {
+        self.player_phase("end the player phase")?;
+        if let Some(&index) = discards.iter().find(|&&index| index >= self.hand.len()) {
+            return Err(GameError::NotInHand(index));
+        }
+
+        let mut discards = discards.to_vec();
+        discards.sort_unstable();
+        discards.dedup();
+        // remove from the back so the other indexes stay valid
+        for index in discards.into_iter().rev() {
+            let card = self.hand.remove(index);
+            self.discard.push(card);
+        }
+        self.draw_up_to_hand_size();
+        self.phase = Phase::Villain;
+
+        Ok(())
+    }
+
+    /// Ends the villain phase and the round, starting the next player phase
+    pub fn end_villain_phase(&mut self) -> Result<(), GameError> {
+        match self.phase {
+            Phase::Villain => (),
+            phase => {
+                return Err(GameError::WrongPhase {
+                    action: "end the villain phase",
+                    phase,
+                })
+            }
+        }
+
+        self.phase = Phase::Player;
+        self.round += 1;
+        self.flipped = false;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{card, deck::DeckCard, graphql::Ctx};
+
+    fn ctx() -> Ctx {
+        let document: card::Document =
+            toml::from_str(include_str!("../data/core-set.toml")).unwrap();
+
+        Ctx::new(document.cards, vec![], vec![])
+    }
+
+    /// Spider-Man's signature cards, two of each, 16 cards in all
+    fn deck(ctx: &Ctx) -> Deck<'_> {
+        let cards: Vec<DeckCard> = (2..=9)
+            .map(|position| DeckCard {
+                code: format!("MC01en-{position}"),
+                quantity: 2,
+            })
+            .collect();
+
+        Deck::new(&cards, ctx).unwrap()
+    }
+
+    fn names(cards: &[&Card]) -> Vec<String> {
+        cards
+            .iter()
+            .map(|card| card.sides[0].name.clone())
+            .collect()
+    }
+
+    fn setup(ctx: &Ctx, seed: u64) -> Game<'_> {
+        Game::setup(ctx.card("MC01en-1").unwrap(), &deck(ctx), seed).unwrap()
+    }
+
+    #[test]
+    fn it_sets_up_in_alter_ego_form() {
+        let ctx = ctx();
+        let game = setup(&ctx, 1);
+
+        assert_eq!(Side::B, game.form);
+        assert_eq!(10, game.hit_points);
+        assert_eq!(6, game.hand_size());
+        assert_eq!(6, game.hand.len());
+        assert_eq!(10, game.deck.len());
+        assert_eq!(Phase::Player, game.phase);
+        assert_eq!(1, game.round);
+    }
+
+    #[test]
+    fn it_shuffles_deterministically() {
+        let ctx = ctx();
+
+        assert_eq!(names(&setup(&ctx, 1).hand), names(&setup(&ctx, 1).hand));
+        assert_ne!(names(&setup(&ctx, 1).deck), names(&setup(&ctx, 2).deck));
+    }
+
+    #[test]
+    fn it_only_sets_up_heroes() {
+        let ctx = ctx();
+
+        assert_eq!(
+            Some(GameError::NotAHero(String::from("Black Cat"))),
+            Game::setup(ctx.card("MC01en-2").unwrap(), &deck(&ctx), 1).err()
+        );
+    }
+
+    #[test]
+    fn it_flips_once_per_turn() {
+        let ctx = ctx();
+        let mut game = setup(&ctx, 1);
+
+        game.flip().unwrap();
+        assert_eq!(Side::A, game.form);
+        assert_eq!(5, game.hand_size());
+        assert_eq!(Err(GameError::AlreadyFlipped), game.flip());
+
+        game.end_player_phase(&[]).unwrap();
+        game.end_villain_phase().unwrap();
+        game.flip().unwrap();
+        assert_eq!(Side::B, game.form);
+    }
+
+    #[test]
+    fn it_plays_cards() {
+        let ctx = ctx();
+        let mut game = setup(&ctx, 1);
+        let event = game
+            .hand
+            .iter()
+            .position(|card| card.sides[0].kind() == CardSideKind::Event)
+            .unwrap();
+
+        game.play(event).unwrap();
+        assert_eq!(5, game.hand.len());
+        assert_eq!(1, game.discard.len());
+        assert_eq!(Err(GameError::NotInHand(5)), game.play(5).map(|_| ()));
+    }
+
+    #[test]
+    fn it_follows_the_phases() {
+        let ctx = ctx();
+        let mut game = setup(&ctx, 1);
+
+        game.flip().unwrap();
+        game.end_player_phase(&[0, 1]).unwrap();
+        // the discarded cards are replaced up to the hero's hand size
+        assert_eq!(5, game.hand.len());
+        assert_eq!(2, game.discard.len());
+        assert_eq!(Phase::Villain, game.phase);
+        assert_eq!(
+            Err(GameError::WrongPhase {
+                action: "flip",
+                phase: Phase::Villain
+            }),
+            game.flip()
+        );
+
+        game.end_villain_phase().unwrap();
+        assert_eq!(Phase::Player, game.phase);
+        assert_eq!(2, game.round);
+    }
+
+    #[test]
+    fn it_reshuffles_the_discard_pile() {
+        let ctx = ctx();
+        let mut game = setup(&ctx, 1);
+
+        let mut round = 0;
+        while game.reshuffles == 0 && round < 10 {
+            let discards: Vec<usize> = (0..game.hand.len()).collect();
+            game.end_player_phase(&discards).unwrap();
+            game.end_villain_phase().unwrap();
+            round += 1;
+        }
+
+        assert_eq!(1, game.reshuffles);
+        assert_eq!(16, game.deck.len() + game.hand.len() + game.discard.len());
+    }
+}
@@ -0,0 +1,261 @@
+use crate::{
+    card::{card_side::CardSideKind, Card, Cost, Resource},
+    deck::ResourceCount,
+    graphql::SHQScalarValue,
+};
+use juniper::GraphQLObject;
+use thiserror::Error;
+
+/// Hands are searched exhaustively, so they're capped well above any real hand size
+pub const MAX_HAND_SIZE: usize = 16;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum PaymentError {
+    #[error("Could not find card {0}")]
+    UnknownCard(String),
+    #[error("X can't be negative, got {0}")]
+    NegativeX(i32),
+    #[error("{0} has no cost")]
+    NoCost(String),
+    #[error("A hand of {0} cards is too large, the maximum is {MAX_HAND_SIZE}")]
+    HandTooLarge(usize),
+}
+
+/// A way to pay for a card by discarding cards from hand
+#[derive(Clone, Debug, GraphQLObject, PartialEq)]
+#[graphql(scalar = SHQScalarValue)]
+pub struct Payment {
+    /// Indexes into the hand of the cards to discard
+    pub discards: Vec<i32>,
+    pub resources: Vec<ResourceCount>,
+    /// What X is paid for, for cards that cost X
+    pub x: Option<i32>,
+    /// How much the discarded cards are worth keeping, lower is better
+    pub value: i32,
+    /// Resources generated beyond the cost
+    pub wasted: i32,
+}
+
+/// How much a card is worth keeping instead of discarding it. Resource cards are meant to be
+/// discarded, while other cards are worth more the more they cost.
+pub fn value(card: &Card) -> i32 {
+    match card.sides.first() {
+        Some(side) if side.kind() == CardSideKind::Resource => 0,
+        Some(side) => match side.cost() {
+            Some(Cost::Number(cost)) => i32::from(*cost) + 1,
+            _ => 1,
+        },
+        None => 0,
+    }
+}
+
+fn resources(card: &Card) -> &[Resource] {
+    card.sides
+        .first()
+        .and_then(|side| side.resources())
+        .map_or(&[], Vec::as_slice)
+}
+
+/// Every way to pay for `target` by discarding other cards in `hand`, least valuable first.
+/// Resources of any kind pay for any cost. Payments never discard a card that isn't needed.
+///
+/// For a card that costs X, `x` is the X to pay for. Without it every X that can be paid is
+/// listed.
+pub fn payments(
+    hand: &[&Card],
+    target: &Card,
+    x: Option<u32>,
+) -> Result<Vec<Payment>, PaymentError> {
+    if hand.len() > MAX_HAND_SIZE {
+        return Err(PaymentError::HandTooLarge(hand.len()));
+    }
+    let cost = match target.sides.first().and_then(|side| side.cost()) {
+        Some(Cost::Number(cost)) => Some(u32::from(*cost)),
+        Some(Cost::X) => x,
+        None => return Err(PaymentError::NoCost(target.sides[0].name.clone())),
+    };
+    let is_x = matches!(target.sides[0].cost(), Some(Cost::X));
+
+    // the target itself can't be discarded to pay for it
+    let target_index = hand.iter().position(|card| card.is_same_printing(target));
+    let candidates: Vec<(usize, u32)> = hand
+        .iter()
+        .enumerate()
+        .filter(|&(index, _)| Some(index) != target_index)
+        .map(|(index, card)| (index, resources(card).len() as u32))
+        .collect();
+
+    let mut payments = vec![];
+    for subset in 0u32..(1 << candidates.len()) {
+        let chosen: Vec<(usize, u32)> = candidates
+            .iter()
+            .enumerate()
+            .filter(|(bit, _)| subset & (1 << bit) != 0)
+            .map(|(_, &candidate)| candidate)
+            .collect();
+        let total: u32 = chosen.iter().map(|(_, count)| count).sum();
+        let needed = match cost {
+            Some(cost) => cost,
+            // every resource raises X, so any discard that generates resources pays for an X
+            None => total,
+        };
+        let minimal = if needed == 0 {
+            chosen.is_empty()
+        } else {
+            chosen.iter().all(|(_, count)| total - count < needed)
+        };
+        if total < needed || !minimal {
+            continue;
+        }
+
+        let discards: Vec<usize> = chosen.iter().map(|&(index, _)| index).collect();
+        payments.push(Payment {
+            resources: count_resources(discards.iter().map(|&index| hand[index])),
+            x: is_x.then_some(needed as i32),
+            value: discards.iter().map(|&index| value(hand[index])).sum(),
+            wasted: (total - needed) as i32,
+            discards: discards.into_iter().map(|index| index as i32).collect(),
+        });
+    }
+    payments.sort_by(|a, b| {
+        (a.value, a.discards.len(), a.wasted)
+            .cmp(&(b.value, b.discards.len(), b.wasted))
+            .then_with(|| a.discards.cmp(&b.discards))
+    });
+
+    Ok(payments)
+}
+
+/// The payment that discards the least valuable cards
+pub fn best_payment(
+    hand: &[&Card],
+    target: &Card,
+    x: Option<u32>,
+) -> Result<Option<Payment>, PaymentError> {
+    Ok(payments(hand, target, x)?.into_iter().next())
+}
+
+fn count_resources<'a>(cards: impl Iterator<Item = &'a Card>) -> Vec<ResourceCount> {
+    let mut counts: Vec<ResourceCount> = vec![];
+    for resource in cards.flat_map(resources) {
+        match counts.iter_mut().find(|count| count.resource == *resource) {
+            Some(count) => count.count += 1,
+            None => counts.push(ResourceCount {
+                resource: *resource,
+                count: 1,
+            }),
+        }
+    }
+
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{card, card::card_side::CardSideVariant, graphql::Ctx};
+
+    fn ctx() -> Ctx {
+        let mut document: card::Document =
+            toml::from_str(include_str!("../../data/core-set.toml")).unwrap();
+        // no core set card costs X, so make Swinging Web Kick cost X
+        for card in document.cards.iter_mut() {
+            if card.sides[0].name == "Swinging Web Kick" {
+                if let CardSideVariant::Event { cost, .. } = &mut card.sides[0].variant {
+                    *cost = Cost::X;
+                }
+            }
+        }
+
+        Ctx::new(document.cards, vec![], vec![])
+    }
+
+    fn hand<'a>(ctx: &'a Ctx, codes: &[&str]) -> Vec<&'a Card> {
+        codes.iter().map(|code| ctx.card(code).unwrap()).collect()
+    }
+
+    fn discards(payments: &[Payment]) -> Vec<Vec<i32>> {
+        payments
+            .iter()
+            .map(|payment| payment.discards.clone())
+            .collect()
+    }
+
+    #[test]
+    fn it_lists_every_minimal_payment() {
+        let ctx = ctx();
+        // Black Cat (2) with Backflip (0), Aunt May (1) and Web-Shooter (1)
+        let hand = hand(&ctx, &["MC01en-2", "MC01en-3", "MC01en-6", "MC01en-8"]);
+        let payments = payments(&hand, hand[0], None).unwrap();
+
+        assert_eq!(
+            vec![vec![1, 2], vec![1, 3], vec![2, 3]],
+            discards(&payments)
+        );
+        assert_eq!(3, payments[0].value);
+        assert!(payments.iter().all(|payment| payment.wasted == 0));
+    }
+
+    #[test]
+    fn it_prefers_discarding_resource_cards() {
+        let ctx = ctx();
+        // Black Cat (2) with Energy Absorption (3 energy) and Backflip
+        let hand = hand(&ctx, &["MC01en-2", "MC01en-14", "MC01en-3", "MC01en-4"]);
+        let best = best_payment(&hand, hand[0], None).unwrap().unwrap();
+
+        assert_eq!(vec![1], best.discards);
+        assert_eq!(0, best.value);
+        assert_eq!(1, best.wasted);
+        assert_eq!(
+            vec![ResourceCount {
+                resource: Resource::Energy,
+                count: 3
+            }],
+            best.resources
+        );
+    }
+
+    #[test]
+    fn it_pays_for_free_cards_without_discards() {
+        let ctx = ctx();
+        let hand = hand(&ctx, &["MC01en-3", "MC01en-4"]);
+        let payments = payments(&hand, hand[0], None).unwrap();
+
+        assert_eq!(vec![Vec::<i32>::new()], discards(&payments));
+    }
+
+    #[test]
+    fn it_finds_no_payment_without_enough_resources() {
+        let ctx = ctx();
+        // Webbed Up (4) with only two other cards
+        let hand = hand(&ctx, &["MC01en-9", "MC01en-3", "MC01en-4"]);
+
+        assert_eq!(None, best_payment(&hand, hand[0], None).unwrap());
+    }
+
+    #[test]
+    fn it_pays_for_x() {
+        let ctx = ctx();
+        // Swinging Web Kick (X) with Energy Absorption and Backflip
+        let hand = hand(&ctx, &["MC01en-5", "MC01en-14", "MC01en-3"]);
+
+        let payments_2 = payments(&hand, hand[0], Some(2)).unwrap();
+        assert_eq!(vec![vec![1]], discards(&payments_2));
+        assert_eq!(Some(2), payments_2[0].x);
+
+        let all = payments(&hand, hand[0], None).unwrap();
+        let xs: Vec<Option<i32>> = all.iter().map(|payment| payment.x).collect();
+        assert_eq!(vec![Some(0), Some(3), Some(1), Some(4)], xs);
+    }
+
+    #[test]
+    fn it_rejects_cards_without_cost() {
+        let ctx = ctx();
+        let hand = hand(&ctx, &["MC01en-14"]);
+
+        assert_eq!(
+            Err(PaymentError::NoCost(String::from("Energy Absorption"))),
+            payments(&hand, hand[0], None)
+        );
+    }
+}
@@ -0,0 +1,504 @@
+use crate::{
+    card::{card_side::Side, Card},
+    game::{Game, GameError, Phase, Rng},
+    graphql::Ctx,
+};
+use std::sync::Arc;
+
+/// The villain's printed stats, which the card data doesn't have yet
+#[derive(Clone, Debug, PartialEq)]
+pub struct Villain {
+    pub name: String,
+    pub sch: u32,
+    pub atk: u32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MainScheme {
+    pub name: String,
+    pub threat: u32,
+    /// Threat placed at the start of every villain phase
+    pub threat_per_round: u32,
+    /// The players lose when the threat reaches the limit
+    pub threat_limit: u32,
+}
+
+/// What happened during the villain phase, in order
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    ThreatPlaced {
+        amount: u32,
+        threat: u32,
+    },
+    SchemeCompleted,
+    BoostDealt {
+        card: String,
+        icons: u32,
+        star: bool,
+    },
+    /// The villain attacked the hero of the player at `player` in turn order
+    VillainAttacked {
+        player: usize,
+        atk: u32,
+        boost: u32,
+        damage: u32,
+    },
+    /// The villain schemed against the alter-ego of the player at `player` in turn order
+    VillainSchemed {
+        player: usize,
+        sch: u32,
+        boost: u32,
+        threat: u32,
+    },
+    EncounterRevealed {
+        player: usize,
+        card: String,
+    },
+    EncounterDeckShuffled,
+    HeroDefeated {
+        player: usize,
+    },
+}
+
+/// Boost icons on the front of `card` and whether it has a boost star
+pub fn boost(card: &Card) -> (u32, bool) {
+    card.sides.first().map_or((0, false), |side| {
+        (
+            side.boost_icons().map_or(0, u32::from),
+            side.boost_star_icon(),
+        )
+    })
+}
+
+/// Cards in the sets named `sets`, with a copy for every position the card has in its set
+pub fn encounter_deck<'a>(context: &'a Ctx, sets: &[&str]) -> Vec<&'a Card> {
+    context
+        .cards
+        .iter()
+        .map(Arc::as_ref)
+        .flat_map(|card| {
+            let copies = card
+                .products
+                .iter()
+                .flat_map(|product| product.sets.iter().flatten())
+                .find(|set| {
+                    set.name(context)
+                        .is_some_and(|name| sets.contains(&name.as_str()))
+                })
+                .map_or(0, |set| set.positions.as_ref().map_or(1, Vec::len));
+
+            std::iter::repeat_n(card, copies)
+        })
+        .collect()
+}
+
+/// The villain, main scheme and encounter deck of a game
+pub struct Encounter<'a> {
+    pub villain: Villain,
+    pub scheme: MainScheme,
+    /// The top of the deck is the last card
+    pub deck: Vec<&'a Card>,
+    pub discard: Vec<&'a Card>,
+    /// Boost cards dealt to the villain when it activates
+    pub boosts_per_activation: u32,
+    pub log: Vec<Event>,
+    rng: Rng,
+}
+
+impl<'a> Encounter<'a> {
+    /// Sets up the encounter, shuffling `cards` into the encounter deck with `seed`
+    pub fn new(villain: Villain, scheme: MainScheme, cards: Vec<&'a Card>, seed: u64) -> Self {
+        let mut rng = Rng::new(seed);
+        let mut deck = cards;
+        rng.shuffle(&mut deck);
+
+        Self {
+            villain,
+            scheme,
+            deck,
+            discard: vec![],
+            boosts_per_activation: 1,
+            log: vec![],
+            rng,
+        }
+    }
+
+    pub fn is_scheme_completed(&self) -> bool {
+        self.scheme.threat >= self.scheme.threat_limit
+    }
+
+    /// Deals the top card, shuffling the discard pile into the deck when it runs out
+    fn deal(&mut self) -> Option<&'a Card> {
+        if self.deck.is_empty() && !self.discard.is_empty() {
+            self.deck.append(&mut self.discard);
+            self.rng.shuffle(&mut self.deck);
+            self.log.push(Event::EncounterDeckShuffled);
+        }
+
+        self.deck.pop()
+    }
+
+    /// Adds `amount` threat to the main scheme, logging `event` with the new threat
+    fn add_threat(&mut self, amount: u32, event: impl FnOnce(u32) -> Event) {
+        let completed = self.is_scheme_completed();
+        self.scheme.threat += amount;
+        self.log.push(event(self.scheme.threat));
+        if !completed && self.is_scheme_completed() {
+            self.log.push(Event::SchemeCompleted);
+        }
+    }
+
+    /// Deals and discards the boost cards for an activation, returning the boost icons
+    fn boost(&mut self) -> u32 {
+        let mut total = 0;
+        for _ in 0..self.boosts_per_activation {
+            if let Some(card) = self.deal() {
+                let (icons, star) = boost(card);
+                total += icons;
+                self.log.push(Event::BoostDealt {
+                    card: card.sides[0].name.clone(),
+                    icons,
+                    star,
+                });
+                self.discard.push(card);
+            }
+        }
+
+        total
+    }
+
+    /// Runs the villain phase of `games`, one per player in turn order: places threat on the
+    /// main scheme, activates the villain against each player, which attacks a hero and schemes
+    /// against an alter-ego, then deals every player an encounter card and reveals them in turn
+    /// order. Players whose hero is already defeated are skipped. Ends the round when done.
+    pub fn villain_phase(&mut self, games: &mut [Game<'a>]) -> Result<(), GameError> {
+        if let Some(game) = games.iter().find(|game| game.phase != Phase::Villain) {
+            return Err(GameError::WrongPhase {
+                action: "run the villain phase",
+                phase: game.phase,
+            });
+        }
+
+        let amount = self.scheme.threat_per_round;
+        self.add_threat(amount, |threat| Event::ThreatPlaced { amount, threat });
+
+        let players: Vec<usize> = (0..games.len())
+            .filter(|&player| !games[player].is_defeated())
+            .collect();
+        for &player in players.iter() {
+            let game = &mut games[player];
+            let boost = self.boost();
+            if game.form == Side::A {
+                let damage = self.villain.atk + boost;
+                game.damage(damage);
+                self.log.push(Event::VillainAttacked {
+                    player,
+                    atk: self.villain.atk,
+                    boost,
+                    damage,
+                });
+                if game.is_defeated() {
+                    self.log.push(Event::HeroDefeated { player });
+                }
+            } else {
+                let sch = self.villain.sch;
+                self.add_threat(sch + boost, |threat| Event::VillainSchemed {
+                    player,
+                    sch,
+                    boost,
+                    threat,
+                });
+            }
+        }
+
+        // every player is dealt a card before any is revealed
+        let dealt: Vec<(usize, &'a Card)> = players
+            .into_iter()
+            .filter(|&player| !games[player].is_defeated())
+            .filter_map(|player| Some((player, self.deal()?)))
+            .collect();
+        // encounter card effects are only text, so revealed cards are logged and discarded
+        for (player, card) in dealt {
+            self.log.push(Event::EncounterRevealed {
+                player,
+                card: card.sides[0].name.clone(),
+            });
+            self.discard.push(card);
+        }
+
+        games.iter_mut().try_for_each(Game::end_villain_phase)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BoostEstimate {
+    pub activations: u32,
+    /// Average boost icons, i.e. the extra damage of an attack
+    pub expected_boost: f64,
+    /// Share of activations with at least one boost star
+    pub star_chance: f64,
+}
+
+/// Estimates the boost per activation by dealing `boosts` cards from a freshly shuffled `deck`
+/// for each of `activations` simulated activations
+pub fn estimate_boost(deck: &[&Card], boosts: u32, activations: u32, seed: u64) -> BoostEstimate {
+    let mut rng = Rng::new(seed);
+    let mut deck = deck.to_vec();
+    let mut icons = 0u64;
+    let mut stars = 0u32;
+
+    for _ in 0..activations {
+        rng.shuffle(&mut deck);
+        let dealt = deck
+            .iter()
+            .rev()
+            .take(boosts as usize)
+            .map(|card| boost(card));
+        let (activation_icons, star) = dealt
+            .fold((0, false), |(total, any_star), (icons, star)| {
+                (total + icons, any_star || star)
+            });
+        icons += u64::from(activation_icons);
+        stars += u32::from(star);
+    }
+
+    let activations_f64 = f64::from(activations.max(1));
+    BoostEstimate {
+        activations,
+        expected_boost: icons as f64 / activations_f64,
+        star_chance: f64::from(stars) / activations_f64,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        card,
+        deck::{Deck, DeckCard},
+        product,
+    };
+    use std::slice;
+
+    fn ctx() -> Ctx {
+        let document: card::Document =
+            toml::from_str(include_str!("../../data/core-set.toml")).unwrap();
+        let products: product::Document =
+            toml::from_str(include_str!("../../data/products.toml")).unwrap();
+
+        Ctx::new(document.cards, products.products, vec![])
+    }
+
+    fn rhino() -> Villain {
+        Villain {
+            name: String::from("Rhino"),
+            sch: 1,
+            atk: 2,
+        }
+    }
+
+    fn scheme() -> MainScheme {
+        MainScheme {
+            name: String::from("The Break-In!"),
+            threat: 0,
+            threat_per_round: 1,
+            threat_limit: 7,
+        }
+    }
+
+    fn game(ctx: &Ctx) -> Game<'_> {
+        let cards: Vec<DeckCard> = (2..=9)
+            .map(|position| DeckCard {
+                code: format!("MC01en-{position}"),
+                quantity: 2,
+            })
+            .collect();
+        let deck = Deck::new(&cards, ctx).unwrap();
+
+        Game::setup(ctx.card("MC01en-1").unwrap(), &deck, 1).unwrap()
+    }
+
+    #[test]
+    fn it_builds_encounter_decks_from_sets() {
+        let ctx = ctx();
+        let deck = encounter_deck(&ctx, &["Spider-Man Nemesis"]);
+
+        // Sweeping Swoop has two copies
+        assert_eq!(5, deck.len());
+        assert_eq!(7, deck.iter().map(|card| boost(card).0).sum::<u32>());
+    }
+
+    #[test]
+    fn it_schemes_against_an_alter_ego() {
+        let ctx = ctx();
+        let mut game = game(&ctx);
+        let cards = encounter_deck(&ctx, &["Spider-Man Nemesis"]);
+        let mut encounter = Encounter::new(rhino(), scheme(), cards, 1);
+
+        game.end_player_phase(&[]).unwrap();
+        encounter.villain_phase(slice::from_mut(&mut game)).unwrap();
+
+        let boost = match &encounter.log[1] {
+            Event::BoostDealt { icons, .. } => *icons,
+            event => panic!("expected a boost, got {event:?}"),
+        };
+        assert_eq!(
+            Event::ThreatPlaced {
+                amount: 1,
+                threat: 1
+            },
+            encounter.log[0]
+        );
+        assert_eq!(
+            Event::VillainSchemed {
+                player: 0,
+                sch: 1,
+                boost,
+                threat: 2 + boost
+            },
+            encounter.log[2]
+        );
+        assert!(matches!(
+            encounter.log[3],
+            Event::EncounterRevealed { player: 0, .. }
+        ));
+        assert_eq!(10, game.hit_points);
+        assert_eq!(Phase::Player, game.phase);
+        assert_eq!(2, game.round);
+    }
+
+    #[test]
+    fn it_attacks_a_hero() {
+        let ctx = ctx();
+        let mut game = game(&ctx);
+        let cards = encounter_deck(&ctx, &["Spider-Man Nemesis"]);
+        let mut encounter = Encounter::new(rhino(), scheme(), cards, 1);
+
+        game.flip().unwrap();
+        game.end_player_phase(&[]).unwrap();
+        encounter.villain_phase(slice::from_mut(&mut game)).unwrap();
+
+        let damage = encounter
+            .log
+            .iter()
+            .find_map(|event| match event {
+                Event::VillainAttacked { damage, .. } => Some(*damage),
+                _ => None,
+            })
+            .unwrap();
+        assert!(damage >= 2);
+        assert_eq!(10 - damage, game.hit_points);
+        assert_eq!(Side::A, game.form);
+    }
+
+    #[test]
+    fn it_activates_against_and_deals_to_every_player() {
+        let ctx = ctx();
+        let mut games = vec![game(&ctx), game(&ctx), game(&ctx)];
+        let cards = encounter_deck(&ctx, &["Spider-Man Nemesis", "She-Hulk Nemesis"]);
+        let mut encounter = Encounter::new(rhino(), scheme(), cards, 1);
+        games[1].hit_points = 0;
+
+        for game in games.iter_mut() {
+            game.end_player_phase(&[]).unwrap();
+        }
+        encounter.villain_phase(&mut games).unwrap();
+
+        let players = |matches: fn(&Event) -> Option<usize>| -> Vec<usize> {
+            encounter.log.iter().filter_map(matches).collect()
+        };
+        assert_eq!(
+            vec![0, 2],
+            players(|event| match event {
+                Event::VillainSchemed { player, .. } => Some(*player),
+                _ => None,
+            })
+        );
+        assert_eq!(
+            vec![0, 2],
+            players(|event| match event {
+                Event::EncounterRevealed { player, .. } => Some(*player),
+                _ => None,
+            })
+        );
+        // both cards are dealt after both activations
+        let revealed = encounter
+            .log
+            .iter()
+            .position(|event| matches!(event, Event::EncounterRevealed { .. }))
+            .unwrap();
+        assert!(encounter.log[revealed..]
+            .iter()
+            .all(|event| matches!(event, Event::EncounterRevealed { .. })));
+        assert!(games
+            .iter()
+            .all(|game| game.phase == Phase::Player && game.round == 2));
+    }
+
+    #[test]
+    fn it_replays_the_same_log_for_a_seed() {
+        let ctx = ctx();
+        let run = |seed| {
+            let mut game = game(&ctx);
+            let cards = encounter_deck(&ctx, &["Spider-Man Nemesis", "She-Hulk Nemesis"]);
+            let mut encounter = Encounter::new(rhino(), scheme(), cards, seed);
+            for _ in 0..3 {
+                game.end_player_phase(&[]).unwrap();
+                encounter.villain_phase(slice::from_mut(&mut game)).unwrap();
+            }
+
+            encounter.log
+        };
+
+        assert_eq!(run(3), run(3));
+        assert_ne!(run(3), run(4));
+    }
+
+    #[test]
+    fn it_completes_the_scheme_and_reshuffles() {
+        let ctx = ctx();
+        let mut game = game(&ctx);
+        let cards = encounter_deck(&ctx, &["Spider-Man Nemesis"]);
+        let mut encounter = Encounter::new(rhino(), scheme(), cards, 1);
+
+        for _ in 0..4 {
+            game.end_player_phase(&[]).unwrap();
+            encounter.villain_phase(slice::from_mut(&mut game)).unwrap();
+        }
+
+        assert!(encounter.is_scheme_completed());
+        assert_eq!(
+            1,
+            encounter
+                .log
+                .iter()
+                .filter(|event| **event == Event::SchemeCompleted)
+                .count()
+        );
+        assert!(encounter.log.contains(&Event::EncounterDeckShuffled));
+    }
+
+    #[test]
+    fn it_only_runs_during_the_villain_phase() {
+        let ctx = ctx();
+        let mut game = game(&ctx);
+        let mut encounter = Encounter::new(rhino(), scheme(), vec![], 1);
+
+        assert!(matches!(
+            encounter.villain_phase(slice::from_mut(&mut game)),
+            Err(GameError::WrongPhase { .. })
+        ));
+    }
+
+    #[test]
+    fn it_estimates_boost_damage() {
+        let ctx = ctx();
+        let deck = encounter_deck(&ctx, &["Spider-Man Nemesis"]);
+        let estimate = estimate_boost(&deck, 1, 10_000, 1);
+
+        // 7 icons and 2 stars over 5 cards
+        assert!((estimate.expected_boost - 1.4).abs() < 0.05);
+        assert!((estimate.star_chance - 0.4).abs() < 0.02);
+        assert_eq!(estimate, estimate_boost(&deck, 1, 10_000, 1));
+    }
+}
@@ -1,12 +1,24 @@
 use crate::{
-    card::{Card, CardInput},
-    product::{Product, ProductInput, Set as ProductSet},
+    campaign::{CampaignError, CampaignLog, CampaignLogs, UpgradeInput},
+    card::{card_side::Side, r#trait, Card, CardInput, Plan, Trait, TraitError},
+    card_store::{CardStore, CardStoreError, MemoryStore},
+    deck::{self, odds, Deck, DeckCard, DeckStats, DrawOdds, DrawTargets},
+    game::{self, Payment, PaymentError},
+    play::{self, Play, PlayInput, Plays, WinRate, WinRateGroup},
+    product::{Product, ProductInput, Set as ProductSet, SetType as ProductSetType},
+    scenario::{self, Campaign, Scenario},
 };
-use juniper::{graphql_object, Context, EmptyMutation, EmptySubscription, FieldResult, RootNode};
-use std::collections::HashMap;
+use juniper::{graphql_object, Context, FieldResult, RootNode};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+use tokio::sync::broadcast;
 
 mod scalar;
+pub mod subscription;
 pub use scalar::SHQScalarValue;
+pub use subscription::{DataEvent, DataReload, Published, Subscription};
 
 /// Macro to simplify writing graphql filters
 macro_rules! filter {
@@ -36,13 +48,11 @@ macro_rules! filter_vec {
     ( $filter:ident, $($item:expr => $input:expr), + ) => {
         $(
             if let Some(input) = $input {
-                if input.is_some() && $item.is_some() {
-                    let a: HashSet<_> = input.as_ref().unwrap().iter().collect();
-                    let b: HashSet<_> = $item.unwrap().iter().collect();
-
-                    $filter = $filter && a.intersection(&b).next().is_some();
-                } else if input.as_ref() != $item {
-                    $filter = false;
+                match (input.as_ref(), $item) {
+                    (Some(input), Some(item)) => {
+                        $filter = $filter && input.iter().any(|value| item.contains(value));
+                    }
+                    (input, item) => $filter = $filter && input == item,
                 }
             }
         )*
@@ -63,51 +73,403 @@ macro_rules! filter_context {
 }
 pub(crate) use filter_context;
 
-#[derive(Default)]
+/// Position of a product in `Ctx::products`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ProductId(usize);
+
+/// Position of a set in the sets of its product
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SetId {
+    product: ProductId,
+    set: usize,
+}
+
+/// The loaded data. Every card, product and trait is stored once and the indexes refer to them
+/// by id, so snapshots and card stores share them instead of copying.
 pub struct Ctx {
-    pub cards: Vec<Card>,
-    pub products: Vec<Product>,
-    pub sets: Vec<ProductSet>,
-    // https://github.com/graphql-rust/juniper/issues/143
-    // Just going to clone products again in memory, since I don't want to deal with lifetime
-    // parameters in the Context object in juniper
-    products_index: HashMap<String, Product>,
-    sets_index: HashMap<String, ProductSet>,
+    pub cards: Vec<Arc<Card>>,
+    pub products: Vec<Arc<Product>>,
+    pub traits: Vec<Trait>,
+    pub scenarios: Vec<Scenario>,
+    pub campaigns: Vec<Campaign>,
+    products_index: HashMap<String, ProductId>,
+    /// Index by product code, then by the code of the set in that product
+    sets_index: HashMap<String, HashMap<String, SetId>>,
+    /// Index by name of the sets scenarios and plays refer to by name, earliest product first
+    set_names_index: HashMap<String, Vec<SetId>>,
+    /// Index into `traits` by name
+    traits_index: HashMap<String, usize>,
+    /// Number of cards with a side of every trait, by trait name
+    trait_counts: HashMap<String, usize>,
+    /// Index into `cards` by the code of every printing
+    cards_index: HashMap<String, usize>,
+    /// Indexes into `cards` of every printing of a card by its identity, earliest first
+    printings_index: HashMap<String, Vec<usize>>,
+    /// Indexes into `cards` of the cards in every hero's signature set, by hero name
+    signatures_index: HashMap<String, Vec<usize>>,
+    /// Index by hero name, to the earliest nemesis set of that hero
+    nemeses_index: HashMap<String, SetId>,
+    /// Where `Query.cards` and `Query.products` read from
+    card_store: Arc<dyn CardStore>,
+    /// Shared by every snapshot of the data, since they're changed through mutations
+    campaign_logs: Arc<CampaignLogs>,
+    plays: Arc<Plays>,
+    events: Option<broadcast::Sender<DataEvent>>,
 }
 
 impl Context for Ctx {}
 
+impl Default for Ctx {
+    fn default() -> Self {
+        Self::new(vec![], vec![], vec![])
+    }
+}
+
 impl Ctx {
-    pub fn new(cards: Vec<Card>, products: Vec<Product>) -> Self {
+    pub fn new(cards: Vec<Card>, products: Vec<Product>, traits: Vec<Trait>) -> Self {
+        let products: Vec<_> = products.into_iter().map(Arc::new).collect();
         let products_index: HashMap<_, _> = products
             .iter()
-            .map(|product| (product.code.clone(), product.clone()))
+            .enumerate()
+            .map(|(index, product)| (product.code.clone(), ProductId(index)))
+            .collect();
+        let mut sets_index: HashMap<String, HashMap<String, SetId>> = HashMap::new();
+        let mut set_names_index = HashMap::new();
+        let mut nemeses_index = HashMap::new();
+        for (index, product) in products.iter().enumerate() {
+            for (set, product_set) in product.sets.iter().enumerate() {
+                let id = SetId {
+                    product: ProductId(index),
+                    set,
+                };
+                sets_index
+                    .entry(product.code.clone())
+                    .or_default()
+                    .insert(product_set.code.clone(), id);
+                if product_set.r#type.is_named() {
+                    set_names_index
+                        .entry(product_set.name.clone())
+                        .or_insert_with(Vec::new)
+                        .push(id);
+                }
+                if product_set.r#type == ProductSetType::Nemesis {
+                    if let Some(hero) = product_set.hero() {
+                        nemeses_index.entry(String::from(hero)).or_insert(id);
+                    }
+                }
+            }
+        }
+        let cards: Vec<_> = cards
+            .into_iter()
+            .map(|mut card| {
+                for product in &mut card.products {
+                    product.id = products_index.get(&product.code).copied();
+                    for set in product.sets.iter_mut().flatten() {
+                        set.id = sets_index
+                            .get(&set.product)
+                            .and_then(|sets| sets.get(&set.code))
+                            .copied();
+                    }
+                }
+
+                Arc::new(card)
+            })
             .collect();
-        let sets: Vec<ProductSet> = products
+        let traits_index: HashMap<_, _> = traits
             .iter()
-            .map(|product| product.sets.clone())
-            .flatten()
+            .enumerate()
+            .map(|(index, r#trait)| (r#trait.name.clone(), index))
             .collect();
-        let sets_index: HashMap<_, _> = sets
+        let mut trait_counts: HashMap<String, usize> = HashMap::new();
+        for card in cards.iter() {
+            let names: HashSet<&String> = card
+                .sides
+                .iter()
+                .flat_map(|side| side.traits().into_iter().flatten())
+                .collect();
+            for name in names {
+                *trait_counts.entry(name.clone()).or_default() += 1;
+            }
+        }
+        let cards_index: HashMap<_, _> = cards
             .iter()
-            .map(|set| (set.name.clone(), set.clone()))
+            .enumerate()
+            .flat_map(|(index, card)| {
+                card.products
+                    .iter()
+                    .filter_map(move |product| Some((product.card_code()?, index)))
+            })
             .collect();
 
-        Self {
+        let card_store = Arc::new(MemoryStore::shared(cards.clone(), products.clone()));
+
+        let mut ctx = Self {
             cards,
             products,
-            sets,
+            traits,
+            scenarios: vec![],
+            campaigns: vec![],
             products_index,
             sets_index,
+            set_names_index,
+            traits_index,
+            trait_counts,
+            cards_index,
+            printings_index: HashMap::new(),
+            signatures_index: HashMap::new(),
+            nemeses_index,
+            card_store,
+            campaign_logs: Arc::default(),
+            plays: Arc::default(),
+            events: None,
+        };
+        ctx.printings_index = ctx.index_printings();
+        ctx.signatures_index = ctx.index_signatures();
+
+        ctx
+    }
+
+    fn index_printings(&self) -> HashMap<String, Vec<usize>> {
+        let mut printings: HashMap<String, Vec<usize>> = HashMap::new();
+        for (index, card) in self.cards.iter().enumerate() {
+            printings
+                .entry(self.identity(card))
+                .or_default()
+                .push(index);
+        }
+        for indexes in printings.values_mut() {
+            indexes.sort_by_key(|&index| {
+                let card = &self.cards[index];
+                let released = card
+                    .products
+                    .iter()
+                    .filter_map(|product| product.release_date(self))
+                    .min()
+                    .copied();
+
+                // cards without a known release date go last
+                (released.is_none(), released, index)
+            });
+        }
+
+        printings
+    }
+
+    fn index_signatures(&self) -> HashMap<String, Vec<usize>> {
+        let mut signatures: HashMap<String, Vec<usize>> = HashMap::new();
+        for (index, card) in self.cards.iter().enumerate() {
+            for set in card.product_sets(self) {
+                if let (ProductSetType::HeroSignature, Some(hero)) = (&set.r#type, set.hero()) {
+                    let cards = signatures.entry(String::from(hero)).or_default();
+                    // a card in several printings of the set is listed once
+                    if cards.last() != Some(&index) {
+                        cards.push(index);
+                    }
+                }
+            }
         }
+
+        signatures
+    }
+
+    pub fn with_scenarios(mut self, document: scenario::Document) -> Self {
+        self.scenarios = document.scenarios;
+        self.campaigns = document.campaigns;
+        self
+    }
+
+    /// Serves `Query.cards` and `Query.products`, and the REST cards and products, from
+    /// `card_store` instead of the in-memory data. `card_store` has to hold the same cards and
+    /// products as the context, which resolves their fields.
+    pub fn with_card_store(mut self, card_store: Arc<dyn CardStore>) -> Self {
+        self.card_store = card_store;
+        self
+    }
+
+    pub fn card_store(&self) -> &dyn CardStore {
+        self.card_store.as_ref()
+    }
+
+    pub fn with_campaign_logs(mut self, campaign_logs: Arc<CampaignLogs>) -> Self {
+        self.campaign_logs = campaign_logs;
+        self
+    }
+
+    pub fn campaign_logs(&self) -> &CampaignLogs {
+        &self.campaign_logs
+    }
+
+    pub fn with_plays(mut self, plays: Arc<Plays>) -> Self {
+        self.plays = plays;
+        self
+    }
+
+    pub fn plays(&self) -> &Plays {
+        &self.plays
+    }
+
+    /// Publishes data changes made through this context to `events`
+    pub fn with_events(mut self, events: broadcast::Sender<DataEvent>) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    pub fn events(&self) -> Option<&broadcast::Sender<DataEvent>> {
+        self.events.as_ref()
+    }
+
+    pub fn publish(&self, event: DataEvent) {
+        if let Some(events) = &self.events {
+            // there's nothing to do when nobody is subscribed
+            let _ = events.send(event);
+        }
+    }
+
+    pub fn product_id(&self, code: impl AsRef<str>) -> Option<ProductId> {
+        self.products_index.get(code.as_ref()).copied()
+    }
+
+    pub fn product_by_id(&self, id: ProductId) -> &Product {
+        &self.products[id.0]
     }
 
     pub fn product(&self, code: impl AsRef<str>) -> Option<&Product> {
-        self.products_index.get(code.as_ref())
+        self.product_id(code).map(|id| self.product_by_id(id))
     }
 
-    pub fn set(&self, name: impl AsRef<str>) -> Option<&ProductSet> {
-        self.sets_index.get(name.as_ref())
+    /// Id of the set with the code `code` in the product with the code `product`
+    pub fn set_id(&self, product: impl AsRef<str>, code: impl AsRef<str>) -> Option<SetId> {
+        self.sets_index
+            .get(product.as_ref())
+            .and_then(|sets| sets.get(code.as_ref()))
+            .copied()
+    }
+
+    pub fn set_by_id(&self, id: SetId) -> &ProductSet {
+        &self.product_by_id(id.product).sets[id.set]
+    }
+
+    pub fn set(&self, product: impl AsRef<str>, code: impl AsRef<str>) -> Option<&ProductSet> {
+        self.set_id(product, code).map(|id| self.set_by_id(id))
+    }
+
+    /// The encounter set named `name`, from the earliest product when it's reprinted. Other sets
+    /// share names, like a hero's signature set and a nemesis set, so they're found by code.
+    pub fn set_by_name(&self, name: impl AsRef<str>) -> Option<&ProductSet> {
+        self.set_names_index
+            .get(name.as_ref())
+            .and_then(|ids| ids.first())
+            .map(|&id| self.set_by_id(id))
+    }
+
+    /// The encounter set named `name` in the product with the code `product`, or from the
+    /// earliest product when that one doesn't have it
+    pub fn set_by_name_in(
+        &self,
+        product: impl AsRef<str>,
+        name: impl AsRef<str>,
+    ) -> Option<&ProductSet> {
+        let ids = self.set_names_index.get(name.as_ref())?;
+        let id = match self.product_id(product) {
+            Some(product) => ids.iter().find(|id| id.product == product),
+            None => None,
+        };
+
+        id.or_else(|| ids.first()).map(|&id| self.set_by_id(id))
+    }
+
+    /// Sets of every product, in product order
+    pub fn sets(&self) -> impl Iterator<Item = &ProductSet> {
+        self.products.iter().flat_map(|product| product.sets.iter())
+    }
+
+    pub fn r#trait(&self, name: impl AsRef<str>) -> Option<&Trait> {
+        self.traits_index
+            .get(name.as_ref())
+            .map(|&index| &self.traits[index])
+    }
+
+    /// Number of cards with a side of the trait named `name`
+    pub fn trait_cards_count(&self, name: impl AsRef<str>) -> usize {
+        self.trait_counts.get(name.as_ref()).copied().unwrap_or(0)
+    }
+
+    /// The trait named `name`, or an error suggesting the closest trait when there's none
+    pub fn known_trait(&self, name: impl AsRef<str>) -> Result<&Trait, TraitError> {
+        let name = name.as_ref();
+
+        self.r#trait(name).ok_or_else(|| TraitError::Undefined {
+            name: String::from(name),
+            suggestion: r#trait::suggest(name, &self.traits).map(|r#trait| r#trait.name.clone()),
+        })
+    }
+
+    pub fn scenario(&self, name: impl AsRef<str>) -> Option<&Scenario> {
+        self.scenarios
+            .iter()
+            .find(|scenario| scenario.name == name.as_ref())
+    }
+
+    pub fn campaign(&self, name: impl AsRef<str>) -> Option<&Campaign> {
+        self.campaigns
+            .iter()
+            .find(|campaign| campaign.name == name.as_ref())
+    }
+
+    /// Finds a card by the code of any of its printings, i.e. MC01en-1
+    pub fn card(&self, code: impl AsRef<str>) -> Option<&Card> {
+        self.cards_index
+            .get(code.as_ref())
+            .map(|&index| self.cards[index].as_ref())
+    }
+
+    /// Cards from the card store matching `input`. Only the candidates the store finds for the
+    /// input's keys are checked.
+    pub fn filter_cards(&self, input: &CardInput) -> Result<Vec<Arc<Card>>, CardStoreError> {
+        let cards = self.card_store.candidates(&Plan::new(input))?;
+
+        Ok(cards
+            .into_iter()
+            .filter(|card| card.included(input, self))
+            .collect())
+    }
+
+    /// Cards in the signature set of the hero named `hero`
+    pub fn signature(&self, hero: impl AsRef<str>) -> impl Iterator<Item = &Card> {
+        self.signatures_index
+            .get(hero.as_ref())
+            .into_iter()
+            .flatten()
+            .map(|&index| self.cards[index].as_ref())
+    }
+
+    /// The nemesis set of the hero named `hero`, from the earliest product when several have one
+    pub fn nemesis_set(&self, hero: impl AsRef<str>) -> Option<&ProductSet> {
+        self.nemeses_index
+            .get(hero.as_ref())
+            .map(|&id| self.set_by_id(id))
+    }
+
+    /// Identity of the card `card` is a printing of, following `reprint_of`
+    pub fn identity(&self, card: &Card) -> String {
+        card.reprint_of
+            .as_ref()
+            .and_then(|code| self.card(code))
+            .unwrap_or(card)
+            .identity()
+    }
+
+    /// Every card printing the same card as `card`, the original first
+    pub fn printings(&self, card: &Card) -> Vec<&Card> {
+        self.printings_index
+            .get(&self.identity(card))
+            .map(|indexes| {
+                indexes
+                    .iter()
+                    .map(|&index| self.cards[index].as_ref())
+                    .collect()
+            })
+            .unwrap_or_default()
     }
 }
 
@@ -115,90 +477,221 @@ pub struct Query;
 
 #[graphql_object(Context = Ctx, Scalar = SHQScalarValue)]
 impl Query {
-    fn products(context: &Ctx, r#where: Option<ProductInput>) -> FieldResult<Vec<&Product>> {
-        let products = &context.products;
+    fn products(context: &Ctx, r#where: Option<ProductInput>) -> FieldResult<Vec<Arc<Product>>> {
+        let products = context.card_store.products()?;
 
         if let Some(r#where) = r#where {
             Ok(products
                 .into_iter()
-                .filter(|product| {
-                    let mut filter = true;
-
-                    filter!(filter,
-                        &product.name => r#where.name,
-                        &product.release_date => r#where.release_date,
-                        &product.r#type => r#where.r#type,
-                        &product.code => r#where.code,
-                        &product.wave => r#where.wave
-                    );
-                    if let Some(input_sets) = &r#where.sets {
-                        filter = filter
-                            && !input_sets
-                                .iter()
-                                .filter(|input_set| {
-                                    product
-                                        .sets
-                                        .iter()
-                                        .any(|product_set| product_set.included(input_set))
-                                })
-                                .collect::<Vec<_>>()
-                                .is_empty();
-                    }
-
-                    filter
-                })
+                .filter(|product| product.included(&r#where))
                 .collect())
         } else {
-            Ok(products.into_iter().collect())
+            Ok(products)
         }
     }
 
-    fn cards(context: &Ctx, r#where: Option<CardInput>) -> FieldResult<Vec<&Card>> {
-        let cards = &context.cards;
-
-        if let Some(r#where) = r#where {
-            Ok(cards
-                .into_iter()
-                .filter(|card| {
-                    let mut filter = true;
-
-                    filter!(filter, &card.aspect => r#where.aspect);
-
-                    if let Some(products) = &r#where.products {
-                        filter = filter
-                            && !card
-                                .products
-                                .iter()
-                                .filter(|card_product| {
-                                    products.iter().any(|input_product| {
-                                        card_product.included(input_product, context)
-                                    })
-                                })
-                                .collect::<Vec<_>>()
-                                .is_empty();
-                    }
-                    if let Some(sides) = &r#where.sides {
-                        filter = filter
-                            && !card
-                                .sides
-                                .iter()
-                                .filter(|card_side| {
-                                    sides
-                                        .iter()
-                                        .any(|input_side| card_side.included(input_side))
-                                })
-                                .collect::<Vec<_>>()
-                                .is_empty();
-                    }
+    /// Cards matching `where`. With `distinct`, reprints are collapsed into the earliest
+    /// matching printing.
+    fn cards(
+        context: &Ctx,
+        r#where: Option<CardInput>,
+        distinct: Option<bool>,
+    ) -> FieldResult<Vec<Arc<Card>>> {
+        let cards = match r#where {
+            Some(r#where) => {
+                r#where.check_traits(context)?;
+                context.filter_cards(&r#where)?
+            }
+            None => context.card_store.cards()?,
+        };
 
-                    filter
-                })
-                .collect())
+        if distinct == Some(true) {
+            Ok(distinct_cards(context, cards))
         } else {
-            Ok(cards.into_iter().collect())
+            Ok(cards)
+        }
+    }
+
+    /// Every way to pay for the card `target` by discarding cards from `hand`, both given by card
+    /// code, with the least valuable discards first. `x` is the X to pay for a card that costs X.
+    fn payments(
+        context: &Ctx,
+        hand: Vec<String>,
+        target: String,
+        x: Option<i32>,
+    ) -> FieldResult<Vec<Payment>> {
+        let card = |code: &String| {
+            context
+                .card(code)
+                .ok_or_else(|| PaymentError::UnknownCard(code.clone()))
+        };
+        let hand = hand.iter().map(card).collect::<Result<Vec<_>, _>>()?;
+        let target = card(&target)?;
+        let x = x
+            .map(|x| u32::try_from(x).map_err(|_| PaymentError::NegativeX(x)))
+            .transpose()?;
+
+        Ok(game::payments(&hand, target, x)?)
+    }
+
+    /// Statistics of a deck list, such as its cost curve and the resources it generates
+    fn deck_stats(context: &Ctx, cards: Vec<DeckCard>) -> FieldResult<DeckStats> {
+        Ok(deck::stats(&Deck::new(&cards, context)?))
+    }
+
+    /// Odds of drawing at least `copies` (1 by default) cards matching `targets` by each of the
+    /// first `turns` turns (1 by default), with the hand size of `hero` in `form`, up to the turn
+    /// that draws the whole deck. Heroes start in alter-ego form (side B), which is the default.
+    fn draw_odds(
+        context: &Ctx,
+        cards: Vec<DeckCard>,
+        hero: String,
+        form: Option<Side>,
+        targets: DrawTargets,
+        copies: Option<i32>,
+        turns: Option<i32>,
+    ) -> FieldResult<Vec<DrawOdds>> {
+        let deck = Deck::new(&cards, context)?;
+        let hand_size = odds::hand_size(context, &hero, &form.unwrap_or(Side::B))?;
+        let copies = odds::positive("copies", copies.unwrap_or(1))?;
+        let turns = odds::positive("turns", turns.unwrap_or(1))?;
+
+        Ok(deck::draw_odds(
+            &deck, hand_size, &targets, copies, turns, context,
+        )?)
+    }
+
+    fn scenarios(context: &Ctx, name: Option<String>, product: Option<String>) -> Vec<&Scenario> {
+        context
+            .scenarios
+            .iter()
+            .filter(|scenario| {
+                let mut filter = true;
+
+                filter!(filter,
+                    &scenario.name => name,
+                    &scenario.product => product
+                );
+
+                filter
+            })
+            .collect()
+    }
+
+    fn campaigns(context: &Ctx) -> &Vec<Campaign> {
+        &context.campaigns
+    }
+
+    fn campaign_logs(context: &Ctx, name: Option<String>) -> Vec<CampaignLog> {
+        match name {
+            Some(name) => context.campaign_logs.get(&name).into_iter().collect(),
+            None => context.campaign_logs.all(),
         }
     }
+
+    /// Logged plays, optionally only those with the hero `hero` or against the villain `villain`
+    fn plays(context: &Ctx, hero: Option<String>, villain: Option<String>) -> Vec<Play> {
+        context
+            .plays
+            .all()
+            .into_iter()
+            .filter(|play| {
+                let mut filter = true;
+
+                filter!(filter, &play.villain => villain);
+                if let Some(hero) = &hero {
+                    filter = filter && play.heroes.iter().any(|played| &played.hero == hero);
+                }
+
+                filter
+            })
+            .collect()
+    }
+
+    /// Win rates of the logged plays per hero, aspect, villain or modular set
+    fn win_rates(context: &Ctx, by: WinRateGroup) -> Vec<WinRate> {
+        play::win_rates(&context.plays.all(), by, context)
+    }
+
+    fn traits(context: &Ctx, slug: Option<String>) -> FieldResult<Vec<&Trait>> {
+        Ok(context
+            .traits
+            .iter()
+            .filter(|r#trait| {
+                let mut filter = true;
+
+                filter!(filter, &r#trait.slug => slug);
+
+                filter
+            })
+            .collect())
+    }
 }
 
-pub type Schema =
-    RootNode<'static, Query, EmptyMutation<Ctx>, EmptySubscription<Ctx>, SHQScalarValue>;
+/// Keeps only the earliest printing of each card in `cards`
+fn distinct_cards(context: &Ctx, cards: Vec<Arc<Card>>) -> Vec<Arc<Card>> {
+    let codes: HashSet<_> = cards.iter().filter_map(|card| card.code()).collect();
+
+    cards
+        .into_iter()
+        .filter(|card| {
+            context
+                .printings(card)
+                .into_iter()
+                .find(|printing| printing.code().is_some_and(|code| codes.contains(&code)))
+                .is_none_or(|first| first.is_same_printing(card))
+        })
+        .collect()
+}
+
+pub struct Mutation;
+
+#[graphql_object(Context = Ctx, Scalar = SHQScalarValue)]
+impl Mutation {
+    /// Starts the campaign log `name` for `campaign`, played with `heroes` by card code
+    fn start_campaign(
+        context: &Ctx,
+        name: String,
+        campaign: String,
+        heroes: Vec<String>,
+    ) -> FieldResult<CampaignLog> {
+        let log = CampaignLog::new(name, campaign, heroes, context)?;
+
+        Ok(context.campaign_logs.start(log)?)
+    }
+
+    /// Completes the next scenario of the campaign in `log`
+    fn complete_scenario(context: &Ctx, log: String, scenario: String) -> FieldResult<CampaignLog> {
+        Ok(context
+            .campaign_logs
+            .update(&log, |log| log.complete(scenario, context))?)
+    }
+
+    fn add_upgrade(context: &Ctx, log: String, upgrade: UpgradeInput) -> FieldResult<CampaignLog> {
+        Ok(context
+            .campaign_logs
+            .update(&log, |log| log.add_upgrade(upgrade))?)
+    }
+
+    fn set_counter(
+        context: &Ctx,
+        log: String,
+        name: String,
+        value: i32,
+    ) -> FieldResult<CampaignLog> {
+        Ok(context.campaign_logs.update(&log, |log| {
+            log.set_counter(name, value);
+            Ok::<_, CampaignError>(())
+        })?)
+    }
+
+    fn log_play(context: &Ctx, play: PlayInput) -> FieldResult<Play> {
+        Ok(context.plays.log(Play::new(play, context)?)?)
+    }
+}
+
+pub type Schema = RootNode<'static, Query, Mutation, Subscription, SHQScalarValue>;
+
+pub fn schema() -> Schema {
+    RootNode::new_with_scalar_value(Query, Mutation, Subscription)
+}
@@ -0,0 +1,351 @@
+use crate::{
+    card::Card,
+    graphql::{Ctx, SHQScalarValue},
+    product::Product,
+};
+use juniper::{
+    futures::{stream, Stream},
+    graphql_subscription,
+    marker::IsOutputType,
+    meta::MetaType,
+    BoxFuture, ExecutionResult, Executor, GraphQLObject, GraphQLType, GraphQLValue,
+    GraphQLValueAsync, Registry, Selection,
+};
+use std::{pin::Pin, sync::Arc};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+/// How many events a slow subscriber can fall behind before it starts missing them
+pub const EVENTS_CAPACITY: usize = 256;
+
+/// A change to the data, published to subscribers
+#[derive(Clone)]
+pub enum DataEvent {
+    CardChanged(Published<Card>),
+    /// Code of a card that's no longer in the data
+    CardRemoved(String),
+    ProductChanged(Published<Product>),
+    /// Code of a product that's no longer in the data
+    ProductRemoved(String),
+    DataReloaded(DataReload),
+}
+
+/// A card or product with the context it was published from, which its fields resolve against in place of
+/// the context of the subscription
+pub struct Published<T> {
+    pub item: Arc<T>,
+    pub ctx: Arc<Ctx>,
+}
+
+impl<T> Published<T> {
+    pub fn new(item: Arc<T>, ctx: Arc<Ctx>) -> Self {
+        Self { item, ctx }
+    }
+}
+
+impl<T> Clone for Published<T> {
+    fn clone(&self) -> Self {
+        Self::new(Arc::clone(&self.item), Arc::clone(&self.ctx))
+    }
+}
+
+impl<T> GraphQLType<SHQScalarValue> for Published<T>
+where
+    T: GraphQLType<SHQScalarValue, Context = Ctx, TypeInfo = ()>,
+{
+    fn name(info: &()) -> Option<&str> {
+        T::name(info)
+    }
+
+    fn meta<'r>(
+        info: &(),
+        registry: &mut Registry<'r, SHQScalarValue>,
+    ) -> MetaType<'r, SHQScalarValue>
+    where
+        SHQScalarValue: 'r,
+    {
+        T::meta(info, registry)
+    }
+}
+
+impl<T> GraphQLValue<SHQScalarValue> for Published<T>
+where
+    T: GraphQLValue<SHQScalarValue, Context = Ctx, TypeInfo = ()>,
+{
+    type Context = Ctx;
+    type TypeInfo = ();
+
+    fn type_name<'i>(&self, info: &'i ()) -> Option<&'i str> {
+        self.item.type_name(info)
+    }
+
+    fn resolve(
+        &self,
+        info: &(),
+        selection_set: Option<&[Selection<SHQScalarValue>]>,
+        executor: &Executor<Ctx, SHQScalarValue>,
+    ) -> ExecutionResult<SHQScalarValue> {
+        self.item.resolve(
+            info,
+            selection_set,
+            &executor.replaced_context(self.ctx.as_ref()),
+        )
+    }
+}
+
+impl<T> GraphQLValueAsync<SHQScalarValue> for Published<T>
+where
+    T: GraphQLValueAsync<SHQScalarValue, Context = Ctx, TypeInfo = ()> + Send + Sync,
+{
+    fn resolve_async<'a>(
+        &'a self,
+        info: &'a (),
+        selection_set: Option<&'a [Selection<SHQScalarValue>]>,
+        executor: &'a Executor<Ctx, SHQScalarValue>,
+    ) -> BoxFuture<'a, ExecutionResult<SHQScalarValue>> {
+        Box::pin(async move {
+            let executor = executor.replaced_context(self.ctx.as_ref());
+            self.item
+                .resolve_async(info, selection_set, &executor)
+                .await
+        })
+    }
+}
+
+impl<T: IsOutputType<SHQScalarValue>> IsOutputType<SHQScalarValue> for Published<T> {}
+
+#[derive(Clone, Debug, GraphQLObject, PartialEq)]
+#[graphql(scalar = SHQScalarValue)]
+pub struct DataReload {
+    /// Hash of the data, as used in ETags
+    pub version: String,
+    pub cards_count: i32,
+    pub products_count: i32,
+    pub changed_cards_count: i32,
+    pub changed_products_count: i32,
+}
+
+pub fn channel() -> broadcast::Sender<DataEvent> {
+    broadcast::channel(EVENTS_CAPACITY).0
+}
+
+type EventStream<T> = Pin<Box<dyn Stream<Item = T> + Send>>;
+
+/// Changes to the cards and products, which only change when the data is reloaded
+pub struct Subscription;
+
+#[graphql_subscription(Context = Ctx, scalar = SHQScalarValue)]
+impl Subscription {
+    /// Cards that were added or changed
+    async fn card_changed(context: &Ctx) -> EventStream<Published<Card>> {
+        events(context, |event| match event {
+            DataEvent::CardChanged(card) => Some(card),
+            _ => None,
+        })
+    }
+
+    /// Codes of cards that were removed
+    async fn card_removed(context: &Ctx) -> EventStream<String> {
+        events(context, |event| match event {
+            DataEvent::CardRemoved(code) => Some(code),
+            _ => None,
+        })
+    }
+
+    /// Products that were added or changed
+    async fn product_changed(context: &Ctx) -> EventStream<Published<Product>> {
+        events(context, |event| match event {
+            DataEvent::ProductChanged(product) => Some(product),
+            _ => None,
+        })
+    }
+
+    /// Codes of products that were removed
+    async fn product_removed(context: &Ctx) -> EventStream<String> {
+        events(context, |event| match event {
+            DataEvent::ProductRemoved(code) => Some(code),
+            _ => None,
+        })
+    }
+
+    async fn data_reloaded(context: &Ctx) -> EventStream<DataReload> {
+        events(context, |event| match event {
+            DataEvent::DataReloaded(reload) => Some(reload),
+            _ => None,
+        })
+    }
+}
+
+fn events<T: Send + 'static>(context: &Ctx, select: fn(DataEvent) -> Option<T>) -> EventStream<T> {
+    let receiver = match context.events() {
+        Some(events) => events.subscribe(),
+        None => return Box::pin(stream::empty()),
+    };
+
+    Box::pin(stream::unfold(receiver, move |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(event) => {
+                    if let Some(item) = select(event) {
+                        return Some((item, receiver));
+                    }
+                }
+                // skip the events a slow subscriber missed rather than ending its subscription
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        card::{self, Aspect},
+        graphql::schema,
+        product,
+    };
+    use juniper::{futures::StreamExt, graphql_value, resolve_into_stream, Value, Variables};
+
+    /// A subscriber's context, which holds none of the data
+    fn ctx() -> Ctx {
+        Ctx::default().with_events(channel())
+    }
+
+    /// The data events are published from
+    fn data() -> Arc<Ctx> {
+        let cards: card::Document =
+            toml::from_str(include_str!("../../data/core-set.toml")).unwrap();
+        let products: product::Document =
+            toml::from_str(include_str!("../../data/products.toml")).unwrap();
+
+        Arc::new(Ctx::new(cards.cards, products.products, vec![]))
+    }
+
+    fn basic_card(data: &Arc<Ctx>) -> DataEvent {
+        let card = data
+            .cards
+            .iter()
+            .find(|card| card.aspect == Some(Aspect::Basic))
+            .unwrap();
+
+        DataEvent::CardChanged(Published::new(Arc::clone(card), Arc::clone(data)))
+    }
+
+    /// Subscribes with `query`, publishes `events` and returns the first value received
+    async fn first(ctx: &Ctx, query: &str, events: Vec<DataEvent>) -> Value<SHQScalarValue> {
+        let schema = schema();
+        let (value, errors) = resolve_into_stream(query, None, &schema, &Variables::new(), ctx)
+            .await
+            .unwrap();
+        assert!(errors.is_empty());
+
+        for event in events {
+            ctx.publish(event);
+        }
+        let field = match value {
+            Value::Object(object) => object.into_iter().next().map(|(_, field)| field),
+            _ => None,
+        };
+        match field {
+            Some(Value::Scalar(mut stream)) => stream.next().await.unwrap().unwrap(),
+            _ => panic!("expected a stream"),
+        }
+    }
+
+    fn reload() -> DataReload {
+        DataReload {
+            version: String::from("abc"),
+            cards_count: 42,
+            products_count: 1,
+            changed_cards_count: 1,
+            changed_products_count: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn it_streams_changed_cards() {
+        let data = data();
+        let value = first(
+            &ctx(),
+            "subscription { cardChanged { aspect } }",
+            vec![DataEvent::DataReloaded(reload()), basic_card(&data)],
+        )
+        .await;
+
+        assert_eq!(graphql_value!({ "aspect": "BASIC" }), value);
+    }
+
+    #[tokio::test]
+    async fn it_resolves_events_against_the_data_they_were_published_from() {
+        let data = data();
+        let value = first(
+            &ctx(),
+            "subscription { cardChanged { products { name } } }",
+            vec![basic_card(&data)],
+        )
+        .await;
+
+        assert_eq!(
+            graphql_value!({ "products": [{ "name": "Core Set" }] }),
+            value
+        );
+    }
+
+    #[tokio::test]
+    async fn it_resolves_changed_products_against_the_data_they_were_published_from() {
+        let data = data();
+        let product = Published::new(Arc::clone(&data.products[0]), Arc::clone(&data));
+        let value = first(
+            &ctx(),
+            "subscription { productChanged { code sets { name } } }",
+            vec![DataEvent::ProductChanged(product)],
+        )
+        .await;
+
+        assert_eq!(
+            Some("MC01en"),
+            value.as_object_value().and_then(|product| {
+                product
+                    .get_field_value("code")
+                    .and_then(Value::as_string_value)
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn it_streams_removed_cards() {
+        let value = first(
+            &ctx(),
+            "subscription { cardRemoved }",
+            vec![DataEvent::CardRemoved(String::from("MC01en-1"))],
+        )
+        .await;
+
+        assert_eq!(graphql_value!("MC01en-1"), value);
+    }
+
+    #[tokio::test]
+    async fn it_streams_data_reloads() {
+        let ctx = ctx();
+        let value = first(
+            &ctx,
+            "subscription { dataReloaded { version cardsCount } }",
+            vec![DataEvent::DataReloaded(reload())],
+        )
+        .await;
+
+        assert_eq!(
+            graphql_value!({ "version": "abc", "cardsCount": 42 }),
+            value
+        );
+    }
+
+    #[tokio::test]
+    async fn it_ends_without_events() {
+        let ctx = Ctx::default();
+        let stream = events(&ctx, Some);
+
+        assert!(stream.collect::<Vec<_>>().await.is_empty());
+    }
+}
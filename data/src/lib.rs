@@ -1,3 +1,10 @@
+pub mod campaign;
 pub mod card;
+pub mod card_store;
+pub mod deck;
+pub mod game;
 pub mod graphql;
+pub mod play;
 pub mod product;
+pub mod scenario;
+pub mod store;
@@ -0,0 +1,478 @@
+use crate::{
+    card::{Aspect, Card},
+    graphql::{Ctx, SHQScalarValue},
+    product::{Set as ProductSet, SetType},
+    scenario::DifficultyLevel,
+    store::{Store, StoreError},
+};
+use chrono::NaiveDate;
+use juniper::{graphql_object, GraphQLEnum, GraphQLInputObject};
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp::Reverse,
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum PlayError {
+    #[error("A play needs at least one hero")]
+    NoHeroes,
+    #[error("{0} is not a hero")]
+    NotAHero(String),
+    #[error("{0:?} is not an aspect heroes can play")]
+    NotAnAspect(Aspect),
+    #[error("Could not find set {0}")]
+    UnknownSet(String),
+    #[error("{set} is not a {expected:?} set")]
+    WrongSetType { set: String, expected: SetType },
+    #[error("Plays: {0}")]
+    Store(#[from] StoreError),
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, GraphQLEnum, PartialEq, Serialize)]
+pub enum PlayResult {
+    Win,
+    Loss,
+}
+
+/// A logged game
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Play {
+    pub date: NaiveDate,
+    pub heroes: Vec<PlayHero>,
+    /// Name of the villain set
+    pub villain: String,
+    /// Names of the modular encounter sets
+    #[serde(default)]
+    pub modulars: Vec<String>,
+    pub difficulty: DifficultyLevel,
+    pub result: PlayResult,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct PlayHero {
+    /// Code of the hero card
+    pub hero: String,
+    pub aspects: Vec<Aspect>,
+}
+
+#[derive(GraphQLInputObject)]
+#[graphql(scalar = SHQScalarValue)]
+pub struct PlayInput {
+    pub date: NaiveDate,
+    pub heroes: Vec<PlayHeroInput>,
+    pub villain: String,
+    pub modulars: Option<Vec<String>>,
+    pub difficulty: DifficultyLevel,
+    pub result: PlayResult,
+}
+
+#[derive(GraphQLInputObject)]
+#[graphql(scalar = SHQScalarValue)]
+pub struct PlayHeroInput {
+    pub hero: String,
+    pub aspects: Vec<Aspect>,
+}
+
+#[graphql_object(Context = Ctx, scalar = SHQScalarValue)]
+impl Play {
+    fn date(&self) -> &NaiveDate {
+        &self.date
+    }
+
+    fn heroes(&self) -> &Vec<PlayHero> {
+        &self.heroes
+    }
+
+    fn villain(&self, context: &Ctx) -> Option<&ProductSet> {
+        context.set_by_name(&self.villain)
+    }
+
+    fn modulars(&self, context: &Ctx) -> Vec<&ProductSet> {
+        self.modulars
+            .iter()
+            .filter_map(|name| context.set_by_name(name))
+            .collect()
+    }
+
+    fn difficulty(&self) -> &DifficultyLevel {
+        &self.difficulty
+    }
+
+    fn result(&self) -> PlayResult {
+        self.result
+    }
+}
+
+#[graphql_object(Context = Ctx, scalar = SHQScalarValue)]
+impl PlayHero {
+    fn hero(&self, context: &Ctx) -> Option<&Card> {
+        context.card(&self.hero)
+    }
+
+    fn aspects(&self) -> &Vec<Aspect> {
+        &self.aspects
+    }
+}
+
+impl Play {
+    /// Checks that `input` refers to hero cards and to sets of the right type
+    pub fn new(input: PlayInput, ctx: &Ctx) -> Result<Self, PlayError> {
+        if input.heroes.is_empty() {
+            return Err(PlayError::NoHeroes);
+        }
+        for hero in input.heroes.iter() {
+            if !ctx.card(&hero.hero).is_some_and(Card::is_hero) {
+                return Err(PlayError::NotAHero(hero.hero.clone()));
+            }
+            if let Some(aspect) = hero.aspects.iter().find(|&aspect| aspect == &Aspect::Basic) {
+                return Err(PlayError::NotAnAspect(aspect.clone()));
+            }
+        }
+        let modulars = input.modulars.unwrap_or_default();
+        let check_set = |name: &String, expected: SetType| match ctx.set_by_name(name) {
+            None => Err(PlayError::UnknownSet(name.clone())),
+            Some(set) if set.r#type != expected => Err(PlayError::WrongSetType {
+                set: name.clone(),
+                expected,
+            }),
+            Some(_) => Ok(()),
+        };
+        check_set(&input.villain, SetType::Villain)?;
+        for modular in modulars.iter() {
+            check_set(modular, SetType::ModularEncounter)?;
+        }
+
+        Ok(Self {
+            date: input.date,
+            heroes: input
+                .heroes
+                .into_iter()
+                .map(|hero| PlayHero {
+                    hero: hero.hero,
+                    aspects: hero.aspects,
+                })
+                .collect(),
+            villain: input.villain,
+            modulars,
+            difficulty: input.difficulty,
+            result: input.result,
+        })
+    }
+}
+
+/// Logged plays, saved to a file after every change when they were loaded from one
+#[derive(Debug)]
+pub struct Plays(Store<Play>);
+
+impl Default for Plays {
+    fn default() -> Self {
+        Self(Store::in_memory(KEY))
+    }
+}
+
+/// Name of the tables holding the plays in a plays file
+const KEY: &str = "play";
+
+impl Plays {
+    /// Loads the plays in `path`, which doesn't need to exist yet
+    pub fn load(path: impl Into<PathBuf>) -> Result<Self, PlayError> {
+        Ok(Self(Store::load(KEY, path)?))
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.0.path()
+    }
+
+    pub fn revision(&self) -> u64 {
+        self.0.revision()
+    }
+
+    pub fn all(&self) -> Vec<Play> {
+        self.0.all()
+    }
+
+    pub fn log(&self, play: Play) -> Result<Play, PlayError> {
+        self.0.change(|plays| {
+            plays.push(play.clone());
+
+            Ok(play)
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, GraphQLEnum, PartialEq)]
+pub enum WinRateGroup {
+    Hero,
+    Aspect,
+    Villain,
+    Modular,
+}
+
+/// What a win rate is about
+#[derive(Clone, Debug, PartialEq)]
+pub enum WinRateSubject {
+    /// Code of the earliest printing of the hero
+    Hero(String),
+    Aspect(Aspect),
+    /// Name of the villain or modular set
+    Set(String),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct WinRate {
+    pub subject: WinRateSubject,
+    pub plays: i32,
+    pub wins: i32,
+}
+
+#[graphql_object(Context = Ctx, scalar = SHQScalarValue)]
+impl WinRate {
+    fn name(&self, context: &Ctx) -> String {
+        match &self.subject {
+            WinRateSubject::Hero(code) => context
+                .card(code)
+                .map(|card| card.sides[0].name.clone())
+                .unwrap_or_else(|| code.clone()),
+            WinRateSubject::Aspect(aspect) => format!("{aspect:?}"),
+            WinRateSubject::Set(name) => name.clone(),
+        }
+    }
+
+    fn hero(&self, context: &Ctx) -> Option<&Card> {
+        match &self.subject {
+            WinRateSubject::Hero(code) => context.card(code),
+            _ => None,
+        }
+    }
+
+    fn aspect(&self) -> Option<&Aspect> {
+        match &self.subject {
+            WinRateSubject::Aspect(aspect) => Some(aspect),
+            _ => None,
+        }
+    }
+
+    fn set(&self, context: &Ctx) -> Option<&ProductSet> {
+        match &self.subject {
+            WinRateSubject::Set(name) => context.set_by_name(name),
+            _ => None,
+        }
+    }
+
+    fn plays(&self) -> i32 {
+        self.plays
+    }
+
+    fn wins(&self) -> i32 {
+        self.wins
+    }
+
+    fn win_rate(&self) -> f64 {
+        self.rate()
+    }
+}
+
+impl WinRate {
+    /// Share of the plays that were won, between 0 and 1
+    pub fn rate(&self) -> f64 {
+        if self.plays == 0 {
+            0.0
+        } else {
+            f64::from(self.wins) / f64::from(self.plays)
+        }
+    }
+}
+
+/// Win rates of `plays` grouped by `group`, most played first. A play counts once for every
+/// hero, aspect or set it has, so reprints of a hero count as the same hero.
+pub fn win_rates(plays: &[Play], group: WinRateGroup, ctx: &Ctx) -> Vec<WinRate> {
+    let mut win_rates: Vec<WinRate> = vec![];
+    for play in plays {
+        let subjects: Vec<WinRateSubject> = match group {
+            WinRateGroup::Hero => play
+                .heroes
+                .iter()
+                .map(|hero| {
+                    let code = ctx
+                        .card(&hero.hero)
+                        .and_then(|card| ctx.printings(card).first()?.code())
+                        .unwrap_or_else(|| hero.hero.clone());
+                    WinRateSubject::Hero(code)
+                })
+                .collect(),
+            WinRateGroup::Aspect => play
+                .heroes
+                .iter()
+                .flat_map(|hero| hero.aspects.iter().cloned().map(WinRateSubject::Aspect))
+                .collect(),
+            WinRateGroup::Villain => vec![WinRateSubject::Set(play.villain.clone())],
+            WinRateGroup::Modular => play
+                .modulars
+                .iter()
+                .cloned()
+                .map(WinRateSubject::Set)
+                .collect(),
+        };
+        let mut counted: Vec<WinRateSubject> = vec![];
+
+        for subject in subjects {
+            if counted.contains(&subject) {
+                continue;
+            }
+            let index = match win_rates.iter().position(|rate| rate.subject == subject) {
+                Some(index) => index,
+                None => {
+                    win_rates.push(WinRate {
+                        subject: subject.clone(),
+                        plays: 0,
+                        wins: 0,
+                    });
+                    win_rates.len() - 1
+                }
+            };
+            win_rates[index].plays += 1;
+            if play.result == PlayResult::Win {
+                win_rates[index].wins += 1;
+            }
+            counted.push(subject);
+        }
+    }
+    // stable, so ties stay in the order they were first played
+    win_rates.sort_by_key(|rate| Reverse(rate.plays));
+
+    win_rates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{card, product};
+
+    fn ctx() -> Ctx {
+        let cards: card::Document = toml::from_str(include_str!("../data/core-set.toml")).unwrap();
+        let products: product::Document =
+            toml::from_str(include_str!("../data/products.toml")).unwrap();
+
+        Ctx::new(cards.cards, products.products, vec![])
+    }
+
+    fn input(heroes: &[(&str, Aspect)], modulars: &[&str], result: PlayResult) -> PlayInput {
+        PlayInput {
+            date: NaiveDate::from_ymd_opt(2024, 5, 4).unwrap(),
+            heroes: heroes
+                .iter()
+                .map(|(hero, aspect)| PlayHeroInput {
+                    hero: String::from(*hero),
+                    aspects: vec![aspect.clone()],
+                })
+                .collect(),
+            villain: String::from("Rhino"),
+            modulars: Some(modulars.iter().map(|name| String::from(*name)).collect()),
+            difficulty: DifficultyLevel::Standard,
+            result,
+        }
+    }
+
+    #[test]
+    fn it_validates_plays() {
+        let ctx = ctx();
+        let play = |input| Play::new(input, &ctx);
+
+        assert!(play(input(
+            &[("MC01en-1", Aspect::Justice)],
+            &["Bomb Scare"],
+            PlayResult::Win
+        ))
+        .is_ok());
+        assert!(matches!(
+            play(input(&[], &[], PlayResult::Win)),
+            Err(PlayError::NoHeroes)
+        ));
+        assert!(matches!(
+            play(input(
+                &[("MC01en-2", Aspect::Justice)],
+                &[],
+                PlayResult::Win
+            )),
+            Err(PlayError::NotAHero(_))
+        ));
+        assert!(matches!(
+            play(input(&[("MC01en-1", Aspect::Basic)], &[], PlayResult::Win)),
+            Err(PlayError::NotAnAspect(Aspect::Basic))
+        ));
+        assert!(matches!(
+            play(input(
+                &[("MC01en-1", Aspect::Justice)],
+                &["Rhino"],
+                PlayResult::Win
+            )),
+            Err(PlayError::WrongSetType { .. })
+        ));
+        assert!(matches!(
+            play(input(
+                &[("MC01en-1", Aspect::Justice)],
+                &["Nope"],
+                PlayResult::Win
+            )),
+            Err(PlayError::UnknownSet(_))
+        ));
+    }
+
+    #[test]
+    fn it_computes_win_rates() {
+        let ctx = ctx();
+        let plays: Vec<Play> = [
+            input(
+                &[
+                    ("MC01en-1", Aspect::Justice),
+                    ("MC01en-10", Aspect::Leadership),
+                ],
+                &["Bomb Scare"],
+                PlayResult::Win,
+            ),
+            input(&[("MC01en-1", Aspect::Justice)], &[], PlayResult::Loss),
+            input(&[("MC01en-1", Aspect::Aggression)], &[], PlayResult::Win),
+        ]
+        .into_iter()
+        .map(|input| Play::new(input, &ctx).unwrap())
+        .collect();
+        let rates = |group| -> Vec<(WinRateSubject, i32, i32)> {
+            win_rates(&plays, group, &ctx)
+                .into_iter()
+                .map(|rate| (rate.subject, rate.plays, rate.wins))
+                .collect()
+        };
+
+        assert_eq!(
+            vec![
+                (WinRateSubject::Hero(String::from("MC01en-1")), 3, 2),
+                (WinRateSubject::Hero(String::from("MC01en-10")), 1, 1),
+            ],
+            rates(WinRateGroup::Hero)
+        );
+        assert_eq!(
+            vec![
+                (WinRateSubject::Aspect(Aspect::Justice), 2, 1),
+                (WinRateSubject::Aspect(Aspect::Leadership), 1, 1),
+                (WinRateSubject::Aspect(Aspect::Aggression), 1, 1),
+            ],
+            rates(WinRateGroup::Aspect)
+        );
+        assert_eq!(
+            vec![(WinRateSubject::Set(String::from("Rhino")), 3, 2)],
+            rates(WinRateGroup::Villain)
+        );
+        assert_eq!(
+            vec![(WinRateSubject::Set(String::from("Bomb Scare")), 1, 1)],
+            rates(WinRateGroup::Modular)
+        );
+        assert_eq!(
+            2.0 / 3.0,
+            win_rates(&plays, WinRateGroup::Hero, &ctx)[0].rate()
+        );
+    }
+}
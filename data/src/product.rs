@@ -1,8 +1,13 @@
-use crate::graphql::{filter, SHQScalarValue};
+use crate::{
+    card::Card,
+    graphql::{filter, Ctx, SHQScalarValue},
+};
 use chrono::NaiveDate;
 use derive_builder::Builder;
-use juniper::{graphql_object, GraphQLEnum, GraphQLInputObject, GraphQLObject};
-use serde::Deserialize;
+use juniper::{graphql_object, GraphQLEnum, GraphQLInputObject};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use utoipa::ToSchema;
 
 #[derive(Deserialize)]
 pub struct Document {
@@ -10,7 +15,7 @@ pub struct Document {
     pub products: Vec<Product>,
 }
 
-#[derive(Clone, Deserialize)]
+#[derive(Clone, Deserialize, Serialize, ToSchema)]
 #[serde(deny_unknown_fields)]
 pub struct Product {
     pub name: String,
@@ -18,7 +23,7 @@ pub struct Product {
     pub r#type: ProductType,
     pub code: String,
     pub wave: u32,
-    #[serde(default, rename = "set")]
+    #[serde(default, rename(deserialize = "set"), alias = "sets")]
     pub sets: Vec<Set>,
 }
 
@@ -33,7 +38,29 @@ pub struct ProductInput {
     pub sets: Option<Vec<SetInput>>,
 }
 
-#[graphql_object(scalar = SHQScalarValue)]
+impl Product {
+    pub fn included(&self, input: &ProductInput) -> bool {
+        let mut filter = true;
+
+        filter!(filter,
+            &self.name => input.name,
+            &self.release_date => input.release_date,
+            &self.r#type => input.r#type,
+            &self.code => input.code,
+            &self.wave => input.wave
+        );
+        if let Some(input_sets) = &input.sets {
+            filter = filter
+                && input_sets
+                    .iter()
+                    .any(|input_set| self.sets.iter().any(|set| set.included(input_set)));
+        }
+
+        filter
+    }
+}
+
+#[graphql_object(Context = Ctx, scalar = SHQScalarValue)]
 impl Product {
     fn name(&self) -> &str {
         &self.name
@@ -55,7 +82,12 @@ impl Product {
         self.wave
     }
 
-    fn sets(&self, name: Option<String>, r#type: Option<SetType>) -> Vec<&Set> {
+    fn sets(
+        &self,
+        code: Option<String>,
+        name: Option<String>,
+        r#type: Option<SetType>,
+    ) -> Vec<&Set> {
         let sets: Vec<&Set> = self.sets.iter().collect();
 
         sets.into_iter()
@@ -63,6 +95,7 @@ impl Product {
                 let mut filter = true;
 
                 filter!(filter,
+                    &set.code => code,
                     &set.name => name,
                     &set.r#type => r#type
                 );
@@ -73,7 +106,7 @@ impl Product {
     }
 }
 
-#[derive(Clone, Deserialize, GraphQLEnum, PartialEq)]
+#[derive(Clone, Debug, Deserialize, GraphQLEnum, PartialEq, Serialize, ToSchema)]
 pub enum ProductType {
     #[serde(rename = "Core Set")]
     CoreSet,
@@ -88,22 +121,96 @@ pub enum ProductType {
 
 #[derive(Builder, Clone, GraphQLInputObject)]
 pub struct SetInput {
+    #[builder(default)]
+    pub code: Option<String>,
     #[builder(default)]
     pub name: Option<String>,
     #[builder(default)]
     pub r#type: Option<SetType>,
 }
 
-#[derive(Clone, Deserialize, GraphQLObject)]
+#[derive(Clone, Deserialize, Serialize, ToSchema)]
+#[serde(from = "SetData")]
 pub struct Set {
+    /// Identifies the set within its product
+    pub code: String,
     pub name: String,
     pub r#type: SetType,
+    /// Name of the hero the set belongs to, when it doesn't follow the naming conventions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hero: Option<String>,
+}
+
+/// A set as written in the data, where the code defaults to the name in kebab case
+#[derive(Deserialize)]
+struct SetData {
+    code: Option<String>,
+    name: String,
+    r#type: SetType,
+    #[serde(default)]
+    hero: Option<String>,
+}
+
+impl From<SetData> for Set {
+    fn from(data: SetData) -> Self {
+        Self {
+            code: data.code.unwrap_or_else(|| slug(&data.name)),
+            name: data.name,
+            r#type: data.r#type,
+            hero: data.hero,
+        }
+    }
+}
+
+/// `name` in lower case, with every run of other characters than letters and digits as a dash
+fn slug(name: &str) -> String {
+    name.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join("-")
 }
 
+#[graphql_object(scalar = SHQScalarValue)]
 impl Set {
+    /// Identifies the set within its product
+    fn code(&self) -> &str {
+        &self.code
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn r#type(&self) -> &SetType {
+        &self.r#type
+    }
+
+    /// Name of the hero whose signature or nemesis set this is
+    fn hero(&self) -> Option<&str> {
+        self.hero()
+    }
+}
+
+impl Set {
+    /// Name of the hero whose signature or nemesis set this is. Hero signature sets are named
+    /// after their hero and nemesis sets are named "<hero> Nemesis", unless `hero` is given.
+    pub fn hero(&self) -> Option<&str> {
+        if let Some(hero) = &self.hero {
+            return Some(hero);
+        }
+
+        match self.r#type {
+            SetType::HeroSignature => Some(&self.name),
+            SetType::Nemesis => self.name.strip_suffix(" Nemesis"),
+            _ => None,
+        }
+    }
+
     pub fn included(&self, input: &SetInput) -> bool {
         let mut filter = true;
         filter!(filter,
+            &self.code => input.code,
             &self.name => input.name,
             &self.r#type => input.r#type
         );
@@ -112,7 +219,7 @@ impl Set {
     }
 }
 
-#[derive(Clone, Deserialize, GraphQLEnum, PartialEq)]
+#[derive(Clone, Debug, Deserialize, GraphQLEnum, PartialEq, Serialize, ToSchema)]
 pub enum SetType {
     #[serde(rename = "Hero Signature")]
     HeroSignature,
@@ -120,6 +227,76 @@ pub enum SetType {
     ModularEncounter,
     Nemesis,
     Villain,
+    /// Encounter cards added to every scenario on standard difficulty
+    Standard,
+    /// Encounter cards added to every scenario on expert difficulty
+    Expert,
+}
+
+impl SetType {
+    /// Whether scenarios and plays refer to sets of this type by their name
+    pub fn is_named(&self) -> bool {
+        matches!(
+            self,
+            SetType::ModularEncounter | SetType::Villain | SetType::Standard | SetType::Expert
+        )
+    }
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum SetError {
+    #[error("{product} defines the set {code} more than once, give each of them a distinct code")]
+    Duplicate { product: String, code: String },
+    #[error("{card}: {product} has no set {code}")]
+    Unknown {
+        card: String,
+        product: String,
+        code: String,
+    },
+}
+
+/// Checks that set codes are unique within their product and that every card set refers to a set
+/// of its product
+pub fn validate(products: &[Product], cards: &[Card]) -> Result<(), Vec<SetError>> {
+    let mut errors = vec![];
+
+    for product in products.iter() {
+        for (i, set) in product.sets.iter().enumerate() {
+            if product.sets[..i].iter().any(|other| other.code == set.code) {
+                errors.push(SetError::Duplicate {
+                    product: product.code.clone(),
+                    code: set.code.clone(),
+                });
+            }
+        }
+    }
+
+    for card in cards.iter() {
+        for card_product in card.products.iter() {
+            // unknown products are left to the card data checks
+            let Some(product) = products
+                .iter()
+                .find(|product| product.code == card_product.code)
+            else {
+                continue;
+            };
+            for card_set in card_product.sets.iter().flatten() {
+                if !product.sets.iter().any(|set| set.code == card_set.code) {
+                    errors.push(SetError::Unknown {
+                        card: card.code().unwrap_or_default(),
+                        product: product.code.clone(),
+                        code: card_set.code.clone(),
+                    });
+                }
+            }
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
 }
 
 #[cfg(test)]
@@ -132,4 +309,194 @@ mod tests {
 
         assert!(document.is_ok());
     }
+
+    #[test]
+    fn it_finds_the_hero_of_a_set() {
+        let set = |name: &str, r#type, hero: Option<&str>| Set {
+            code: slug(name),
+            name: String::from(name),
+            r#type,
+            hero: hero.map(String::from),
+        };
+
+        assert_eq!(
+            Some("Spider-Man"),
+            set("Spider-Man", SetType::HeroSignature, None).hero()
+        );
+        assert_eq!(
+            Some("Spider-Man"),
+            set("Spider-Man Nemesis", SetType::Nemesis, None).hero()
+        );
+        assert_eq!(
+            Some("Spider-Man"),
+            set("Vulture", SetType::Nemesis, Some("Spider-Man")).hero()
+        );
+        assert_eq!(None, set("Rhino", SetType::Villain, None).hero());
+    }
+
+    fn products(source: &str) -> Vec<Product> {
+        toml::from_str::<Document>(source).unwrap().products
+    }
+
+    #[test]
+    fn it_defaults_set_codes_to_the_name() {
+        let products = products(include_str!("../data/products.toml"));
+        let codes: Vec<&str> = products[0]
+            .sets
+            .iter()
+            .map(|set| set.code.as_str())
+            .collect();
+
+        assert!(codes.contains(&"spider-man"));
+        assert!(codes.contains(&"spider-man-nemesis"));
+        assert_eq!("s-h-i-e-l-d", slug("S.H.I.E.L.D."));
+    }
+
+    #[test]
+    fn it_validates_sets() {
+        let cards: crate::card::Document =
+            toml::from_str(include_str!("../data/core-set.toml")).unwrap();
+
+        assert_eq!(
+            Ok(()),
+            validate(
+                &products(include_str!("../data/products.toml")),
+                &cards.cards
+            )
+        );
+    }
+
+    #[test]
+    fn it_rejects_sets_defined_twice() {
+        let product = |sets: &str| {
+            format!(
+                r#"
+                [[product]]
+                name = "Core Set"
+                release_date = "2019-11-01"
+                type = "Core Set"
+                code = "MC01en"
+                wave = 1
+                {sets}
+                "#
+            )
+        };
+        let twice = product(
+            r#"
+            [[product.set]]
+            name = "Spider-Man"
+            type = "Hero Signature"
+
+            [[product.set]]
+            name = "Spider-Man"
+            type = "Nemesis"
+            "#,
+        );
+        let disambiguated = product(
+            r#"
+            [[product.set]]
+            name = "Spider-Man"
+            type = "Hero Signature"
+
+            [[product.set]]
+            code = "spider-man-nemesis"
+            name = "Spider-Man"
+            type = "Nemesis"
+            "#,
+        );
+
+        assert_eq!(
+            Err(vec![SetError::Duplicate {
+                product: String::from("MC01en"),
+                code: String::from("spider-man"),
+            }]),
+            validate(&products(&twice), &[])
+        );
+        assert_eq!(Ok(()), validate(&products(&disambiguated), &[]));
+    }
+
+    #[test]
+    fn it_allows_reprinted_sets() {
+        let products = products(
+            r#"
+            [[product]]
+            name = "Core Set"
+            release_date = "2019-11-01"
+            type = "Core Set"
+            code = "MC01en"
+            wave = 1
+
+            [[product.set]]
+            name = "Bomb Scare"
+            type = "Modular Encounter"
+
+            [[product.set]]
+            name = "Spider-Man"
+            type = "Hero Signature"
+
+            [[product]]
+            name = "Core Set Reprint"
+            release_date = "2020-01-01"
+            type = "Custom"
+            code = "MC99en"
+            wave = 1
+
+            [[product.set]]
+            name = "Bomb Scare"
+            type = "Modular Encounter"
+
+            [[product.set]]
+            name = "Spider-Man"
+            type = "Nemesis"
+            "#,
+        );
+
+        assert_eq!(Ok(()), validate(&products, &[]));
+
+        let ctx = Ctx::new(vec![], products, vec![]);
+        let reprint = ctx.set("MC99en", "bomb-scare").unwrap();
+        let original = ctx.set("MC01en", "bomb-scare").unwrap();
+        assert!(std::ptr::eq(
+            original,
+            ctx.set_by_name("Bomb Scare").unwrap()
+        ));
+        assert!(std::ptr::eq(
+            reprint,
+            ctx.set_by_name_in("MC99en", "Bomb Scare").unwrap()
+        ));
+        assert!(std::ptr::eq(
+            original,
+            ctx.set_by_name_in("MC02en", "Bomb Scare").unwrap()
+        ));
+    }
+
+    #[test]
+    fn it_rejects_card_sets_missing_from_their_product() {
+        let cards: crate::card::Document = toml::from_str(
+            r#"
+            [[card]]
+            [[card.product]]
+            code = "MC01en"
+            positions = [1]
+            [[card.product.set]]
+            code = "spider-woman"
+            [[card.side]]
+            type = "Obligation"
+            name = "Eviction Notice"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            Err(vec![SetError::Unknown {
+                card: String::from("MC01en-1"),
+                product: String::from("MC01en"),
+                code: String::from("spider-woman"),
+            }]),
+            validate(
+                &products(include_str!("../data/products.toml")),
+                &cards.cards
+            )
+        );
+    }
 }
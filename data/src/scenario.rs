@@ -0,0 +1,402 @@
+use crate::{
+    card::{BasicPower, HitPoints},
+    graphql::{Ctx, SHQScalarValue},
+    product::{Product, ProductType, Set as ProductSet, SetType},
+};
+use juniper::{graphql_object, GraphQLEnum, GraphQLObject};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Document {
+    #[serde(rename = "scenario", default)]
+    pub scenarios: Vec<Scenario>,
+    #[serde(rename = "campaign", default)]
+    pub campaigns: Vec<Campaign>,
+}
+
+/// How to set up a villain's scenario. Per player values use the same format as hit points,
+/// i.e. "7:player:".
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    pub name: String,
+    /// Code of the product the scenario is in
+    pub product: String,
+    pub villain_set: String,
+    #[serde(default)]
+    pub recommended_modulars: Vec<String>,
+    #[serde(rename(deserialize = "difficulty"))]
+    pub difficulties: Vec<Difficulty>,
+    #[serde(rename(deserialize = "villain_stage"))]
+    pub villain_stages: Vec<VillainStage>,
+    #[serde(rename(deserialize = "main_scheme"))]
+    pub main_schemes: Vec<MainSchemeStage>,
+    /// Special setup instructions
+    pub setup: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, GraphQLEnum, PartialEq, Serialize)]
+pub enum DifficultyLevel {
+    Standard,
+    Expert,
+}
+
+#[derive(Clone, Deserialize, GraphQLObject, Serialize)]
+#[graphql(scalar = SHQScalarValue)]
+#[serde(deny_unknown_fields)]
+pub struct Difficulty {
+    pub level: DifficultyLevel,
+    /// Encounter sets added on this difficulty
+    pub sets: Vec<String>,
+    /// Villain stages used on this difficulty, in order
+    pub villain_stages: Vec<String>,
+}
+
+#[derive(Clone, Deserialize, GraphQLObject, Serialize)]
+#[graphql(scalar = SHQScalarValue)]
+#[serde(deny_unknown_fields)]
+pub struct VillainStage {
+    pub stage: String,
+    pub sch: BasicPower,
+    pub atk: BasicPower,
+    pub hit_points: HitPoints,
+    pub text: Option<String>,
+}
+
+#[derive(Clone, Deserialize, GraphQLObject, Serialize)]
+#[graphql(scalar = SHQScalarValue)]
+#[serde(deny_unknown_fields)]
+pub struct MainSchemeStage {
+    /// Stage and side, i.e. 1A
+    pub stage: String,
+    pub name: String,
+    pub starting_threat: HitPoints,
+    /// Threat placed at the start of every villain phase
+    pub threat_per_round: HitPoints,
+    pub threat_limit: HitPoints,
+    pub text: Option<String>,
+}
+
+/// The scenarios of a campaign expansion, played in order
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Campaign {
+    pub name: String,
+    pub product: String,
+    pub scenarios: Vec<String>,
+}
+
+#[graphql_object(Context = Ctx, scalar = SHQScalarValue)]
+impl Scenario {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn product(&self, context: &Ctx) -> Option<&Product> {
+        context.product(&self.product)
+    }
+
+    fn villain_set(&self, context: &Ctx) -> Option<&ProductSet> {
+        context.set_by_name_in(&self.product, &self.villain_set)
+    }
+
+    fn recommended_modulars(&self, context: &Ctx) -> Vec<&ProductSet> {
+        self.recommended_modulars
+            .iter()
+            .filter_map(|name| context.set_by_name_in(&self.product, name))
+            .collect()
+    }
+
+    fn difficulties(&self) -> &Vec<Difficulty> {
+        &self.difficulties
+    }
+
+    fn villain_stages(&self, difficulty: Option<DifficultyLevel>) -> Vec<&VillainStage> {
+        match difficulty {
+            Some(level) => self.stages(&level),
+            None => self.villain_stages.iter().collect(),
+        }
+    }
+
+    fn main_schemes(&self) -> &Vec<MainSchemeStage> {
+        &self.main_schemes
+    }
+
+    fn setup(&self) -> Option<&String> {
+        self.setup.as_ref()
+    }
+}
+
+impl Scenario {
+    pub fn difficulty(&self, level: &DifficultyLevel) -> Option<&Difficulty> {
+        self.difficulties
+            .iter()
+            .find(|difficulty| &difficulty.level == level)
+    }
+
+    /// Villain stages used on the difficulty `level`, in order
+    pub fn stages(&self, level: &DifficultyLevel) -> Vec<&VillainStage> {
+        self.difficulty(level)
+            .map(|difficulty| {
+                difficulty
+                    .villain_stages
+                    .iter()
+                    .filter_map(|stage| self.villain_stages.iter().find(|s| &s.stage == stage))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+#[graphql_object(Context = Ctx, scalar = SHQScalarValue)]
+impl Campaign {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn product(&self, context: &Ctx) -> Option<&Product> {
+        context.product(&self.product)
+    }
+
+    fn scenarios(&self, context: &Ctx) -> Vec<&Scenario> {
+        self.scenarios
+            .iter()
+            .filter_map(|name| context.scenario(name))
+            .collect()
+    }
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum ScenarioError {
+    #[error("{scenario}: could not find product {product}")]
+    UnknownProduct { scenario: String, product: String },
+    #[error("{scenario}: could not find set {set}")]
+    UnknownSet { scenario: String, set: String },
+    #[error("{scenario}: {set} is not a {expected:?} set")]
+    WrongSetType {
+        scenario: String,
+        set: String,
+        expected: SetType,
+    },
+    #[error("{scenario}: could not find villain stage {stage}")]
+    UnknownStage { scenario: String, stage: String },
+    #[error("{campaign}: {product} is not a campaign expansion")]
+    NotACampaign { campaign: String, product: String },
+    #[error("{campaign}: could not find scenario {scenario} in {product}")]
+    UnknownScenario {
+        campaign: String,
+        scenario: String,
+        product: String,
+    },
+}
+
+/// Checks that scenarios and campaigns only refer to products, sets and stages that exist
+/// The encounter set named `name` in `product`, or in the earliest product when that one doesn't
+/// have it, like `Ctx::set_by_name_in`
+fn set_by_name_in<'a>(
+    products: &'a [Product],
+    product: &str,
+    name: &str,
+) -> Option<&'a ProductSet> {
+    let named = |of: &'a Product| {
+        of.sets
+            .iter()
+            .find(|set| set.r#type.is_named() && set.name == name)
+    };
+
+    products
+        .iter()
+        .find(|of| of.code == product)
+        .and_then(named)
+        .or_else(|| products.iter().find_map(named))
+}
+
+pub fn validate(document: &Document, products: &[Product]) -> Result<(), Vec<ScenarioError>> {
+    let mut errors = vec![];
+    let product = |code: &str| products.iter().find(|product| product.code == code);
+
+    for scenario in document.scenarios.iter() {
+        let name = || scenario.name.clone();
+        if product(&scenario.product).is_none() {
+            errors.push(ScenarioError::UnknownProduct {
+                scenario: name(),
+                product: scenario.product.clone(),
+            });
+        }
+
+        let mut check_set = |set_name: &String, expected: Option<SetType>| match set_by_name_in(
+            products,
+            &scenario.product,
+            set_name,
+        ) {
+            None => errors.push(ScenarioError::UnknownSet {
+                scenario: name(),
+                set: set_name.clone(),
+            }),
+            Some(set) => {
+                if let Some(expected) = expected.filter(|expected| &set.r#type != expected) {
+                    errors.push(ScenarioError::WrongSetType {
+                        scenario: name(),
+                        set: set_name.clone(),
+                        expected,
+                    });
+                }
+            }
+        };
+        check_set(&scenario.villain_set, Some(SetType::Villain));
+        for modular in scenario.recommended_modulars.iter() {
+            check_set(modular, Some(SetType::ModularEncounter));
+        }
+        for difficulty in scenario.difficulties.iter() {
+            for set_name in difficulty.sets.iter() {
+                check_set(set_name, None);
+            }
+        }
+
+        for stage in scenario
+            .difficulties
+            .iter()
+            .flat_map(|difficulty| difficulty.villain_stages.iter())
+        {
+            if !scenario.villain_stages.iter().any(|s| &s.stage == stage) {
+                errors.push(ScenarioError::UnknownStage {
+                    scenario: name(),
+                    stage: stage.clone(),
+                });
+            }
+        }
+    }
+
+    for campaign in document.campaigns.iter() {
+        if product(&campaign.product).map(|product| &product.r#type)
+            != Some(&ProductType::CampaignExpansion)
+        {
+            errors.push(ScenarioError::NotACampaign {
+                campaign: campaign.name.clone(),
+                product: campaign.product.clone(),
+            });
+        }
+        for scenario in campaign.scenarios.iter() {
+            let found = document
+                .scenarios
+                .iter()
+                .any(|s| &s.name == scenario && s.product == campaign.product);
+            if !found {
+                errors.push(ScenarioError::UnknownScenario {
+                    campaign: campaign.name.clone(),
+                    scenario: scenario.clone(),
+                    product: campaign.product.clone(),
+                });
+            }
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::product;
+
+    fn products() -> Vec<Product> {
+        let document: product::Document =
+            toml::from_str(include_str!("../data/products.toml")).unwrap();
+
+        document.products
+    }
+
+    fn document() -> Document {
+        toml::from_str(include_str!("../data/scenarios.toml")).unwrap()
+    }
+
+    #[test]
+    fn it_parses_and_validates_scenarios() {
+        let document = document();
+
+        if let Err(errors) = validate(&document, &products()) {
+            let errors: Vec<_> = errors.iter().map(ToString::to_string).collect();
+            panic!(
+                "Found {} invalid scenarios:\n{}",
+                errors.len(),
+                errors.join("\n")
+            );
+        }
+    }
+
+    #[test]
+    fn it_has_stages_per_difficulty() {
+        let document = document();
+        let rhino = &document.scenarios[0];
+
+        let stages = |level| -> Vec<&str> {
+            rhino
+                .stages(&level)
+                .iter()
+                .map(|stage| stage.stage.as_str())
+                .collect()
+        };
+        assert_eq!(vec!["I", "II"], stages(DifficultyLevel::Standard));
+        assert_eq!(vec!["II", "III"], stages(DifficultyLevel::Expert));
+        assert_eq!(
+            vec![String::from("Standard"), String::from("Expert")],
+            rhino.difficulty(&DifficultyLevel::Expert).unwrap().sets
+        );
+    }
+
+    #[test]
+    fn it_reports_invalid_references() {
+        let mut document = document();
+        let rhino = &mut document.scenarios[0];
+        rhino.product = String::from("MC99en");
+        rhino.villain_set = String::from("Bomb Scare");
+        rhino.recommended_modulars.push(String::from("Nope"));
+        rhino.difficulties[0]
+            .villain_stages
+            .push(String::from("IV"));
+        document.campaigns.push(Campaign {
+            name: String::from("Core"),
+            product: String::from("MC01en"),
+            scenarios: vec![String::from("Rhino")],
+        });
+
+        let errors = validate(&document, &products()).unwrap_err();
+        assert_eq!(
+            vec![
+                ScenarioError::UnknownProduct {
+                    scenario: String::from("Rhino"),
+                    product: String::from("MC99en"),
+                },
+                ScenarioError::WrongSetType {
+                    scenario: String::from("Rhino"),
+                    set: String::from("Bomb Scare"),
+                    expected: SetType::Villain,
+                },
+                ScenarioError::UnknownSet {
+                    scenario: String::from("Rhino"),
+                    set: String::from("Nope"),
+                },
+                ScenarioError::UnknownStage {
+                    scenario: String::from("Rhino"),
+                    stage: String::from("IV"),
+                },
+                ScenarioError::NotACampaign {
+                    campaign: String::from("Core"),
+                    product: String::from("MC01en"),
+                },
+                ScenarioError::UnknownScenario {
+                    campaign: String::from("Core"),
+                    scenario: String::from("Rhino"),
+                    product: String::from("MC01en"),
+                },
+            ],
+            errors
+        );
+    }
+}
@@ -0,0 +1,204 @@
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum StoreError {
+    #[error("Could not read {path}: {source}")]
+    Read { path: PathBuf, source: io::Error },
+    #[error("Invalid data in {path}: {source}")]
+    Parse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    #[error("{path} should only hold [[{key}]] tables")]
+    UnknownKey { path: PathBuf, key: &'static str },
+    #[error("Could not write {path}: {source}")]
+    Write { path: PathBuf, source: io::Error },
+    #[error("Could not serialize records: {0}")]
+    Serialize(#[from] toml::ser::Error),
+}
+
+/// Records kept in a TOML file as `[[key]]` tables, saved after every change. Without a file
+/// they're only kept in memory.
+#[derive(Debug)]
+pub struct Store<T> {
+    key: &'static str,
+    path: Option<PathBuf>,
+    records: Mutex<Vec<T>>,
+    /// Bumped on every change
+    revision: AtomicU64,
+}
+
+impl<T: Clone + DeserializeOwned + Serialize> Store<T> {
+    pub fn in_memory(key: &'static str) -> Self {
+        Self {
+            key,
+            path: None,
+            records: Mutex::new(vec![]),
+            revision: AtomicU64::new(0),
+        }
+    }
+
+    /// Loads the records in `path`, which doesn't need to exist yet
+    pub fn load(key: &'static str, path: impl Into<PathBuf>) -> Result<Self, StoreError> {
+        let path = path.into();
+        let records = match fs::read_to_string(&path) {
+            Ok(source) => {
+                let mut document: BTreeMap<String, Vec<T>> =
+                    toml::from_str(&source).map_err(|source| StoreError::Parse {
+                        path: path.clone(),
+                        source,
+                    })?;
+                let records = document.remove(key).unwrap_or_default();
+                if !document.is_empty() {
+                    return Err(StoreError::UnknownKey { path, key });
+                }
+
+                records
+            }
+            Err(error) if error.kind() == io::ErrorKind::NotFound => vec![],
+            Err(source) => return Err(StoreError::Read { path, source }),
+        };
+
+        Ok(Self {
+            key,
+            path: Some(path),
+            records: Mutex::new(records),
+            revision: AtomicU64::new(0),
+        })
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    pub fn revision(&self) -> u64 {
+        self.revision.load(Ordering::Acquire)
+    }
+
+    pub fn all(&self) -> Vec<T> {
+        self.records.lock().unwrap().clone()
+    }
+
+    pub fn find(&self, predicate: impl Fn(&T) -> bool) -> Option<T> {
+        self.records
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|record| predicate(record))
+            .cloned()
+    }
+
+    /// Applies `change` to a copy of the records, which replaces them once it's saved. The
+    /// records are kept as they were when `change` or saving fails.
+    pub fn change<R, E: From<StoreError>>(
+        &self,
+        change: impl FnOnce(&mut Vec<T>) -> Result<R, E>,
+    ) -> Result<R, E> {
+        let mut records = self.records.lock().unwrap();
+        let mut changed = records.clone();
+        let result = change(&mut changed)?;
+        self.save(&changed)?;
+        *records = changed;
+        self.revision.fetch_add(1, Ordering::AcqRel);
+
+        Ok(result)
+    }
+
+    fn save(&self, records: &[T]) -> Result<(), StoreError> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        // going through a value emits plain values before tables, whatever the field order
+        let source = toml::to_string(&toml::Value::try_from(BTreeMap::from([(
+            self.key, records,
+        )]))?)?;
+        let write_error = |source| StoreError::Write {
+            path: path.clone(),
+            source,
+        };
+        // write to a temporary file first so a failed write doesn't lose the records
+        let temporary = path.with_extension("toml.tmp");
+        fs::write(&temporary, source).map_err(write_error)?;
+        fs::rename(&temporary, path).map_err(write_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    fn path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("shq-store-{}-{name}.toml", std::process::id()))
+    }
+
+    #[test]
+    fn it_saves_and_loads_records() {
+        let path = path("records");
+        let _ = fs::remove_file(&path);
+
+        let record = BTreeMap::from([(String::from("name"), String::from("a"))]);
+        let store: Store<BTreeMap<String, String>> = Store::load("record", &path).unwrap();
+        store
+            .change(|records| {
+                records.push(record.clone());
+                Ok::<_, StoreError>(())
+            })
+            .unwrap();
+        let source = fs::read_to_string(&path).unwrap();
+        let loaded: Store<BTreeMap<String, String>> = Store::load("record", &path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(1, store.revision());
+        assert!(source.starts_with("[[record]]"));
+        assert_eq!(vec![record], loaded.all());
+    }
+
+    #[test]
+    fn it_saves_tables_before_values() {
+        #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+        struct Record {
+            tables: Vec<BTreeMap<String, String>>,
+            value: String,
+        }
+
+        let path = path("order");
+        let _ = fs::remove_file(&path);
+        let record = Record {
+            tables: vec![BTreeMap::from([(String::from("a"), String::from("b"))])],
+            value: String::from("c"),
+        };
+        let store: Store<Record> = Store::load("record", &path).unwrap();
+        store
+            .change(|records| {
+                records.push(record.clone());
+                Ok::<_, StoreError>(())
+            })
+            .unwrap();
+        let loaded: Store<Record> = Store::load("record", &path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(vec![record], loaded.all());
+    }
+
+    #[test]
+    fn it_rejects_other_tables() {
+        let path = path("other");
+        fs::write(&path, "[[other]]\nname = \"a\"\n").unwrap();
+        let store = Store::<BTreeMap<String, String>>::load("record", &path);
+        fs::remove_file(&path).unwrap();
+
+        assert!(matches!(store, Err(StoreError::UnknownKey { .. })));
+    }
+}